@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "game.character",
+        always_run = true,
         name = "角色",
         category = "游戏",
         color = "#4CAF50",
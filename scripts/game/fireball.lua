@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "game.fireball",
+        always_run = true,
         name = "火球术",
         category = "游戏",
         description = "法术技能：消耗法力释放火球，有冷却时间和暴击机制",
@@ -38,7 +39,7 @@ return {
         { id = "cast_event", name = "施法事件", type = "event" }
     },
 
-    execute = function(self, inputs)
+    execute = function(self, inputs, ctx)
         local props = self.properties
         local state = self.state or {}
         local cast = inputs.cast_trigger
@@ -46,12 +47,10 @@ return {
         local caster_mana = inputs.caster_mana or 100
         local spell_power = inputs.spell_power or 0
 
-        -- 获取当前时间（使用简单计数器模拟）
-        local current_tick = (state.tick or 0) + 1
-        local last_cast_tick = state.last_cast_tick or -9999
-        local ticks_since_cast = current_tick - last_cast_tick
-        local cd_ticks = props.cooldown * 10  -- 假设10tick/秒
-        local remaining_cd = math.max(0, cd_ticks - ticks_since_cast) / 10
+        -- 冷却按模拟时间计算
+        local now = ctx.elapsed
+        local last_cast_time = state.last_cast_time or -math.huge
+        local remaining_cd = math.max(0, props.cooldown - (now - last_cast_time))
         local is_ready = remaining_cd <= 0
 
         -- 默认输出
@@ -97,7 +96,7 @@ return {
             result.cast_event = true
 
             -- 更新冷却
-            state.last_cast_tick = current_tick
+            state.last_cast_time = now
             result.is_ready = false
             result.remaining_cd = props.cooldown
         elseif cast and not is_ready then
@@ -107,7 +106,6 @@ return {
         end
 
         -- 保存状态
-        state.tick = current_tick
         self.state = state
 
         return result
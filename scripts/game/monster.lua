@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "game.monster",
+        always_run = true,
         name = "怪物",
         category = "游戏",
         color = "#9C27B0",
@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "game.turn_controller",
+        always_run = true,
         name = "回合控制",
         category = "游戏",
         description = "自动触发回合，控制游戏节奏",
@@ -21,16 +22,17 @@ return {
     },
 
     outputs = {
+        -- 事件负载为回合数
         { id = "turn_event", name = "回合事件", type = "event" },
         { id = "turn_count", name = "回合数", type = "number" },
         { id = "is_running", name = "运行中", type = "boolean" }
     },
 
-    execute = function(self, inputs)
+    execute = function(self, inputs, ctx)
         local props = self.properties
         local state = self.state or {}
 
-        local tick = (state.tick or 0) + 1
+        local tick = ctx.tick
         local turn_count = state.turn_count or 0
         local interval = props.turn_interval or 30
         local paused = inputs.pause or false
@@ -38,19 +40,18 @@ return {
         local turn_event = nil
         local is_running = not paused
 
-        -- 手动触发
+        -- 手动触发（事件只在触发的这次执行中有值）
         if inputs.manual_trigger then
-            turn_event = true
             turn_count = turn_count + 1
+            turn_event = turn_count
             print("[回合] 第 " .. turn_count .. " 回合 (手动)")
         -- 自动触发
         elseif props.auto_turn and not paused and tick % interval == 0 then
-            turn_event = true
             turn_count = turn_count + 1
+            turn_event = turn_count
             print("[回合] 第 " .. turn_count .. " 回合")
         end
 
-        state.tick = tick
         state.turn_count = turn_count
         self.state = state
 
@@ -0,0 +1,26 @@
+-- 子图输入Block
+-- 每个子图输入对应子图实例的一个输入端口，执行时由引擎写入实例收到的值
+
+return {
+    meta = {
+        id = "graph.input",
+        name = "子图输入",
+        category = "子图",
+        description = "子图实例的输入端口，端口名由属性指定",
+        color = "#26A69A"
+    },
+
+    properties = {
+        { id = "port", name = "端口名", type = "string", default = "in" }
+    },
+
+    inputs = {},
+
+    outputs = {
+        { id = "value", name = "值", type = "any" }
+    },
+
+    execute = function(self, inputs)
+        return {}
+    end
+}
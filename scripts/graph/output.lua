@@ -0,0 +1,26 @@
+-- 子图输出Block
+-- 每个子图输出对应子图实例的一个输出端口，连入的值作为实例的输出
+
+return {
+    meta = {
+        id = "graph.output",
+        name = "子图输出",
+        category = "子图",
+        description = "子图实例的输出端口，端口名由属性指定",
+        color = "#26A69A"
+    },
+
+    properties = {
+        { id = "port", name = "端口名", type = "string", default = "out" }
+    },
+
+    inputs = {
+        { id = "value", name = "值", type = "any" }
+    },
+
+    outputs = {},
+
+    execute = function(self, inputs)
+        return {}
+    end
+}
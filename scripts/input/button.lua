@@ -16,33 +16,27 @@ return {
         { id = "button_text", name = "按钮文字", type = "string", default = "点击" }
     },
 
-    inputs = {},
+    inputs = {
+        -- 点击按钮时由界面触发，也可以连接其他事件模拟点击
+        { id = "click", name = "点击", type = "event" }
+    },
 
     outputs = {
-        { id = "clicked", name = "点击事件", type = "event", default = nil },
+        { id = "clicked", name = "点击事件", type = "event" },
         { id = "click_count", name = "点击次数", type = "number", default = 0 }
     },
 
     execute = function(self, inputs)
-        local state = self.state or {}
-        local count = state.click_count or 0
-        local was_checked = state.last_checked or false
-        local is_checked = self.state.widget_checked or false
-        
-        -- 检测点击（从未选中变为选中）
-        local clicked = is_checked and not was_checked
-        if clicked then
+        local count = self.state.click_count or 0
+        if inputs.click then
             count = count + 1
         end
-        
-        state.click_count = count
-        state.last_checked = is_checked
-        self.state = state
-        
+        self.state.click_count = count
+
+        -- 事件只在点击的这一次执行中传递，负载为点击次数
         return {
-            clicked = clicked and true or nil,
+            clicked = inputs.click and count or nil,
             click_count = count
         }
     end
 }
-
@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "lite.boss",
+        always_run = true,
         name = "Boss",
         category = "Lite",
         color = "#E91E63",
@@ -41,21 +42,21 @@ return {
         { id = "counter_event", name = "反击事件", type = "event", default = nil }
     },
 
-    execute = function(self, inputs)
+    execute = function(self, inputs, ctx)
         local props = self.properties
         local state = self.state or {}
         
         local max_hp = props.max_hp
         local hp = state.hp or max_hp
-        local tick = state.tick or 0
+        local tick = ctx.tick
+        -- 每秒的 tick 数
+        local tps = math.max(1, math.floor(1 / ctx.dt + 0.5))
         local dead_ticks = state.dead_ticks or 0
         local total_damage = state.total_damage or 0
         local damage_window = state.damage_window or {}
         
-        tick = tick + 1
-        
         -- 重生逻辑
-        local respawn_ticks = props.respawn_time * 10
+        local respawn_ticks = props.respawn_time * tps
         if hp <= 0 then
             dead_ticks = dead_ticks + 1
             if dead_ticks >= respawn_ticks then
@@ -67,7 +68,6 @@ return {
             end
             state.hp = hp
             state.dead_ticks = dead_ticks
-            state.tick = tick
             self.state = state
             return {
                 hp = hp, max_hp = max_hp, hp_percent = 0,
@@ -106,7 +106,7 @@ return {
         end
         
         -- 计算DPS (最近10秒的伤害)
-        local dps_window = 100 -- 10秒 = 100 ticks
+        local dps_window = 10 * tps
         local recent_damage = 0
         local new_window = {}
         for _, entry in ipairs(damage_window) do
@@ -119,7 +119,7 @@ return {
         local dps_taken = math.floor(recent_damage / 10)
         
         -- 反击逻辑
-        local attack_interval = math.floor(10 / props.attack_speed)
+        local attack_interval = math.max(1, math.floor(tps / props.attack_speed))
         local counter_damage = 0
         local counter_event = nil
         
@@ -130,7 +130,6 @@ return {
         end
         
         state.hp = hp
-        state.tick = tick
         state.dead_ticks = dead_ticks
         state.total_damage = total_damage
         state.damage_window = damage_window
@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "lite.hero",
+        always_run = true,
         name = "英雄",
         category = "Lite",
         color = "#4CAF50",
@@ -51,7 +52,7 @@ return {
         { id = "action_event", name = "行动事件", type = "event", default = nil }
     },
 
-    execute = function(self, inputs)
+    execute = function(self, inputs, ctx)
         local props = self.properties
         local state = self.state or {}
         
@@ -77,11 +78,12 @@ return {
         -- 初始化状态
         local hp = state.hp or max_hp
         local mp = state.mp or max_mp
-        local tick = state.tick or 0
-        tick = tick + 1
+        local tick = ctx.tick
+        -- 每秒的 tick 数
+        local tps = math.max(1, math.floor(1 / ctx.dt + 0.5))
         
-        -- 生命/魔法恢复 (每10tick = 1秒)
-        if tick % 10 == 0 then
+        -- 生命/魔法恢复 (每秒)
+        if tick % tps == 0 then
             hp = math.min(max_hp, hp + props.hp_regen)
             mp = math.min(max_mp, mp + props.mp_regen)
         end
@@ -103,7 +105,7 @@ return {
         end
         
         -- 攻击判定 (根据攻速)
-        local attack_interval = math.floor(10 / props.attack_speed)
+        local attack_interval = math.max(1, math.floor(tps / props.attack_speed))
         local action_event = nil
         local action_damage = 0
         
@@ -124,7 +126,6 @@ return {
         
         state.hp = hp
         state.mp = mp
-        state.tick = tick
         self.state = state
         
         return {
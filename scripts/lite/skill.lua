@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "lite.skill",
+        always_run = true,
         name = "技能",
         category = "Lite",
         color = "#9C27B0",
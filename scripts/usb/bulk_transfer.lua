@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "usb.bulk_transfer",
+        always_run = true,
         name = "USB Bulk 传输",
         category = "USB",
         description = "USB Bulk 读取/写入操作",
@@ -4,6 +4,7 @@
 return {
     meta = {
         id = "usb.control_transfer",
+        always_run = true,
         name = "USB Control 传输",
         category = "USB",
         description = "发送 USB 控制传输请求（Vendor/Class/Standard）",
@@ -5,6 +5,7 @@
 return {
     meta = {
         id = "usb.device_scanner",
+        always_run = true,
         name = "USB 设备扫描",
         category = "USB",
         description = "扫描所有 USB 设备，动态生成每个设备的 VID/PID 输出端口",
@@ -1,8 +1,8 @@
 //! 应用状态
 
-use crate::script::{ScriptRegistry, ScriptWatcher};
-use crate::ui::{BlockWidget, Canvas, ConnectionIndicator, ConnectionMode, ConnectionWidget, LayerEvent, LayerPanel, MenuEvent, PropertyPanel, SideMenu};
-use crate::workflow::{Block, BlockDisplayMode, BlueprintStorage, Clipboard, Connection, GamePackage, Vec2, Workflow, WorkflowExecutor};
+use crate::script::{Capability, ScriptRegistry, ScriptWatcher};
+use crate::ui::{BlockWidget, Canvas, ConnectionIndicator, ConnectionMode, ConnectionWidget, DebugOverlay, DiffOverlay, LayerEvent, LayerPanel, MenuEvent, PropertyPanel, SideMenu};
+use crate::workflow::{instance_of, Block, BlockDisplayMode, Breakpoint, BlueprintStorage, Clipboard, Connection, ExecutorCommand, ExecutorHandle, FixedStep, GamePackage, Interaction, Pause, PublisherKey, SimulationClock, StepMode, Subgraph, Trace, TraceHeader, TraceRecorder, Vec2, Workflow, WorkflowDiff, DEFAULT_DT, TRACE_EXTENSION};
 use anyhow::Result;
 use egui::{CentralPanel, Context, Key, Pos2, SidePanel};
 use std::collections::HashSet;
@@ -38,6 +38,26 @@ struct LogEntry {
     message: String,
 }
 
+/// 正在编辑的子图的上一层
+struct SubgraphFrame {
+    /// 进入子图前的工作流
+    parent: Workflow,
+    subgraph_id: Uuid,
+}
+
+/// 执行录制回放（画布显示录制的数据，不运行 Lua）
+struct Replay {
+    trace: Trace,
+    /// 当前 tick 在录制中的索引
+    index: usize,
+    playing: bool,
+    step: FixedStep,
+    /// 回放前的工作流，退出回放时恢复
+    live: Workflow,
+    /// 回放前是否在自动执行
+    was_running: bool,
+}
+
 /// 撤销/重做历史快照
 #[derive(Clone)]
 struct HistorySnapshot {
@@ -49,7 +69,10 @@ pub struct WorkflowApp {
     registry: ScriptRegistry,
     watcher: Option<ScriptWatcher>,
     workflow: Workflow,
-    executor: WorkflowExecutor,
+    /// 后台执行线程
+    executor: ExecutorHandle,
+    /// 已同步到执行线程的工作流修订号
+    synced_revision: u64,
     clipboard: Clipboard,
     state: InteractionState,
     canvas_rect: egui::Rect,
@@ -57,10 +80,24 @@ pub struct WorkflowApp {
     show_log_panel: bool,
     selected_connections: HashSet<Uuid>,
     box_select_end: Option<Pos2>,
-    last_execute_time: std::time::Instant,
     space_pressed: bool,
     auto_execute: bool,
     execution_speed: f32,
+    /// 每个 tick 的模拟步长（秒）
+    dt: f64,
+    /// 模拟时间缩放
+    time_scale: f64,
+    /// 执行线程最近一次回传的模拟时钟
+    clock: SimulationClock,
+    /// 执行线程正在录制
+    recording: bool,
+    replay: Option<Replay>,
+    /// 执行线程停在断点处
+    paused: Option<Pause>,
+    /// 执行历史中最早和最晚的 tick
+    history: Option<(u64, u64)>,
+    /// 时间线滑块位置
+    timeline_tick: u64,
     // 文件对话框状态
     show_save_dialog: bool,
     show_password_dialog: bool,
@@ -68,6 +105,14 @@ pub struct WorkflowApp {
     pending_operation: Option<FileOperation>,
     current_file_path: Option<std::path::PathBuf>,
     save_options: SaveOptions,
+    /// 上次保存或打开的工作流（差异高亮的基准）
+    saved_workflow: Option<Workflow>,
+    /// 在画布上高亮与上次保存版本的差异
+    show_diff: bool,
+    /// 缓存的差异及计算时的修订号（编辑、撤销、保存后清除）
+    diff_cache: Option<(u64, WorkflowDiff)>,
+    /// 子图编辑层级（为空时编辑顶层工作流）
+    subgraph_stack: Vec<SubgraphFrame>,
     // 流动效果
     flow_phase: f32,
     use_bezier_mode: bool,
@@ -89,6 +134,12 @@ pub struct WorkflowApp {
     // 发布对话框
     show_publish_dialog: bool,
     publish_game_name: String,
+    /// 发布时将在清单中声明的能力
+    publish_capabilities: std::collections::BTreeSet<Capability>,
+    /// 游戏包口令（留空则任何人可打开）
+    publish_passphrase: String,
+    /// 发布者签名密钥文件（None 表示不签名）
+    publish_key_path: Option<std::path::PathBuf>,
 }
 
 /// 右键菜单目标
@@ -129,7 +180,7 @@ impl WorkflowApp {
     pub fn new(script_dir: PathBuf) -> Result<Self> {
         let registry = ScriptRegistry::new(&script_dir)?;
         let watcher = ScriptWatcher::new(&script_dir).ok();
-        let executor = WorkflowExecutor::new()?;
+        let executor = ExecutorHandle::spawn(script_dir.clone())?;
 
         // 收集加载信息
         let mut logs = Vec::new();
@@ -144,11 +195,12 @@ impl WorkflowApp {
             });
         }
 
-        Ok(Self {
+        let app = Self {
             registry,
             watcher,
             workflow: Workflow::new("新工作流"),
             executor,
+            synced_revision: 0,
             clipboard: Clipboard::new(),
             state: InteractionState::Idle,
             canvas_rect: egui::Rect::NOTHING,
@@ -156,16 +208,27 @@ impl WorkflowApp {
             show_log_panel: true,
             selected_connections: HashSet::new(),
             box_select_end: None,
-            last_execute_time: std::time::Instant::now(),
             space_pressed: false,
             auto_execute: true,
             execution_speed: 10.0,
+            dt: DEFAULT_DT,
+            time_scale: 1.0,
+            clock: SimulationClock::default(),
+            recording: false,
+            replay: None,
+            paused: None,
+            history: None,
+            timeline_tick: 0,
             show_save_dialog: false,
             show_password_dialog: false,
             password_input: String::new(),
             pending_operation: None,
             current_file_path: None,
             save_options: SaveOptions::default(),
+            saved_workflow: None,
+            show_diff: false,
+            diff_cache: None,
+            subgraph_stack: Vec::new(),
             flow_phase: 0.0,
             use_bezier_mode: false,
             context_menu_pos: None,
@@ -179,11 +242,21 @@ impl WorkflowApp {
             connection_target_block: None,
             show_publish_dialog: false,
             publish_game_name: String::new(),
-        })
+            publish_capabilities: std::collections::BTreeSet::new(),
+            publish_passphrase: String::new(),
+            publish_key_path: None,
+        };
+
+        app.executor.send(ExecutorCommand::SetSpeed(app.execution_speed));
+        app.executor.send(ExecutorCommand::SetRunning(app.auto_execute));
+        app.executor.send(ExecutorCommand::Load(Box::new(app.workflow.clone())));
+        Ok(app)
     }
 
     /// 保存当前状态到撤销栈
     fn save_undo_snapshot(&mut self) {
+        // 调用方随后会修改工作流
+        self.diff_cache = None;
         // 防止频繁保存（至少间隔100ms）
         if self.last_snapshot_time.elapsed().as_millis() < 100 {
             return;
@@ -219,6 +292,8 @@ impl WorkflowApp {
             // 恢复之前的状态
             if let Ok(workflow) = serde_json::from_str::<Workflow>(&snapshot.workflow_json) {
                 self.workflow = workflow;
+                self.workflow.register_subgraphs(&mut self.registry);
+                self.load_into_executor();
                 self.selected_connections.clear();
                 self.add_log("INFO", "已撤销".to_string());
             }
@@ -245,6 +320,8 @@ impl WorkflowApp {
             // 恢复重做状态
             if let Ok(workflow) = serde_json::from_str::<Workflow>(&snapshot.workflow_json) {
                 self.workflow = workflow;
+                self.workflow.register_subgraphs(&mut self.registry);
+                self.load_into_executor();
                 self.selected_connections.clear();
                 self.add_log("INFO", "已重做".to_string());
             }
@@ -253,17 +330,461 @@ impl WorkflowApp {
         }
     }
 
-    /// 执行工作流（自动调用）
-    fn run_workflow(&mut self) {
-        // 标记所有block为脏，触发执行
-        let all_ids: Vec<Uuid> = self.workflow.blocks.keys().cloned().collect();
-        for id in all_ids {
-            self.workflow.mark_dirty(id);
+    /// 单步推进工作流（Tick 执行完当前 tick，Block 停在下一个Block之前，Continue 执行到下一个断点）
+    fn step_workflow(&mut self, mode: StepMode) {
+        self.sync_workflow();
+        self.executor.send(ExecutorCommand::Step(mode));
+    }
+
+    /// 将编辑后的工作流同步到执行线程（脏Block会立即执行）
+    fn sync_workflow(&mut self) {
+        if self.workflow.revision == self.synced_revision {
+            return;
+        }
+        self.executor.send(ExecutorCommand::Sync(Box::new(self.root_workflow())));
+        self.workflow.dirty_blocks.clear();
+        self.synced_revision = self.workflow.revision;
+    }
+
+    /// 用当前工作流替换执行线程中的工作流（打开文件、撤销/重做后调用）
+    fn load_into_executor(&mut self) {
+        self.diff_cache = None;
+        self.executor.send(ExecutorCommand::Load(Box::new(self.root_workflow())));
+        self.paused = None;
+        self.history = None;
+        self.workflow.dirty_blocks.clear();
+        self.synced_revision = self.workflow.revision;
+    }
+
+    /// 顶层工作流（编辑子图时包含各层尚未写回的修改）
+    fn root_workflow(&self) -> Workflow {
+        let mut workflow = self.workflow.clone();
+        for frame in self.subgraph_stack.iter().rev() {
+            let mut parent = frame.parent.clone();
+            parent.apply_subgraph_view(frame.subgraph_id, workflow);
+            workflow = parent;
+        }
+        workflow
+    }
+
+    /// 应用执行线程回传的快照（编辑子图时写入顶层工作流，回放时丢弃）
+    fn apply_snapshots(&mut self) {
+        let snapshots = self.executor.poll();
+        if self.replay.is_some() {
+            return;
+        }
+        for snapshot in snapshots {
+            if let Some(e) = &snapshot.error {
+                self.add_log("ERROR", format!("执行错误: {}", e));
+            }
+            self.clock = snapshot.clock;
+            self.paused = snapshot.paused.clone();
+            self.history = snapshot.history;
+            let target = match self.subgraph_stack.first_mut() {
+                Some(frame) => &mut frame.parent,
+                None => &mut self.workflow,
+            };
+            snapshot.apply(target, &self.registry);
+        }
+    }
+
+    /// 与上次保存版本的差异（工作流修订号不变时使用缓存）
+    fn current_diff(&mut self) -> Option<WorkflowDiff> {
+        if !self.show_diff || !self.subgraph_stack.is_empty() {
+            return None;
+        }
+        let saved = self.saved_workflow.as_ref()?;
+        match &self.diff_cache {
+            Some((revision, diff)) if *revision == self.workflow.revision => Some(diff.clone()),
+            _ => {
+                let diff = saved.diff(&self.workflow);
+                self.diff_cache = Some((self.workflow.revision, diff.clone()));
+                Some(diff)
+            }
+        }
+    }
+
+    /// 进入子图编辑
+    fn enter_subgraph(&mut self, subgraph_id: Uuid) {
+        let Some(view) = self.workflow.subgraph_view(subgraph_id) else {
+            self.add_log("ERROR", format!("找不到子图: {}", subgraph_id));
+            return;
+        };
+        let name = view.name.clone();
+        let parent = std::mem::replace(&mut self.workflow, view);
+        self.subgraph_stack.push(SubgraphFrame { parent, subgraph_id });
+        self.reset_editing_state();
+        self.synced_revision = self.workflow.revision;
+        self.add_log("INFO", format!("进入子图: {}", name));
+    }
+
+    /// 返回上一层，写回子图的修改
+    fn leave_subgraph(&mut self) {
+        let Some(frame) = self.subgraph_stack.pop() else { return };
+        let view = std::mem::replace(&mut self.workflow, frame.parent);
+        self.workflow.apply_subgraph_view(frame.subgraph_id, view);
+        self.workflow.register_subgraphs(&mut self.registry);
+        self.workflow.update_block_sizes(&self.registry);
+        self.reset_editing_state();
+        if self.subgraph_stack.is_empty() {
+            self.executor.send(ExecutorCommand::Sync(Box::new(self.workflow.clone())));
+            self.workflow.dirty_blocks.clear();
+            self.synced_revision = self.workflow.revision;
+        } else {
+            self.sync_workflow();
+        }
+    }
+
+    /// 返回顶层工作流（保存、发布前调用）
+    fn leave_all_subgraphs(&mut self) {
+        while !self.subgraph_stack.is_empty() {
+            self.leave_subgraph();
+        }
+    }
+
+    /// 开始/结束录制执行过程
+    fn toggle_recording(&mut self) {
+        if self.recording {
+            self.executor.send(ExecutorCommand::StopTrace);
+            self.recording = false;
+            self.add_log("INFO", "录制已结束".to_string());
+            return;
+        }
+
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("执行录制", &[TRACE_EXTENSION])
+            .set_file_name(format!("{}.{}", self.workflow.name, TRACE_EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+        let header = TraceHeader::new(&self.root_workflow(), self.clock);
+        match TraceRecorder::create(&path, &header) {
+            Ok(recorder) => {
+                self.executor.send(ExecutorCommand::StartTrace(Box::new(recorder)));
+                self.recording = true;
+                self.add_log("INFO", format!("开始录制: {}", path.display()));
+            }
+            Err(e) => self.add_log("ERROR", e.to_string()),
+        }
+    }
+
+    /// 打开录制文件进入回放（暂停执行，画布只读）
+    fn open_replay(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("执行录制", &[TRACE_EXTENSION])
+            .pick_file()
+        else {
+            return;
+        };
+        let trace = match Trace::load(&path) {
+            Ok(trace) if !trace.is_empty() => trace,
+            Ok(_) => return self.add_log("WARN", "录制中没有 tick".to_string()),
+            Err(e) => return self.add_log("ERROR", format!("加载录制失败: {}", e)),
+        };
+
+        self.leave_all_subgraphs();
+        let recorded: HashSet<Uuid> = trace.ticks.iter().flat_map(|t| t.blocks.keys().copied()).collect();
+        if !recorded.iter().any(|id| self.workflow.blocks.contains_key(id)) {
+            return self.add_log("ERROR", format!("录制来自工作流「{}」，与当前工作流没有相同的 Block", trace.header.workflow));
+        }
+        if trace.header.workflow != self.workflow.name {
+            self.add_log("WARN", format!("录制来自工作流「{}」", trace.header.workflow));
+        }
+        self.add_log("INFO", format!("回放 {} 个 tick（种子 {}）", trace.len(), trace.header.seed));
+
+        let was_running = self.auto_execute;
+        self.auto_execute = false;
+        self.executor.send(ExecutorCommand::SetRunning(false));
+        let live = self.workflow.clone();
+        self.workflow.readonly = true;
+        self.replay = Some(Replay {
+            trace,
+            index: 0,
+            playing: false,
+            step: FixedStep::new(self.execution_speed),
+            live,
+            was_running,
+        });
+        self.seek_replay(0);
+    }
+
+    /// 跳转到回放的第 index 个 tick
+    fn seek_replay(&mut self, index: usize) {
+        let Some(replay) = &mut self.replay else { return };
+        replay.index = index.min(replay.trace.len() - 1);
+        replay.trace.apply(replay.index, &mut self.workflow);
+    }
+
+    /// 回放播放时按执行速度前进
+    fn advance_replay(&mut self) {
+        let Some(replay) = &mut self.replay else { return };
+        if !replay.playing {
+            return;
+        }
+        let due = replay.step.due() as usize;
+        if due == 0 {
+            return;
+        }
+        let index = replay.index + due;
+        if index + 1 >= replay.trace.len() {
+            replay.playing = false;
+        }
+        self.seek_replay(index);
+    }
+
+    /// 退出回放，恢复回放前的工作流和执行状态
+    fn exit_replay(&mut self) {
+        let Some(replay) = self.replay.take() else { return };
+        self.workflow = replay.live;
+        if replay.was_running {
+            self.auto_execute = true;
+            self.executor.send(ExecutorCommand::SetRunning(true));
+        }
+        self.add_log("INFO", "已退出回放".to_string());
+    }
+
+    /// 断点条件编辑（选中的顶层Block有断点时显示）
+    fn draw_breakpoint_editor(&mut self, ui: &mut egui::Ui, block_id: Uuid) {
+        if !self.subgraph_stack.is_empty() {
+            return;
+        }
+        let Some(mut breakpoint) = self.workflow.blocks.get(&block_id).and_then(|b| b.breakpoint.clone()) else {
+            return;
+        };
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.colored_label(egui::Color32::from_rgb(220, 60, 60), "🔴 断点条件");
+            let edit = ui.add(
+                egui::TextEdit::singleline(&mut breakpoint.condition)
+                    .hint_text("为空时总是暂停，例如 inputs.hp < 10")
+                    .desired_width(240.0),
+            );
+            if edit.on_hover_text("Lua 表达式，可使用 inputs / state / properties / ctx").changed() {
+                self.workflow.set_breakpoint(block_id, Some(breakpoint));
+            }
+        });
+    }
+
+    /// 断点暂停面板：显示暂停Block即将收到的输入、属性和状态
+    fn show_debugger_panel(&mut self, ctx: &Context) {
+        let Some(pause) = &self.paused else { return };
+        let root = self.subgraph_stack.first().map_or(&self.workflow, |frame| &frame.parent);
+        let Some(block) = root.blocks.get(&pause.block_id) else { return };
+        let name = self.registry.get(&block.script_id).map_or(block.script_id.as_str(), |def| block.display_name(def));
+
+        let sections = [("输入", &pause.inputs), ("属性", &block.properties), ("状态", &block.state)];
+        egui::TopBottomPanel::bottom("debugger").resizable(true).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::from_rgb(255, 210, 40), format!("⏸ 暂停在「{}」之前", name));
+                if let Some(error) = &pause.condition_error {
+                    ui.colored_label(egui::Color32::from_rgb(255, 150, 0), format!("⚠ {}", error));
+                }
+            });
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                ui.columns(sections.len(), |columns| {
+                    for (column, (title, values)) in columns.iter_mut().zip(sections) {
+                        column.strong(title);
+                        let mut keys: Vec<&String> = values.keys().collect();
+                        keys.sort();
+                        for key in keys {
+                            column.horizontal_wrapped(|ui| {
+                                ui.label(egui::RichText::new(format!("{}:", key)).size(10.0));
+                                ui.label(egui::RichText::new(Self::format_value_compact(&values[key])).monospace().size(10.0));
+                            });
+                        }
+                    }
+                });
+            });
+        });
+    }
+
+    /// 回到执行历史中的某个 tick（暂停自动执行并结束录制，之后从这里继续）
+    fn rewind_to(&mut self, tick: u64) {
+        if self.recording {
+            self.toggle_recording();
+        }
+        if self.auto_execute {
+            self.auto_execute = false;
+            self.executor.send(ExecutorCommand::SetRunning(false));
+        }
+        self.sync_workflow();
+        self.executor.send(ExecutorCommand::Rewind(tick));
+        self.timeline_tick = tick;
+        self.add_log("INFO", format!("回退到 tick {}", tick));
+    }
+
+    /// 时间线：拖动滑块回到之前的 tick
+    fn show_timeline_bar(&mut self, ui: &mut egui::Ui) {
+        let Some((first, last)) = self.history else { return };
+        let mut target = None;
+        let mut dragging = false;
+
+        ui.horizontal(|ui| {
+            ui.strong("⏪ 时间线");
+            if ui.add_enabled(self.clock.tick > first, egui::Button::new("◀")).on_hover_text("回退一个 tick").clicked() {
+                target = Some(self.clock.tick - 1);
+            }
+            let slider = ui.add(egui::Slider::new(&mut self.timeline_tick, first..=last).show_value(false));
+            dragging = slider.dragged();
+            if slider.drag_stopped() || (slider.changed() && !dragging) {
+                target = Some(self.timeline_tick);
+            }
+            if ui.add_enabled(self.clock.tick < last, egui::Button::new("▶|")).on_hover_text("前进到下一个已保存的 tick").clicked() {
+                target = Some(self.clock.tick + 1);
+            }
+            ui.label(format!("tick {} / {}", self.timeline_tick, last))
+                .on_hover_text(format!("保留最近 {} 个 tick，回退后继续执行会覆盖之后的历史", last - first + 1));
+        });
+
+        match target {
+            Some(tick) => self.rewind_to(tick.clamp(first, last)),
+            None if !dragging => self.timeline_tick = self.clock.tick.clamp(first, last),
+            None => {}
+        }
+    }
+
+    /// 回放控制条
+    fn show_replay_bar(&mut self, ui: &mut egui::Ui) {
+        let Some(replay) = &mut self.replay else { return };
+        let last = replay.trace.len() - 1;
+        let mut index = replay.index;
+        let mut exit = false;
+
+        ui.horizontal(|ui| {
+            ui.strong(format!("📼 回放「{}」", replay.trace.header.workflow));
+            if ui.button("⏮").on_hover_text("回到开头").clicked() {
+                index = 0;
+            }
+            if ui.button("◀").on_hover_text("上一个 tick").clicked() {
+                index = index.saturating_sub(1);
+            }
+            let play_text = if replay.playing { "⏸" } else { "▶" };
+            if ui.button(play_text).clicked() {
+                replay.playing = !replay.playing;
+                if replay.playing {
+                    if index == last {
+                        index = 0;
+                    }
+                    replay.step.set_rate(self.execution_speed);
+                    replay.step.restart();
+                }
+            }
+            if ui.button("▶|").on_hover_text("下一个 tick").clicked() {
+                index = (index + 1).min(last);
+            }
+
+            let record = &replay.trace.ticks[index];
+            let tick_label = format!("tick {} · {:.1}s", record.tick, record.elapsed);
+            ui.add(egui::Slider::new(&mut index, 0..=last).show_value(false));
+            ui.label(tick_label);
+
+            let interactions = &replay.trace.ticks[index].interactions;
+            if !interactions.is_empty() {
+                let details: Vec<String> = interactions.iter()
+                    .map(|interaction| {
+                        let name = self.workflow.blocks.get(&interaction.block_id())
+                            .map(|b| b.custom_name.clone().filter(|n| !n.is_empty()).unwrap_or_else(|| b.script_id.clone()))
+                            .unwrap_or_else(|| interaction.block_id().to_string());
+                        match interaction {
+                            Interaction::SetOutputs { outputs, .. } => format!("{}: 设置 {:?}", name, outputs),
+                            Interaction::FireEvent { port, payload, .. } => format!("{}: {} {:?}", name, port, payload),
+                        }
+                    })
+                    .collect();
+                ui.colored_label(egui::Color32::from_rgb(255, 200, 80), format!("🖱 {} 次交互", interactions.len()))
+                    .on_hover_text(details.join("\n"));
+            }
+
+            if ui.button("✖ 退出回放").clicked() {
+                exit = true;
+            }
+        });
+
+        if exit {
+            self.exit_replay();
+        } else if index != self.replay.as_ref().map_or(index, |r| r.index) {
+            self.seek_replay(index);
+        }
+    }
+
+    /// 切换编辑层级后清理选择和撤销历史（历史只对应单一层级）
+    fn reset_editing_state(&mut self) {
+        self.diff_cache = None;
+        self.state = InteractionState::Idle;
+        self.selected_connections.clear();
+        self.context_menu_pos = None;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// 将选中的Block折叠为子图
+    fn collapse_selected(&mut self) {
+        let selected: HashSet<Uuid> = self.workflow.selected_blocks().into_iter().collect();
+        if selected.is_empty() {
+            return;
+        }
+        self.save_undo_snapshot();
+        let name = format!("子图{}", self.workflow.subgraphs.len() + 1);
+        match self.workflow.collapse_to_subgraph(&selected, &name, &self.registry) {
+            Ok(_) => {
+                self.workflow.register_subgraphs(&mut self.registry);
+                self.selected_connections.clear();
+                self.add_log("INFO", format!("已折叠 {} 个Block为{}", selected.len(), name));
+            }
+            Err(e) => self.add_log("ERROR", format!("折叠失败: {}", e)),
+        }
+    }
+
+    /// 引用另一个 .L 文件作为子图，在 position 处放置实例
+    fn reference_subgraph_file(&mut self, position: Vec2) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("明文蓝图", &["L", "l"])
+            .set_directory(std::env::current_dir().unwrap_or_default())
+            .pick_file()
+        else {
+            return;
+        };
+
+        let referenced = match BlueprintStorage::load(&path, None) {
+            Ok(wf) if wf.has_password() => {
+                self.add_log("ERROR", "引用失败: 文件有打开密码".to_string());
+                return;
+            }
+            Ok(wf) => wf,
+            Err(e) => {
+                self.add_log("ERROR", format!("引用失败: {}", e));
+                return;
+            }
+        };
+
+        // 路径相对于当前文件保存
+        let base = self.current_file_path.as_deref().and_then(|p| p.parent());
+        let source = base
+            .and_then(|dir| path.strip_prefix(dir).ok())
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+
+        self.save_undo_snapshot();
+        let mut subgraph = Subgraph::new(referenced.name.clone());
+        subgraph.source = Some(source.clone());
+        subgraph.blocks = referenced.blocks;
+        subgraph.connections = referenced.connections;
+        for (id, nested) in referenced.subgraphs {
+            self.workflow.subgraphs.entry(id).or_insert(nested);
         }
+        let script_id = subgraph.script_id();
+        self.workflow.subgraphs.insert(subgraph.id, subgraph);
+        self.workflow.register_subgraphs(&mut self.registry);
 
-        if let Err(e) = self.executor.execute_dirty(&mut self.workflow, &self.registry) {
-            self.add_log("ERROR", format!("执行错误: {}", e));
+        if let Some(definition) = self.registry.get(&script_id) {
+            let block = Block::new(definition, position);
+            let id = self.workflow.add_block(block);
+            self.workflow.mark_dirty(id);
         }
+        self.add_log("INFO", format!("已引用子图: {}", source));
     }
 
     /// 添加日志
@@ -400,8 +921,13 @@ impl WorkflowApp {
             let changed = watcher.poll_changes();
             for path in changed {
                 log::info!("热重载: {}", path.display());
-                if let Err(e) = self.registry.reload_script(&path) {
-                    log::error!("重载失败: {}", e);
+                match self.registry.reload_script(&path) {
+                    Ok(script_id) => {
+                        self.executor.send(ExecutorCommand::ReloadScript(path.clone()));
+                        // 脚本修改后重新尝试执行故障Block
+                        self.workflow.clear_faults(Some(&script_id));
+                    }
+                    Err(e) => log::error!("重载失败: {}", e),
                 }
             }
         }
@@ -420,6 +946,7 @@ impl WorkflowApp {
                 if !new_name.trim().is_empty() {
                     if let Some(layer) = self.workflow.layers.get_mut(index) {
                         layer.name = new_name;
+                        self.diff_cache = None;
                     }
                 }
                 self.editing_layer = None;
@@ -523,15 +1050,18 @@ impl WorkflowApp {
 impl eframe::App for WorkflowApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
         self.handle_hot_reload();
-        self.handle_shortcuts(ctx);
+        // 回放时画布只读，不处理编辑快捷键
+        if self.replay.is_none() {
+            self.handle_shortcuts(ctx);
+        }
 
         // 更新流动效果
         if self.auto_execute {
             self.flow_phase = (self.flow_phase + 0.02) % 1.0;
         }
 
-        // 更新Block动画（假设60fps，delta_time ≈ 0.016秒）
-        let delta_time = 1.0 / 60.0;
+        // 更新Block动画（按实际帧间隔）
+        let delta_time = ctx.input(|i| i.stable_dt);
         for block in self.workflow.blocks.values_mut() {
             block.update_animation(delta_time);
         }
@@ -543,17 +1073,15 @@ impl eframe::App for WorkflowApp {
             ConnectionMode::Orthogonal
         });
 
-        // 自动执行工作流（根据速度设置）
-        let interval = 1.0 / self.execution_speed.max(0.1);
-        if self.auto_execute && self.last_execute_time.elapsed().as_secs_f32() >= interval {
-            self.last_execute_time = std::time::Instant::now();
-            if !self.workflow.blocks.is_empty() {
-                self.run_workflow();
-            }
-        }
+        // 自动执行由后台线程按速度设置驱动，这里只取回结果
+        self.apply_snapshots();
+        self.advance_replay();
         // 请求持续重绘
         ctx.request_repaint();
 
+        // 与上次保存版本的差异（编辑子图时不显示）
+        let diff = self.current_diff();
+
         // 顶部工具栏
         egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -569,23 +1097,75 @@ impl eframe::App for WorkflowApp {
                     self.save_options = SaveOptions::default();
                 }
                 if ui.button("📦 发布").clicked() {
+                    self.leave_all_subgraphs();
                     self.show_publish_dialog = true;
                     self.publish_game_name = self.workflow.name.clone();
+                    self.publish_capabilities = GamePackage::from_workflow(&self.workflow, &self.registry, "", "")
+                        .map(|package| package.capabilities)
+                        .unwrap_or_default();
                 }
 
                 ui.separator();
 
-                // 执行控制
-                let play_text = if self.auto_execute { "⏸ 暂停" } else { "▶ 运行" };
-                if ui.button(play_text).clicked() {
-                    self.auto_execute = !self.auto_execute;
-                }
+                // 执行控制（回放时停用）
+                ui.add_enabled_ui(self.replay.is_none(), |ui| {
+                    let play_text = if self.auto_execute { "⏸ 暂停" } else { "▶ 运行" };
+                    if ui.button(play_text).clicked() {
+                        self.auto_execute = !self.auto_execute;
+                        if self.auto_execute {
+                            self.workflow.clear_faults(None);
+                            self.executor.send(ExecutorCommand::ClearFaults(None));
+                        }
+                        self.executor.send(ExecutorCommand::SetRunning(self.auto_execute));
+                    }
+
+                    // 断点调试
+                    if ui.button("⤵ 下一个Block").on_hover_text("停在下一个要执行的 Block 之前").clicked() {
+                        self.step_workflow(StepMode::Block);
+                    }
+                    if ui.button("⏯ 单步").on_hover_text("执行完当前 tick（忽略断点）").clicked() {
+                        self.step_workflow(StepMode::Tick);
+                    }
+                    if ui.add_enabled(self.paused.is_some(), egui::Button::new("⏵ 继续")).on_hover_text("执行到下一个断点").clicked() {
+                        self.step_workflow(StepMode::Continue);
+                    }
 
-                ui.label("速度:");
-                ui.add(egui::Slider::new(&mut self.execution_speed, 1.0..=60.0).suffix(" Hz"));
+                    ui.label("速度:");
+                    if ui.add(egui::Slider::new(&mut self.execution_speed, 1.0..=60.0).suffix(" Hz")).changed() {
+                        self.executor.send(ExecutorCommand::SetSpeed(self.execution_speed));
+                    }
+
+                    // 模拟步长和时间缩放，只影响脚本看到的 dt / elapsed
+                    let dt = ui.add(egui::DragValue::new(&mut self.dt).range(0.001..=1.0).speed(0.005).suffix(" s"));
+                    if dt.on_hover_text("步长：每个 tick 前进的模拟时间").changed() {
+                        self.executor.send(ExecutorCommand::SetDt(self.dt));
+                    }
+                    let scale = ui.add(egui::DragValue::new(&mut self.time_scale).range(0.0..=10.0).speed(0.05).prefix("×"));
+                    if scale.on_hover_text("时间缩放：脚本 ctx.dt = 步长 × 缩放").changed() {
+                        self.executor.send(ExecutorCommand::SetTimeScale(self.time_scale));
+                    }
+                    ui.label(format!("tick {} · {:.1}s", self.clock.tick, self.clock.elapsed));
+
+                    // 随机数种子保存在顶层工作流中（编辑子图时同样生效）
+                    let mut seed = self.subgraph_stack.first().map_or(self.workflow.seed, |frame| frame.parent.seed);
+                    let seed_edit = ui.add(egui::DragValue::new(&mut seed).prefix("🎲 "));
+                    if seed_edit.on_hover_text("随机数种子：相同种子下 math.random 的结果相同").changed() {
+                        self.save_undo_snapshot();
+                        match self.subgraph_stack.first_mut() {
+                            Some(frame) => frame.parent.seed = seed,
+                            None => self.workflow.seed = seed,
+                        }
+                        self.workflow.revision += 1;
+                    }
 
-                if ui.button("⏯ 单步").clicked() {
-                    self.run_workflow();
+                    // 录制执行过程
+                    let record_text = if self.recording { "⏹ 停止录制" } else { "⏺ 录制" };
+                    if ui.button(record_text).on_hover_text("录制每个 tick 的输入、输出、状态和控件交互").clicked() {
+                        self.toggle_recording();
+                    }
+                });
+                if ui.add_enabled(self.replay.is_none(), egui::Button::new("📼 回放")).on_hover_text("打开录制文件，在画布上逐 tick 回放").clicked() {
+                    self.open_replay();
                 }
 
                 ui.separator();
@@ -617,6 +1197,19 @@ impl eframe::App for WorkflowApp {
                     self.add_log("INFO", "已自动布局".to_string());
                 }
 
+                // 差异高亮（对比上次保存的文件）
+                let diff_button = ui.add_enabled(
+                    self.saved_workflow.is_some(),
+                    egui::SelectableLabel::new(self.show_diff, "± 差异"),
+                );
+                if diff_button.on_hover_text("高亮与上次保存版本的差异").clicked() {
+                    self.show_diff = !self.show_diff;
+                }
+                if let Some(diff) = &diff {
+                    let (added, removed, modified) = diff.counts();
+                    ui.label(format!("+{} -{} ~{}", added, removed, modified));
+                }
+
                 // 显示/隐藏日志
                 let log_text = if self.show_log_panel { "📋" } else { "📋 输出" };
                 if ui.button(log_text).clicked() {
@@ -625,6 +1218,19 @@ impl eframe::App for WorkflowApp {
 
                 ui.separator();
 
+                // 子图层级
+                if !self.subgraph_stack.is_empty() {
+                    let path: Vec<&str> = self.subgraph_stack.iter()
+                        .map(|frame| frame.parent.name.as_str())
+                        .chain(std::iter::once(self.workflow.name.as_str()))
+                        .collect();
+                    ui.label(format!("📦 {}", path.join(" › ")));
+                    if ui.button("⬆ 返回上层").clicked() {
+                        self.leave_subgraph();
+                    }
+                    ui.separator();
+                }
+
                 // 只读模式提示
                 if self.workflow.readonly {
                     ui.colored_label(egui::Color32::from_rgb(255, 100, 100), "🔒 只读模式");
@@ -637,6 +1243,24 @@ impl eframe::App for WorkflowApp {
 
                 ui.label(format!("Blocks: {}", self.workflow.blocks.len()));
 
+                // 循环依赖提示（点击选中循环中的Block）
+                if !self.workflow.cycles.is_empty() {
+                    let blocks: usize = self.workflow.cycles.iter().map(Vec::len).sum();
+                    let label = ui.colored_label(
+                        egui::Color32::from_rgb(255, 180, 60),
+                        format!("⚠ {} 个循环", self.workflow.cycles.len()),
+                    );
+                    let hover = format!("{} 个Block处于循环依赖中，循环内按 id 顺序执行。\n右键其中一条连线「⏱ 设为反馈连线」可读取上一 tick 的值", blocks);
+                    if label.on_hover_text(hover).interact(egui::Sense::click()).clicked() {
+                        self.workflow.clear_selection();
+                        for id in self.workflow.cycles.concat() {
+                            if let Some(block) = self.workflow.blocks.get_mut(&id) {
+                                block.selected = true;
+                            }
+                        }
+                    }
+                }
+
                 if !self.selected_connections.is_empty() {
                     ui.separator();
                     let count = self.selected_connections.len();
@@ -652,6 +1276,13 @@ impl eframe::App for WorkflowApp {
             });
         });
 
+        // 回放控制条
+        if self.replay.is_some() {
+            egui::TopBottomPanel::top("replay").show(ctx, |ui| self.show_replay_bar(ui));
+        } else if self.history.is_some() {
+            egui::TopBottomPanel::top("timeline").show(ctx, |ui| self.show_timeline_bar(ui));
+        }
+
         // 对话框
         self.draw_save_dialog(ctx);
         self.draw_password_dialog(ctx);
@@ -678,6 +1309,9 @@ impl eframe::App for WorkflowApp {
             }
         });
 
+        // 断点暂停面板
+        self.show_debugger_panel(ctx);
+
         // 底部属性面板（先绘制，这样右侧面板可以占据剩余全高）
         egui::TopBottomPanel::bottom("properties")
             .resizable(true)
@@ -694,9 +1328,11 @@ impl eframe::App for WorkflowApp {
                                     for change in changes {
                                         block.properties.insert(change.property_id, change.new_value);
                                     }
+                                    block.fault = None;
                                 }
                                 self.workflow.mark_dirty(block_id);
                             }
+                            self.draw_breakpoint_editor(ui, selected[0]);
                         }
                     }
                 } else {
@@ -738,6 +1374,24 @@ impl eframe::App for WorkflowApp {
                                         .default_open(true)
                                         .show(ui, |ui| {
                                             ui.spacing_mut().item_spacing.y = 2.0;
+                                            if let Some(fault) = &block.fault {
+                                                ui.colored_label(
+                                                    egui::Color32::from_rgb(255, 150, 0),
+                                                    egui::RichText::new(format!("⚠ {}", fault)).size(10.0)
+                                                );
+                                            }
+                                            if !block.waiting_for.is_empty() {
+                                                ui.colored_label(
+                                                    egui::Color32::from_rgb(150, 180, 255),
+                                                    egui::RichText::new(format!("⏳ 等待输入: {}", block.waiting_for.join(", "))).size(10.0)
+                                                );
+                                            }
+                                            for warning in &block.warnings {
+                                                ui.colored_label(
+                                                    egui::Color32::from_rgb(255, 220, 100),
+                                                    egui::RichText::new(format!("! {}", warning)).size(10.0)
+                                                );
+                                            }
                                             let all_outputs = block.all_outputs(def);
                                             for output in all_outputs {
                                                 if let Some(value) = block.output_values.get(&output.id) {
@@ -884,6 +1538,9 @@ impl eframe::App for WorkflowApp {
                                 let is_selected = self.selected_connections.contains(conn_id);
                                 let activation = self.workflow.get_connection_activation(*conn_id);
                                 ConnectionWidget::draw_with_flow(&painter, from_pos, to_pos, is_selected, activation);
+                                if conn.delayed {
+                                    ConnectionWidget::draw_feedback_marker(&painter, from_pos, to_pos);
+                                }
                             }
                         }
                     }
@@ -916,6 +1573,9 @@ impl eframe::App for WorkflowApp {
                             let is_selected = self.selected_connections.contains(conn_id);
                             let activation = self.workflow.get_connection_activation(*conn_id);
                             ConnectionWidget::draw_with_flow(&painter, from_pos, to_pos, is_selected, activation);
+                            if conn.delayed {
+                                ConnectionWidget::draw_feedback_marker(&painter, from_pos, to_pos);
+                            }
                         }
                     }
                 }
@@ -961,6 +1621,16 @@ impl eframe::App for WorkflowApp {
                 }
             }
 
+            // 差异高亮
+            if let (Some(diff), Some(saved)) = (&diff, &self.saved_workflow) {
+                DiffOverlay::draw(&painter, diff, saved, &self.workflow, &self.registry, canvas_offset);
+            }
+
+            // 断点和暂停位置（断点只在顶层工作流生效）
+            if self.subgraph_stack.is_empty() {
+                DebugOverlay::draw(&painter, &self.workflow, self.paused.as_ref(), canvas_offset);
+            }
+
             // 渲染可交互Block的控件（输入框、密码框等）
             self.render_interactive_widgets(&response.ctx, canvas_offset);
 
@@ -1023,12 +1693,8 @@ impl eframe::App for WorkflowApp {
             // 处理交互
             self.handle_canvas_interaction(&response, canvas_offset);
 
-            // 执行脏Block
-            if !self.workflow.dirty_blocks.is_empty() {
-                if let Err(e) = self.executor.execute_dirty(&mut self.workflow, &self.registry) {
-                    log::error!("执行错误: {}", e);
-                }
-            }
+            // 同步编辑到执行线程（脏Block在后台执行）
+            self.sync_workflow();
 
             // 衰减激活状态（每帧调用，约60fps时0.05表示约20帧淡出）
             self.workflow.decay_activation(0.03);
@@ -1062,7 +1728,15 @@ impl WorkflowApp {
                     let readonly = self.workflow.readonly;
 
                     match target {
-                        ContextMenuTarget::Block(_) => {
+                        ContextMenuTarget::Block(block_id) => {
+                            let subgraph_id = self.workflow.blocks.get(&block_id).and_then(|b| instance_of(&b.script_id));
+                            if let Some(subgraph_id) = subgraph_id {
+                                if ui.button("📂 进入子图").clicked() {
+                                    self.context_menu_pos = None;
+                                    self.enter_subgraph(subgraph_id);
+                                    return;
+                                }
+                            }
                             if ui.button("📋 复制 (Ctrl+C)").clicked() {
                                 self.copy_selected();
                                 self.context_menu_pos = None;
@@ -1072,6 +1746,22 @@ impl WorkflowApp {
                                     self.paste_at_cursor();
                                     self.context_menu_pos = None;
                                 }
+                                if self.subgraph_stack.is_empty() {
+                                    let has_breakpoint = self.workflow.blocks.get(&block_id).is_some_and(|b| b.breakpoint.is_some());
+                                    let text = if has_breakpoint { "⭕ 移除断点" } else { "🔴 设置断点" };
+                                    if ui.button(text).on_hover_text("执行到该 Block 之前暂停").clicked() {
+                                        let breakpoint = (!has_breakpoint).then(Breakpoint::default);
+                                        self.workflow.set_breakpoint(block_id, breakpoint);
+                                        self.context_menu_pos = None;
+                                    }
+                                }
+                                if ui.button("📦 折叠为子图").clicked() {
+                                    if let Some(block) = self.workflow.blocks.get_mut(&block_id) {
+                                        block.selected = true;
+                                    }
+                                    self.collapse_selected();
+                                    self.context_menu_pos = None;
+                                }
                                 ui.separator();
                                 if ui.button("🗑 删除 (Delete)").clicked() {
                                     self.delete_selected();
@@ -1079,8 +1769,15 @@ impl WorkflowApp {
                                 }
                             }
                         }
-                        ContextMenuTarget::Connection(_) => {
+                        ContextMenuTarget::Connection(conn_id) => {
                             if !readonly {
+                                let delayed = self.workflow.connections.get(&conn_id).is_some_and(|c| c.delayed);
+                                let text = if delayed { "➡ 取消反馈连线" } else { "⏱ 设为反馈连线" };
+                                if ui.button(text).on_hover_text("反馈连线读取上一 tick 的值，用于打破循环").clicked() {
+                                    self.save_undo_snapshot();
+                                    self.workflow.set_connection_delayed(conn_id, !delayed);
+                                    self.context_menu_pos = None;
+                                }
                                 if ui.button("🗑 删除连线").clicked() {
                                     self.delete_selected();
                                     self.context_menu_pos = None;
@@ -1095,6 +1792,11 @@ impl WorkflowApp {
                                     self.paste_at_cursor();
                                     self.context_menu_pos = None;
                                 }
+                                if ui.button("📎 引用子图文件").clicked() {
+                                    let position = Canvas::pos2_to_vec2(menu_pos, &self.workflow.viewport, self.canvas_rect.min);
+                                    self.context_menu_pos = None;
+                                    self.reference_subgraph_file(position);
+                                }
                             }
                             if ui.button("🔍 全选 (Ctrl+A)").clicked() {
                                 for block in self.workflow.blocks.values_mut() {
@@ -1148,7 +1850,9 @@ impl WorkflowApp {
             self.workflow.add_block(block);
         }
         for conn in connections {
-            self.workflow.add_connection(conn);
+            if let Err(e) = self.workflow.add_connection(conn, &self.registry) {
+                self.add_log("WARN", format!("跳过连线: {}", e));
+            }
         }
         if count > 0 {
             self.add_log("INFO", format!("已粘贴 {} 个Block", count));
@@ -1187,6 +1891,7 @@ impl WorkflowApp {
 
     /// 处理图层事件
     fn handle_layer_event(&mut self, event: LayerEvent) {
+        self.diff_cache = None;
         match event {
             LayerEvent::GotoLayer(index) => {
                 self.workflow.goto_layer(index);
@@ -1496,8 +2201,20 @@ impl WorkflowApp {
             }
         }
 
+        // 双击子图实例的主体 - 进入子图
+        let mut entered = false;
+        if response.double_clicked_by(egui::PointerButton::Primary) {
+            let subgraph_id = self.workflow.blocks.values()
+                .find(|b| b.contains(canvas_pos) && canvas_pos.y > b.position.y + 28.0)
+                .and_then(|b| instance_of(&b.script_id));
+            if let Some(subgraph_id) = subgraph_id {
+                self.enter_subgraph(subgraph_id);
+                entered = true;
+            }
+        }
+
         // 双击Block名称 - 开始编辑（只读模式禁止）
-        if response.double_clicked_by(egui::PointerButton::Primary) && !self.workflow.readonly {
+        if response.double_clicked_by(egui::PointerButton::Primary) && !entered && !self.workflow.readonly {
             // 检测Block碰撞
             for (id, block) in &self.workflow.blocks {
                 if block.contains(canvas_pos) {
@@ -1666,31 +2383,35 @@ impl WorkflowApp {
                     if self.workflow.readonly {
                         self.add_log("WARN", "只读模式，无法创建连线".to_string());
                     } else if let Some(to_port) = self.find_port_at(pointer_pos, canvas_offset) {
-                        let mut log_msg: Option<String> = None;
                         // 确保连接方向正确：output -> input
-                        if from.is_output && !to_port.is_output && from.block_id != to_port.block_id {
-                            self.save_undo_snapshot();
-                            let conn = Connection::new(
+                        let conn = if from.is_output && !to_port.is_output && from.block_id != to_port.block_id {
+                            Some(Connection::new(
                                 from.block_id,
                                 from.port_id.clone(),
                                 to_port.block_id,
                                 to_port.port_id.clone(),
-                            );
-                            self.workflow.add_connection(conn);
-                            log_msg = Some(format!("连接: {} -> {}", from.port_id, to_port.port_id));
+                            ))
                         } else if !from.is_output && to_port.is_output && from.block_id != to_port.block_id {
-                            self.save_undo_snapshot();
-                            let conn = Connection::new(
+                            Some(Connection::new(
                                 to_port.block_id,
                                 to_port.port_id.clone(),
                                 from.block_id,
                                 from.port_id.clone(),
-                            );
-                            self.workflow.add_connection(conn);
-                            log_msg = Some(format!("连接: {} -> {}", to_port.port_id, from.port_id));
-                        }
-                        if let Some(msg) = log_msg {
-                            self.add_log("INFO", msg);
+                            ))
+                        } else {
+                            None
+                        };
+                        if let Some(conn) = conn {
+                            match conn.validate(&self.workflow.blocks, &self.registry) {
+                                Ok(()) => {
+                                    self.save_undo_snapshot();
+                                    let msg = format!("连接: {} -> {}", conn.from_port, conn.to_port);
+                                    if self.workflow.add_connection(conn, &self.registry).is_ok() {
+                                        self.add_log("INFO", msg);
+                                    }
+                                }
+                                Err(e) => self.add_log("WARN", format!("无法连接: {}", e)),
+                            }
                         }
                     }
                 }
@@ -1808,12 +2529,45 @@ impl WorkflowApp {
 
     /// 加载工作流文件
     fn load_workflow_file(&mut self, path: &std::path::Path, password: Option<&str>) {
+        self.exit_replay();
         match BlueprintStorage::load(path, password) {
             Ok(mut wf) => {
+                let legacy_password = wf.has_legacy_password();
+                if wf.has_password() {
+                    match password {
+                        None => {
+                            self.pending_operation = Some(FileOperation::Load(path.to_path_buf()));
+                            self.show_password_dialog = true;
+                            return;
+                        }
+                        Some(p) if !wf.unlock(p) => {
+                            self.add_log("ERROR", "加载失败: 密码错误".to_string());
+                            return;
+                        }
+                        Some(_) => {}
+                    }
+                }
                 wf.update_execution_order();
+                self.subgraph_stack.clear();
+                self.reset_editing_state();
+                wf.register_subgraphs(&mut self.registry);
                 self.workflow = wf;
+                self.load_into_executor();
                 self.add_log("INFO", format!("已加载: {}", path.display()));
+                for (_, e) in self.workflow.validate(&self.registry) {
+                    self.add_log("WARN", format!("连线校验: {}", e));
+                }
                 self.current_file_path = Some(path.to_path_buf());
+                self.saved_workflow = Some(self.workflow.clone());
+                self.diff_cache = None;
+
+                // 旧版密码哈希已在 unlock 中升级，写回文件，否则下次打开仍是旧哈希
+                if legacy_password {
+                    match BlueprintStorage::save(&self.workflow, path, password) {
+                        Ok(()) => self.add_log("INFO", "打开密码已升级为 Argon2id 并写回文件".to_string()),
+                        Err(e) => self.add_log("WARN", format!("打开密码升级未能写回文件: {}", e)),
+                    }
+                }
             }
             Err(e) => {
                 self.add_log("ERROR", format!("加载失败: {}", e));
@@ -1848,6 +2602,9 @@ impl WorkflowApp {
 
     /// 保存工作流文件
     fn save_workflow_file(&mut self, path: &std::path::Path, password: Option<&str>) {
+        // 保存回放前的工作流，而不是回放中的运行时数据
+        self.exit_replay();
+        self.leave_all_subgraphs();
         let mut workflow = self.workflow.clone();
         workflow.readonly = self.save_options.readonly;
 
@@ -1858,6 +2615,8 @@ impl WorkflowApp {
                     self.add_log("INFO", format!("可编辑: {}", edit_path.display()));
                     self.add_log("INFO", format!("可分发: {}", dist_path.display()));
                     self.current_file_path = Some(edit_path);
+                    self.saved_workflow = Some(self.workflow.clone());
+                    self.diff_cache = None;
                 }
                 Err(e) => self.add_log("ERROR", format!("保存失败: {}", e)),
             }
@@ -1866,6 +2625,8 @@ impl WorkflowApp {
                 Ok(()) => {
                     self.add_log("INFO", format!("已保存: {}", path.display()));
                     self.current_file_path = Some(path.to_path_buf());
+                    self.saved_workflow = Some(self.workflow.clone());
+                    self.diff_cache = None;
                 }
                 Err(e) => self.add_log("ERROR", format!("保存失败: {}", e)),
             }
@@ -1983,12 +2744,44 @@ impl WorkflowApp {
                     ui.label("游戏名称:");
                     ui.text_edit_singleline(&mut self.publish_game_name);
                 });
+                ui.horizontal(|ui| {
+                    ui.label("游戏口令:");
+                    ui.add(egui::TextEdit::singleline(&mut self.publish_passphrase).password(true));
+                });
+                if self.publish_passphrase.is_empty() {
+                    ui.label(egui::RichText::new("留空则任何人都能打开游戏包（仍可防篡改）").weak());
+                }
+                ui.horizontal(|ui| {
+                    ui.label("签名密钥:");
+                    match &self.publish_key_path {
+                        Some(path) => ui.label(path.file_name().unwrap_or_default().to_string_lossy()),
+                        None => ui.label(egui::RichText::new("不签名").weak()),
+                    };
+                    if ui.small_button("选择...").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("发布者密钥", &["json"]).pick_file() {
+                            self.publish_key_path = Some(path);
+                        }
+                    }
+                    if self.publish_key_path.is_some() && ui.small_button("清除").clicked() {
+                        self.publish_key_path = None;
+                    }
+                });
 
                 ui.add_space(8.0);
                 ui.label("发布后将创建发布文件夹，包含：");
                 ui.label("  • 播放器可执行文件");
                 ui.label("  • 加密的游戏数据包 (.lpack)");
 
+                ui.add_space(8.0);
+                if self.publish_capabilities.is_empty() {
+                    ui.label("脚本不需要额外权限，播放器将在沙箱中运行");
+                } else {
+                    ui.label("脚本需要以下权限（播放器运行前会请求用户确认）：");
+                    for capability in &self.publish_capabilities {
+                        ui.colored_label(egui::Color32::from_rgb(255, 180, 80), format!("  • {}", capability.label()));
+                    }
+                }
+
                 ui.add_space(12.0);
                 ui.separator();
                 ui.add_space(8.0);
@@ -2015,6 +2808,7 @@ impl WorkflowApp {
             .pick_folder();
 
         let Some(base_dir) = folder else { return };
+        self.leave_all_subgraphs();
 
         // 创建发布文件夹: {目录}/{游戏名}_publish/
         let publish_dir = base_dir.join(format!("{}_publish", self.publish_game_name));
@@ -2023,6 +2817,15 @@ impl WorkflowApp {
             return;
         }
 
+        // 签名密钥
+        let signer = match self.publish_key_path.as_deref().map(PublisherKey::load).transpose() {
+            Ok(signer) => signer,
+            Err(e) => {
+                self.add_log("ERROR", format!("加载签名密钥失败: {}", e));
+                return;
+            }
+        };
+
         // 1. 复制播放器
         let player_copied = self.copy_player_to(&publish_dir);
 
@@ -2035,9 +2838,13 @@ impl WorkflowApp {
             "1.0.0",
         ) {
             Ok(package) => {
-                match package.save(&game_path) {
+                match package.save(&game_path, &self.publish_passphrase, signer.as_ref()) {
                     Ok(()) => {
                         self.add_log("INFO", format!("游戏包: {}", game_path.display()));
+                        match &signer {
+                            Some(key) => self.add_log("INFO", format!("已签名: {} ({})", key.name, key.public_key_hex())),
+                            None => self.add_log("WARN", "游戏包未签名，播放器将提示来源未知".to_string()),
+                        }
                     }
                     Err(e) => {
                         self.add_log("ERROR", format!("保存游戏包失败: {}", e));
@@ -2195,12 +3002,15 @@ impl WorkflowApp {
                             resp.changed()
                         }
                         WidgetType::Button => {
+                            // 点击作为事件投递到按钮的 click 端口，不修改控件状态
                             if ui.button(&placeholder).clicked() {
-                                checked = true;
-                                true
-                            } else {
-                                false
+                                self.executor.send(ExecutorCommand::FireEvent {
+                                    block_id,
+                                    port: "click".to_string(),
+                                    payload: crate::script::Value::Boolean(true),
+                                });
                             }
+                            false
                         }
                         _ => false,
                     };
@@ -2218,7 +3028,13 @@ impl WorkflowApp {
                                 crate::script::Value::Boolean(block.widget_checked));
                             block.output_values.insert("slider".to_string(),
                                 crate::script::Value::Number(block.widget_slider_value as f64));
+                            let outputs = ["value", "checked", "slider"]
+                                .into_iter()
+                                .filter_map(|key| block.output_values.get(key).map(|v| (key.to_string(), v.clone())))
+                                .collect();
+                            self.executor.send(ExecutorCommand::SetOutputs { block_id, outputs });
                         }
+                        self.workflow.mark_dirty(block_id);
                     }
                 });
         }
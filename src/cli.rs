@@ -0,0 +1,673 @@
+//! 命令行模式 - 无界面运行工作流
+//!
+//! 用法: workflow_engine run <file.L|file.LZ> [--ticks N] [--script-dir DIR] [--block ID]... [--output FILE]
+//!
+//! 按 tick 驱动执行引擎，结束后以 JSON 输出选中 Block 的 output_values。
+//! Lua 执行出错时以非零退出码结束，便于在构建服务器上做回归测试。
+//!
+//! workflow_engine validate <file> 检查连线端口是否存在、类型是否兼容。
+//! workflow_engine keygen / trust 管理游戏包发布者签名密钥和播放器信任列表。
+//! workflow_engine merge 三方合并 .L 文件，可作为 git merge driver 使用。
+//! workflow_engine diff 比较两个工作流，按 Block 名称列出语义差异。
+
+use crate::script::{SandboxPolicy, ScriptRegistry};
+use crate::workflow::{self, Block, BlueprintStorage, PublisherKey, TraceHeader, TraceRecorder, TrustStore, Workflow, WorkflowExecutor};
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+/// 退出码：执行成功
+const EXIT_OK: i32 = 0;
+/// 退出码：工作流执行失败（Lua错误等）
+const EXIT_FAILURE: i32 = 1;
+/// 退出码：参数错误
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+用法:
+  workflow_engine                 启动可视化编辑器
+  workflow_engine run <file> [选项]
+  workflow_engine validate <file> [--script-dir <DIR>] [--password <P>]
+                                  检查连线端口和类型，有问题时退出码为 1
+  workflow_engine keygen <名称> [-o <FILE>]
+                                  生成发布者签名密钥（默认 publisher_key.json）
+  workflow_engine trust list | add <名称> <公钥> | remove <名称或公钥>
+                                  管理播放器信任的发布者（LEGNA_TRUST_STORE 可指定文件）
+  workflow_engine merge <base> <ours> <theirs> -o <out> [--conflicts <FILE>]
+                                  三方合并，冲突保留 ours 并以 JSON 输出冲突列表，有冲突时退出码为 1
+  workflow_engine diff <a> <b> [--json]
+                                  列出 a → b 新增/删除/移动的 Block 及属性、连线、分组、图层变化，有差异时退出码为 1
+
+run 选项:
+  --ticks <N>          执行的 tick 数（默认 1）
+  --dt <秒>            每个 tick 的模拟步长（默认 0.1）
+  --time-scale <X>     模拟时间缩放（默认 1）
+  --seed <N>           随机数种子（默认使用文件中保存的种子）
+  --script-dir <DIR>   脚本目录（默认自动查找 scripts/）
+  --block <ID>         只输出指定 Block，可重复；匹配 UUID、自定义名称或 script_id
+  --output <FILE>      将 JSON 结果写入文件（脚本 print 也会输出到 stdout）
+  --trace <FILE>       录制每个 tick 的执行过程（.ltrace，可在编辑器中回放）
+  --sandbox            在沙箱中运行（移除 os/io/debug/require/load/dofile/usb）
+  --password <P>       打开受密码保护的蓝图";
+
+/// `run` 子命令参数
+#[derive(Debug, Clone, PartialEq)]
+struct RunArgs {
+    file: PathBuf,
+    ticks: u64,
+    dt: f64,
+    time_scale: f64,
+    seed: Option<u64>,
+    script_dir: Option<PathBuf>,
+    blocks: Vec<String>,
+    output: Option<PathBuf>,
+    trace: Option<PathBuf>,
+    sandbox: bool,
+    password: Option<String>,
+}
+
+/// `validate` 子命令参数
+#[derive(Debug, Clone, PartialEq)]
+struct ValidateArgs {
+    file: PathBuf,
+    script_dir: Option<PathBuf>,
+    password: Option<String>,
+}
+
+/// `keygen` 子命令参数
+#[derive(Debug, Clone, PartialEq)]
+struct KeygenArgs {
+    name: String,
+    output: PathBuf,
+}
+
+/// `merge` 子命令参数
+#[derive(Debug, Clone, PartialEq)]
+struct MergeArgs {
+    base: PathBuf,
+    ours: PathBuf,
+    theirs: PathBuf,
+    output: PathBuf,
+    conflicts: Option<PathBuf>,
+}
+
+/// `diff` 子命令参数
+#[derive(Debug, Clone, PartialEq)]
+struct DiffArgs {
+    old: PathBuf,
+    new: PathBuf,
+    json: bool,
+}
+
+/// `trust` 子命令
+#[derive(Debug, Clone, PartialEq)]
+enum TrustCommand {
+    List,
+    Add { name: String, public_key: String },
+    Remove(String),
+}
+
+/// 分发命令行子命令
+/// 返回 None 表示没有子命令（启动GUI），否则返回进程退出码
+pub fn dispatch(args: &[String], default_script_dir: impl FnOnce() -> PathBuf) -> Option<i32> {
+    let command = args.first()?;
+
+    let code = match command.as_str() {
+        "run" => match parse_run_args(&args[1..]) {
+            Ok(run_args) => {
+                let script_dir = run_args.script_dir.clone().unwrap_or_else(default_script_dir);
+                match run(&run_args, script_dir) {
+                    Ok(()) => EXIT_OK,
+                    Err(e) => {
+                        eprintln!("错误: {:#}", e);
+                        EXIT_FAILURE
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("错误: {}\n\n{}", e, USAGE);
+                EXIT_USAGE
+            }
+        },
+        "validate" => match parse_validate_args(&args[1..]) {
+            Ok(validate_args) => {
+                let script_dir = validate_args.script_dir.clone().unwrap_or_else(default_script_dir);
+                match validate(&validate_args, script_dir) {
+                    Ok(true) => EXIT_OK,
+                    Ok(false) => EXIT_FAILURE,
+                    Err(e) => {
+                        eprintln!("错误: {:#}", e);
+                        EXIT_FAILURE
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("错误: {}\n\n{}", e, USAGE);
+                EXIT_USAGE
+            }
+        },
+        "keygen" => match parse_keygen_args(&args[1..]) {
+            Ok(keygen_args) => match keygen(&keygen_args) {
+                Ok(()) => EXIT_OK,
+                Err(e) => {
+                    eprintln!("错误: {:#}", e);
+                    EXIT_FAILURE
+                }
+            },
+            Err(e) => {
+                eprintln!("错误: {}\n\n{}", e, USAGE);
+                EXIT_USAGE
+            }
+        },
+        "trust" => match parse_trust_args(&args[1..]) {
+            Ok(command) => match trust(&command) {
+                Ok(()) => EXIT_OK,
+                Err(e) => {
+                    eprintln!("错误: {:#}", e);
+                    EXIT_FAILURE
+                }
+            },
+            Err(e) => {
+                eprintln!("错误: {}\n\n{}", e, USAGE);
+                EXIT_USAGE
+            }
+        },
+        "merge" => match parse_merge_args(&args[1..]) {
+            Ok(merge_args) => match merge(&merge_args) {
+                Ok(true) => EXIT_OK,
+                Ok(false) => EXIT_FAILURE,
+                Err(e) => {
+                    eprintln!("错误: {:#}", e);
+                    EXIT_FAILURE
+                }
+            },
+            Err(e) => {
+                eprintln!("错误: {}\n\n{}", e, USAGE);
+                EXIT_USAGE
+            }
+        },
+        "diff" => match parse_diff_args(&args[1..]) {
+            Ok(diff_args) => match diff(&diff_args) {
+                Ok(true) => EXIT_OK,
+                Ok(false) => EXIT_FAILURE,
+                Err(e) => {
+                    eprintln!("错误: {:#}", e);
+                    EXIT_FAILURE
+                }
+            },
+            Err(e) => {
+                eprintln!("错误: {}\n\n{}", e, USAGE);
+                EXIT_USAGE
+            }
+        },
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            EXIT_OK
+        }
+        _ => return None,
+    };
+
+    Some(code)
+}
+
+fn parse_run_args(args: &[String]) -> Result<RunArgs> {
+    let mut file = None;
+    let mut ticks = 1;
+    let mut dt = workflow::DEFAULT_DT;
+    let mut time_scale = 1.0;
+    let mut seed = None;
+    let mut script_dir = None;
+    let mut blocks = Vec::new();
+    let mut output = None;
+    let mut trace = None;
+    let mut sandbox = false;
+    let mut password = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| anyhow!("{} 缺少参数值", name))
+        };
+
+        match arg.as_str() {
+            "--ticks" => {
+                let raw = value("--ticks")?;
+                ticks = raw.parse().map_err(|_| anyhow!("无效的 tick 数: {}", raw))?;
+            }
+            "--dt" => {
+                let raw = value("--dt")?;
+                dt = raw.parse().ok().filter(|v: &f64| *v > 0.0).ok_or_else(|| anyhow!("无效的步长: {}", raw))?;
+            }
+            "--time-scale" => {
+                let raw = value("--time-scale")?;
+                time_scale = raw.parse().ok().filter(|v: &f64| *v >= 0.0).ok_or_else(|| anyhow!("无效的时间缩放: {}", raw))?;
+            }
+            "--seed" => {
+                let raw = value("--seed")?;
+                seed = Some(raw.parse().map_err(|_| anyhow!("无效的随机数种子: {}", raw))?);
+            }
+            "--script-dir" => script_dir = Some(PathBuf::from(value("--script-dir")?)),
+            "--block" => blocks.push(value("--block")?),
+            "--output" | "-o" => output = Some(PathBuf::from(value("--output")?)),
+            "--trace" => trace = Some(PathBuf::from(value("--trace")?)),
+            "--sandbox" => sandbox = true,
+            "--password" => password = Some(value("--password")?),
+            other if other.starts_with("--") => return Err(anyhow!("未知选项: {}", other)),
+            other => {
+                if file.is_some() {
+                    return Err(anyhow!("多余的参数: {}", other));
+                }
+                file = Some(PathBuf::from(other));
+            }
+        }
+    }
+
+    Ok(RunArgs {
+        file: file.ok_or_else(|| anyhow!("缺少工作流文件"))?,
+        ticks,
+        dt,
+        time_scale,
+        seed,
+        script_dir,
+        blocks,
+        output,
+        trace,
+        sandbox,
+        password,
+    })
+}
+
+fn parse_validate_args(args: &[String]) -> Result<ValidateArgs> {
+    let mut file = None;
+    let mut script_dir = None;
+    let mut password = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| anyhow!("{} 缺少参数值", name))
+        };
+
+        match arg.as_str() {
+            "--script-dir" => script_dir = Some(PathBuf::from(value("--script-dir")?)),
+            "--password" => password = Some(value("--password")?),
+            other if other.starts_with("--") => return Err(anyhow!("未知选项: {}", other)),
+            other => {
+                if file.is_some() {
+                    return Err(anyhow!("多余的参数: {}", other));
+                }
+                file = Some(PathBuf::from(other));
+            }
+        }
+    }
+
+    Ok(ValidateArgs {
+        file: file.ok_or_else(|| anyhow!("缺少工作流文件"))?,
+        script_dir,
+        password,
+    })
+}
+
+fn parse_keygen_args(args: &[String]) -> Result<KeygenArgs> {
+    let mut name = None;
+    let mut output = PathBuf::from("publisher_key.json");
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--output" | "-o" => {
+                output = PathBuf::from(iter.next().ok_or_else(|| anyhow!("{} 缺少参数值", arg))?);
+            }
+            other if other.starts_with("--") => return Err(anyhow!("未知选项: {}", other)),
+            other => {
+                if name.is_some() {
+                    return Err(anyhow!("多余的参数: {}", other));
+                }
+                name = Some(other.to_string());
+            }
+        }
+    }
+
+    Ok(KeygenArgs {
+        name: name.ok_or_else(|| anyhow!("缺少发布者名称"))?,
+        output,
+    })
+}
+
+fn parse_trust_args(args: &[String]) -> Result<TrustCommand> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["list"] => Ok(TrustCommand::List),
+        ["add", name, public_key] => Ok(TrustCommand::Add {
+            name: name.to_string(),
+            public_key: public_key.to_string(),
+        }),
+        ["remove", target] => Ok(TrustCommand::Remove(target.to_string())),
+        [] => Err(anyhow!("缺少 trust 子命令")),
+        _ => Err(anyhow!("无效的 trust 参数: {}", args.join(" "))),
+    }
+}
+
+fn parse_merge_args(args: &[String]) -> Result<MergeArgs> {
+    let mut files = Vec::new();
+    let mut output = None;
+    let mut conflicts = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| anyhow!("{} 缺少参数值", name))
+        };
+
+        match arg.as_str() {
+            "--output" | "-o" => output = Some(PathBuf::from(value("--output")?)),
+            "--conflicts" => conflicts = Some(PathBuf::from(value("--conflicts")?)),
+            other if other.starts_with("--") => return Err(anyhow!("未知选项: {}", other)),
+            other => files.push(PathBuf::from(other)),
+        }
+    }
+
+    let [base, ours, theirs]: [PathBuf; 3] = files
+        .try_into()
+        .map_err(|_| anyhow!("需要 base、ours、theirs 三个文件"))?;
+    Ok(MergeArgs {
+        base,
+        ours,
+        theirs,
+        output: output.ok_or_else(|| anyhow!("缺少 -o <输出文件>"))?,
+        conflicts,
+    })
+}
+
+/// 三方合并，返回是否无冲突
+fn merge(args: &MergeArgs) -> Result<bool> {
+    let load = |file: &Path| {
+        BlueprintStorage::load(file, None).map_err(|e| anyhow!("加载工作流失败 {}: {}", file.display(), e))
+    };
+    let result = workflow::merge(&load(&args.base)?, &load(&args.ours)?, &load(&args.theirs)?)?;
+    BlueprintStorage::save(&result.workflow, &args.output, None)?;
+
+    if !result.conflicts.is_empty() {
+        let json = serde_json::to_string_pretty(&result.conflicts)?;
+        match &args.conflicts {
+            Some(path) => std::fs::write(path, json + "\n")?,
+            None => println!("{}", json),
+        }
+        eprintln!("合并完成: {} 处冲突，已保留 ours 的修改", result.conflicts.len());
+    }
+    Ok(result.conflicts.is_empty())
+}
+
+fn parse_diff_args(args: &[String]) -> Result<DiffArgs> {
+    let mut files = Vec::new();
+    let mut json = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            other if other.starts_with("--") => return Err(anyhow!("未知选项: {}", other)),
+            other => files.push(PathBuf::from(other)),
+        }
+    }
+
+    let [old, new]: [PathBuf; 2] = files
+        .try_into()
+        .map_err(|_| anyhow!("需要两个工作流文件"))?;
+    Ok(DiffArgs { old, new, json })
+}
+
+/// 比较两个工作流，返回是否相同
+fn diff(args: &DiffArgs) -> Result<bool> {
+    let load = |file: &Path| {
+        BlueprintStorage::load(file, None).map_err(|e| anyhow!("加载工作流失败 {}: {}", file.display(), e))
+    };
+    let diff = load(&args.old)?.diff(&load(&args.new)?);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff.entries)?);
+    } else {
+        print!("{}", diff);
+    }
+    Ok(diff.is_empty())
+}
+
+/// 生成发布者密钥
+fn keygen(args: &KeygenArgs) -> Result<()> {
+    if args.output.exists() {
+        return Err(anyhow!("文件已存在，不会覆盖: {}", args.output.display()));
+    }
+
+    let key = PublisherKey::generate(&args.name);
+    key.save(&args.output)?;
+    println!("已生成密钥: {}", args.output.display());
+    println!("公钥: {}", key.public_key_hex());
+    println!("玩家信任该发布者: workflow_engine trust add \"{}\" {}", args.name, key.public_key_hex());
+    Ok(())
+}
+
+/// 管理信任列表
+fn trust(command: &TrustCommand) -> Result<()> {
+    let path = TrustStore::default_path();
+    let mut store = TrustStore::load(&path)?;
+
+    match command {
+        TrustCommand::List => {
+            if store.publishers.is_empty() {
+                println!("信任列表为空: {}", path.display());
+            }
+            for publisher in &store.publishers {
+                println!("{}  {}", publisher.public_key, publisher.name);
+            }
+            return Ok(());
+        }
+        TrustCommand::Add { name, public_key } => {
+            store.add(name, public_key)?;
+            println!("已信任: {}", name);
+        }
+        TrustCommand::Remove(target) => {
+            if store.remove(target) == 0 {
+                return Err(anyhow!("信任列表中没有: {}", target));
+            }
+            println!("已移除: {}", target);
+        }
+    }
+
+    store.save(&path)?;
+    Ok(())
+}
+
+/// 加载工作流并校验打开密码
+fn load_workflow(file: &Path, password: Option<&str>) -> Result<Workflow> {
+    let mut workflow = BlueprintStorage::load(file, password)
+        .map_err(|e| anyhow!("加载工作流失败 {}: {}", file.display(), e))?;
+
+    if workflow.has_password() {
+        let password = password.ok_or_else(|| anyhow!("蓝图受密码保护，请使用 --password"))?;
+        if !workflow.unlock(password) {
+            return Err(anyhow!("密码错误"));
+        }
+    }
+
+    workflow.update_execution_order();
+    Ok(workflow)
+}
+
+/// 校验工作流连线，返回是否全部合法
+fn validate(args: &ValidateArgs, script_dir: PathBuf) -> Result<bool> {
+    let mut registry = ScriptRegistry::new(&script_dir)?;
+    let workflow = load_workflow(&args.file, args.password.as_deref())?;
+    workflow.register_subgraphs(&mut registry);
+
+    let errors = workflow.validate(&registry);
+    for (id, error) in &errors {
+        println!("连线 {}: {}", id, error);
+    }
+    if errors.is_empty() {
+        println!("校验通过: {} 条连线", workflow.connections.len());
+    } else {
+        println!("{} 条连线有问题", errors.len());
+    }
+
+    // 循环不算错误，但执行顺序只取决于 id，提示标记反馈连线
+    for cycle in &workflow.cycles {
+        let names: Vec<String> = cycle.iter()
+            .filter_map(|id| workflow.blocks.get(id))
+            .map(|block| match &block.custom_name {
+                Some(name) if !name.is_empty() => format!("{} ({})", name, block.script_id),
+                _ => block.script_id.clone(),
+            })
+            .collect();
+        println!("警告: 循环依赖 [{}]，可将其中一条连线标记为反馈连线", names.join(", "));
+    }
+    Ok(errors.is_empty())
+}
+
+/// 无界面执行工作流
+fn run(args: &RunArgs, script_dir: PathBuf) -> Result<()> {
+    let mut registry = ScriptRegistry::new(&script_dir)?;
+    let mut workflow = load_workflow(&args.file, args.password.as_deref())?;
+    workflow.register_subgraphs(&mut registry);
+    if let Some(seed) = args.seed {
+        workflow.seed = seed;
+    }
+
+    let policy = if args.sandbox { SandboxPolicy::restricted() } else { SandboxPolicy::trusted() };
+    let mut executor = WorkflowExecutor::with_policy(&policy)?;
+    executor.set_dt(args.dt);
+    executor.set_time_scale(args.time_scale);
+    if let Some(path) = &args.trace {
+        executor.start_trace(TraceRecorder::create(path, &TraceHeader::new(&workflow, executor.clock()))?);
+    }
+    let result = (0..args.ticks).try_for_each(|tick| {
+        executor
+            .execute_all(&mut workflow, &registry)
+            .map_err(|e| anyhow!("第 {} 个 tick 执行失败: {}", tick + 1, e))
+    });
+    // 执行失败时也写出录制，便于回放出错前的过程
+    if let Some(recorder) = executor.take_trace() {
+        recorder.finish()?;
+    }
+    result?;
+
+    let selected: Vec<&Block> = if args.blocks.is_empty() {
+        workflow.execution_order.iter().filter_map(|id| workflow.blocks.get(id)).collect()
+    } else {
+        let mut selected = Vec::new();
+        for pattern in &args.blocks {
+            let matched: Vec<&Block> = workflow.execution_order.iter()
+                .filter_map(|id| workflow.blocks.get(id))
+                .filter(|b| block_matches(b, pattern))
+                .collect();
+            if matched.is_empty() {
+                return Err(anyhow!("找不到 Block: {}", pattern));
+            }
+            selected.extend(matched);
+        }
+        selected
+    };
+
+    let mut result = serde_json::Map::new();
+    for block in selected {
+        result.insert(
+            block.id.to_string(),
+            serde_json::json!({
+                "script_id": block.script_id,
+                "name": block.custom_name,
+                "outputs": block.output_values,
+            }),
+        );
+    }
+
+    let json = serde_json::to_string_pretty(&serde_json::Value::Object(result))?;
+    match &args.output {
+        Some(path) => std::fs::write(path, json + "\n")?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// Block 是否匹配命令行给出的标识
+fn block_matches(block: &Block, pattern: &str) -> bool {
+    block.id.to_string() == pattern
+        || block.custom_name.as_deref() == Some(pattern)
+        || block.script_id == pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_run_args() {
+        let parsed = parse_run_args(&args(&[
+            "battle.L", "--ticks", "500", "--script-dir", "scripts", "--block", "hero", "--block", "boss",
+        ]))
+        .unwrap();
+
+        assert_eq!(parsed.file, PathBuf::from("battle.L"));
+        assert_eq!(parsed.ticks, 500);
+        assert_eq!(parsed.script_dir, Some(PathBuf::from("scripts")));
+        assert_eq!(parsed.blocks, vec!["hero".to_string(), "boss".to_string()]);
+        assert_eq!(parsed.output, None);
+        assert!(!parsed.sandbox);
+        assert_eq!(parsed.password, None);
+        assert_eq!((parsed.dt, parsed.time_scale, parsed.seed), (workflow::DEFAULT_DT, 1.0, None));
+
+        assert_eq!(parsed.trace, None);
+
+        let parsed = parse_run_args(&args(&["battle.L", "--dt", "0.02", "--time-scale", "0.5", "--seed", "42", "--trace", "boss.ltrace"])).unwrap();
+        assert_eq!((parsed.dt, parsed.time_scale, parsed.seed), (0.02, 0.5, Some(42)));
+        assert_eq!(parsed.trace, Some(PathBuf::from("boss.ltrace")));
+    }
+
+    #[test]
+    fn test_parse_run_args_errors() {
+        assert!(parse_run_args(&args(&[])).is_err());
+        assert!(parse_run_args(&args(&["a.L", "--ticks"])).is_err());
+        assert!(parse_run_args(&args(&["a.L", "--ticks", "abc"])).is_err());
+        assert!(parse_run_args(&args(&["a.L", "--dt", "0"])).is_err());
+        assert!(parse_run_args(&args(&["a.L", "--seed", "-1"])).is_err());
+        assert!(parse_run_args(&args(&["a.L", "b.L"])).is_err());
+        assert!(parse_run_args(&args(&["a.L", "--unknown"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_merge_args() {
+        let parsed = parse_merge_args(&args(&["base.L", "ours.L", "theirs.L", "-o", "out.L"])).unwrap();
+        assert_eq!(parsed.ours, PathBuf::from("ours.L"));
+        assert_eq!(parsed.output, PathBuf::from("out.L"));
+        assert_eq!(parsed.conflicts, None);
+        assert!(parse_merge_args(&args(&["base.L", "ours.L", "-o", "out.L"])).is_err());
+        assert!(parse_merge_args(&args(&["base.L", "ours.L", "theirs.L"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_diff_args() {
+        let parsed = parse_diff_args(&args(&["a.L", "--json", "b.L"])).unwrap();
+        assert_eq!(parsed, DiffArgs { old: PathBuf::from("a.L"), new: PathBuf::from("b.L"), json: true });
+        assert!(parse_diff_args(&args(&["a.L"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_keygen_and_trust_args() {
+        let parsed = parse_keygen_args(&args(&["Legna", "-o", "key.json"])).unwrap();
+        assert_eq!(parsed, KeygenArgs { name: "Legna".into(), output: PathBuf::from("key.json") });
+        assert!(parse_keygen_args(&args(&[])).is_err());
+
+        assert_eq!(parse_trust_args(&args(&["list"])).unwrap(), TrustCommand::List);
+        assert_eq!(
+            parse_trust_args(&args(&["add", "Legna", "abcd"])).unwrap(),
+            TrustCommand::Add { name: "Legna".into(), public_key: "abcd".into() }
+        );
+        assert!(parse_trust_args(&args(&["add", "Legna"])).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 //! WorkflowEngine - 可视化工作流引擎
 
 mod app;
+mod cli;
 mod script;
 mod ui;
 mod usb;
@@ -118,8 +119,18 @@ fn main() -> eframe::Result<()> {
     // Windows 控制台 UTF-8 支持
     setup_windows_console();
 
-    // 初始化日志
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    // 命令行子命令（无界面模式）
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        // 命令行模式默认只输出警告，避免日志淹没结果
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+        if let Some(code) = cli::dispatch(&args, get_script_dir) {
+            std::process::exit(code);
+        }
+    } else {
+        // 初始化日志
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    }
 
     // 获取脚本目录
     let script_dir = get_script_dir();
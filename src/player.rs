@@ -5,21 +5,30 @@
 //! 从加密的游戏数据包中加载工作流和脚本运行游戏。
 //! 如果不指定文件，会自动扫描同目录下的 .lpack 文件。
 //! 多个 .lpack 时会显示选择界面。
+//! 运行前展示游戏包的能力清单，脚本只能使用用户确认过的受限库。
+//! 设置了口令的游戏包会先要求输入口令。
+//! 签名无效（被篡改）的游戏包拒绝加载，未签名或发布者不在信任列表中时给出警告。
 
 mod script;
 mod ui;
 mod usb;
 mod workflow;
 
-use script::{BlockDefinition, ScriptParser, Value};
+use script::{BlockDefinition, SandboxPolicy, ScriptParser, Value};
 use ui::{BlockWidget, Canvas, ConnectionWidget};
-use workflow::{GamePackage, Viewport, Workflow, Vec2};
+use workflow::crypto::CryptoError;
+use workflow::executor::{ExecutionLimits, LuaLimiter};
+use workflow::signing::Publisher;
+use workflow::{
+    BlockRng, FixedStep, GamePackage, SimulationClock, TraceHeader, TraceRecorder, TrustStore, Viewport,
+    Workflow, Vec2, TRACE_EXTENSION,
+};
 
 use anyhow::{anyhow, Result};
 use egui::{CentralPanel, Context, FontData, FontDefinitions, FontFamily, Pos2};
 use mlua::{Lua, Table, Value as LuaValue};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 /// 内存脚本注册表（从 GamePackage 加载，不读取文件）
@@ -29,8 +38,8 @@ pub struct MemoryRegistry {
 }
 
 impl MemoryRegistry {
-    pub fn from_package(package: &GamePackage) -> Result<Self> {
-        let parser = ScriptParser::new()?;
+    pub fn from_package(package: &GamePackage, policy: &SandboxPolicy) -> Result<Self> {
+        let parser = ScriptParser::with_policy(policy)?;
         let mut definitions = HashMap::new();
 
         for (script_id, source) in &package.scripts {
@@ -56,17 +65,35 @@ impl MemoryRegistry {
 /// 内存执行器
 pub struct MemoryExecutor {
     lua: Lua,
+    clock: SimulationClock,
+    /// 每个Block的随机数流（脚本环境每次执行重建）
+    rngs: HashMap<Uuid, BlockRng>,
+    seed: u64,
+    /// 执行录制（玩家反馈问题时附上录制文件）
+    trace: Option<TraceRecorder>,
+    /// 指令预算和内存上限（下载的游戏包中的死循环不会卡住播放器）
+    limiter: LuaLimiter,
 }
 
 impl MemoryExecutor {
-    pub fn new() -> Result<Self> {
+    pub fn new(policy: &SandboxPolicy) -> Result<Self> {
         let lua = Lua::new();
         // 注册 USB 模块
         usb::register_usb_module(&lua).map_err(|e| anyhow!("注册USB模块失败: {}", e))?;
-        Ok(Self { lua })
+        // 限制下载的游戏包可访问的库
+        policy.apply(&lua).map_err(|e| anyhow!("应用沙箱策略失败: {}", e))?;
+        let limiter = LuaLimiter::install(&lua, ExecutionLimits::default())?;
+        Ok(Self { lua, clock: SimulationClock::default(), rngs: HashMap::new(), seed: 0, trace: None, limiter })
     }
 
-    pub fn execute_all(&self, workflow: &mut Workflow, registry: &MemoryRegistry) -> Result<()> {
+    pub fn execute_all(&mut self, workflow: &mut Workflow, registry: &MemoryRegistry) -> Result<()> {
+        if workflow.seed != self.seed {
+            self.seed = workflow.seed;
+            for rng in self.rngs.values() { rng.reseed(self.seed); }
+        }
+        self.clock.advance();
+        if let Some(trace) = &mut self.trace { trace.begin_tick(self.clock); }
+        workflow.latch_feedback();
         let order = workflow.execution_order.clone();
         for block_id in order {
             self.execute_block(workflow, registry, block_id)?;
@@ -75,24 +102,65 @@ impl MemoryExecutor {
         Ok(())
     }
 
-    fn execute_block(&self, workflow: &mut Workflow, registry: &MemoryRegistry, block_id: Uuid) -> Result<()> {
+    /// 为本次执行新建Block的脚本环境（math.random 使用该Block的随机数流）
+    fn block_env(&mut self, block_id: Uuid) -> Result<Table> {
+        let seed = self.seed;
+        let rng = self.rngs.entry(block_id).or_insert_with(|| BlockRng::new(seed, block_id)).clone();
+        let env = self.lua.create_table().map_err(|e| anyhow!("{}", e))?;
+        let meta = self.lua.create_table().map_err(|e| anyhow!("{}", e))?;
+        meta.set("__index", self.lua.globals()).map_err(|e| anyhow!("{}", e))?;
+        env.set_metatable(Some(meta));
+        // _G 指向自身，避免通过 _G 写入共享全局表
+        env.set("_G", env.clone()).map_err(|e| anyhow!("{}", e))?;
+        rng.install(&self.lua, &env).map_err(|e| anyhow!("{}", e))?;
+        Ok(env)
+    }
+
+    /// 超出资源限制时标记Block故障（之后不再执行），返回是否已标记
+    fn fault_if_exceeded(&self, workflow: &mut Workflow, block_id: Uuid, e: &mlua::Error) -> bool {
+        let Some(block) = workflow.blocks.get_mut(&block_id) else { return false };
+        let Some(block_error) = self.limiter.exceeded(e, block_id, &block.script_id) else { return false };
+        log::warn!("{}", block_error);
+        block.fault = Some(block_error.to_string());
+        true
+    }
+
+    fn execute_block(&mut self, workflow: &mut Workflow, registry: &MemoryRegistry, block_id: Uuid) -> Result<()> {
         let block = match workflow.blocks.get(&block_id) { Some(b) => b, None => return Ok(()) };
+        if block.fault.is_some() { return Ok(()); }
         let source = match registry.get_source(&block.script_id) { Some(s) => s, None => return Ok(()) };
+        let definition = match registry.get(&block.script_id) { Some(d) => d, None => return Ok(()) };
 
-        // 收集输入
+        // 收集输入（按端口类型转换，事件只在收到的这次执行中有值）
         let mut inputs: HashMap<String, Value> = block.input_values.clone();
-        let input_conns: Vec<_> = workflow.get_input_connections(block_id).iter()
-            .filter_map(|c| workflow.blocks.get(&c.from_block)
-                .and_then(|b| b.output_values.get(&c.from_port).map(|v| (c.to_port.clone(), v.clone()))))
-            .collect();
+        let (input_conns, warnings) = workflow.connected_inputs(block_id, definition);
+        for warning in warnings { log::warn!("[{}] {}", block.script_id, warning); }
         for (port, val) in input_conns { inputs.insert(port, val); }
+        let events = workflow.take_events(block_id, definition, &mut inputs);
+        let block = match workflow.blocks.get(&block_id) { Some(b) => b, None => return Ok(()) };
+
+        // 检查必需输入，约束数值范围（等待输入时事件放回队列）
+        let mut properties = block.properties.clone();
+        let report = definition.apply_constraints(&mut inputs, &mut properties);
+        for warning in report.warnings { log::warn!("[{}] {}", block.script_id, warning); }
+        if !report.missing.is_empty() {
+            workflow.requeue_events(block_id, events);
+            return Ok(());
+        }
 
-        // 执行
-        let script_table: Table = self.lua.load(source).eval().map_err(|e| anyhow!("Lua错误: {}", e))?;
+        // 执行（每个Block单独计算指令预算）
+        let env = self.block_env(block_id)?;
+        self.limiter.reset();
+        let script_table: Table = match self.lua.load(source).set_environment(env).eval() {
+            Ok(table) => table,
+            Err(e) if self.fault_if_exceeded(workflow, block_id, &e) => return Ok(()),
+            Err(e) => return Err(anyhow!("Lua错误: {}", e)),
+        };
+        let block = match workflow.blocks.get(&block_id) { Some(b) => b, None => return Ok(()) };
         let self_table = self.lua.create_table().map_err(|e| anyhow!("{}", e))?;
 
         let props_table = self.lua.create_table().map_err(|e| anyhow!("{}", e))?;
-        for (k, v) in &block.properties { props_table.set(k.as_str(), self.value_to_lua(v)?).ok(); }
+        for (k, v) in &properties { props_table.set(k.as_str(), self.value_to_lua(v)?).ok(); }
         self_table.set("properties", props_table).ok();
 
         let state_table = self.lua.create_table().map_err(|e| anyhow!("{}", e))?;
@@ -103,34 +171,44 @@ impl MemoryExecutor {
         for (k, v) in &inputs { inputs_table.set(k.as_str(), self.value_to_lua(v)?).ok(); }
 
         if let Ok(execute_fn) = script_table.get::<mlua::Function>("execute") {
-            if let Ok(result) = execute_fn.call::<Table>((self_table.clone(), inputs_table)) {
-                // 更新state 和处理动画
-                if let Ok(new_state) = self_table.get::<Table>("state") {
+            let ctx_table = self.clock.to_lua(&self.lua).map_err(|e| anyhow!("{}", e))?;
+            match execute_fn.call::<Table>((self_table.clone(), inputs_table, ctx_table)) {
+                Ok(result) => {
+                    // 更新state 和处理动画
+                    if let Ok(new_state) = self_table.get::<Table>("state") {
+                        if let Some(block) = workflow.blocks.get_mut(&block_id) {
+                            for pair in new_state.clone().pairs::<String, LuaValue>() {
+                                if let Ok((k, v)) = pair {
+                                    if let Ok(val) = self.lua_to_value(v) { block.state.insert(k, val); }
+                                }
+                            }
+                            // 处理动画设置：从state中读取_animation表
+                            if let Ok(animation_table) = new_state.get::<Table>("_animation") {
+                                let offset_x = animation_table.get::<f32>("x").unwrap_or(0.0);
+                                let offset_y = animation_table.get::<f32>("y").unwrap_or(0.0);
+                                let speed = animation_table.get::<f32>("speed").ok();
+                                block.set_animation_target(offset_x, offset_y, speed);
+                            }
+                        }
+                    }
+                    // 更新outputs
                     if let Some(block) = workflow.blocks.get_mut(&block_id) {
-                        for pair in new_state.clone().pairs::<String, LuaValue>() {
+                        for pair in result.pairs::<String, LuaValue>() {
                             if let Ok((k, v)) = pair {
-                                if let Ok(val) = self.lua_to_value(v) { block.state.insert(k, val); }
+                                if let Ok(val) = self.lua_to_value(v) { block.output_values.insert(k, val); }
                             }
                         }
-                        // 处理动画设置：从state中读取_animation表
-                        if let Ok(animation_table) = new_state.get::<Table>("_animation") {
-                            let offset_x = animation_table.get::<f32>("x").unwrap_or(0.0);
-                            let offset_y = animation_table.get::<f32>("y").unwrap_or(0.0);
-                            let speed = animation_table.get::<f32>("speed").ok();
-                            block.set_animation_target(offset_x, offset_y, speed);
-                        }
                     }
+                    // 录制事件清空前的输出
+                    if let (Some(trace), Some(block)) = (&mut self.trace, workflow.blocks.get(&block_id)) { trace.record_block(block); }
+                    // 发出事件
+                    workflow.emit_events(block_id, definition);
+                    // 激活Block和连线（用于动画）
+                    workflow.activate_block(block_id);
                 }
-                // 更新outputs
-                if let Some(block) = workflow.blocks.get_mut(&block_id) {
-                    for pair in result.pairs::<String, LuaValue>() {
-                        if let Ok((k, v)) = pair {
-                            if let Ok(val) = self.lua_to_value(v) { block.output_values.insert(k, val); }
-                        }
-                    }
+                Err(e) => {
+                    self.fault_if_exceeded(workflow, block_id, &e);
                 }
-                // 激活Block和连线（用于动画）
-                workflow.activate_block(block_id);
             }
         }
         Ok(())
@@ -197,7 +275,7 @@ struct PlayerApp {
     workflow: Workflow,
     executor: MemoryExecutor,
     package_name: String,
-    last_execute_time: std::time::Instant,
+    step: FixedStep,
     execution_speed: f32,
     auto_execute: bool,
     error_message: Option<String>,
@@ -206,11 +284,9 @@ struct PlayerApp {
 }
 
 impl PlayerApp {
-    fn new(package_path: PathBuf) -> Result<Self> {
-        log::info!("加载游戏包: {}", package_path.display());
-
-        let package = GamePackage::load(&package_path)?;
-        let registry = MemoryRegistry::from_package(&package)?;
+    fn new(package: GamePackage, policy: &SandboxPolicy) -> Result<Self> {
+        log::info!("沙箱能力: {:?}", policy.capabilities);
+        let registry = MemoryRegistry::from_package(&package, policy)?;
 
         let mut workflow = package.workflow;
         // 更新Block尺寸
@@ -224,14 +300,14 @@ impl PlayerApp {
         log::info!("游戏: {} v{}", package.name, package.version);
         log::info!("Block数: {}, 脚本数: {}", workflow.blocks.len(), registry.sources.len());
 
-        let executor = MemoryExecutor::new()?;
+        let executor = MemoryExecutor::new(policy)?;
 
         Ok(Self {
             registry,
             workflow,
             executor,
             package_name: package.name,
-            last_execute_time: std::time::Instant::now(),
+            step: FixedStep::new(10.0),
             execution_speed: 10.0,
             auto_execute: true,
             error_message: None,
@@ -240,6 +316,32 @@ impl PlayerApp {
         })
     }
 
+    /// 开始/结束录制
+    fn toggle_trace(&mut self) {
+        if let Some(recorder) = self.executor.trace.take() {
+            match recorder.finish() {
+                Ok(ticks) => log::info!("录制结束: {} 个 tick", ticks),
+                Err(e) => self.error_message = Some(format!("录制失败: {}", e)),
+            }
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("执行录制", &[TRACE_EXTENSION])
+            .set_file_name(format!("{}.{}", self.package_name, TRACE_EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+        let header = TraceHeader::new(&self.workflow, self.executor.clock);
+        match TraceRecorder::create(&path, &header) {
+            Ok(recorder) => {
+                log::info!("开始录制: {}", path.display());
+                self.executor.trace = Some(recorder);
+            }
+            Err(e) => self.error_message = Some(e.to_string()),
+        }
+    }
+
     fn run_workflow(&mut self) {
         let all_ids: Vec<Uuid> = self.workflow.blocks.keys().cloned().collect();
         for id in all_ids {
@@ -255,19 +357,19 @@ impl PlayerApp {
 
 impl eframe::App for PlayerApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
-        // 更新Block动画（假设60fps，delta_time ≈ 0.016秒）
-        let delta_time = 1.0 / 60.0;
+        // 更新Block动画（按实际帧间隔）
+        let delta_time = ctx.input(|i| i.stable_dt);
         for block in self.workflow.blocks.values_mut() {
             block.update_animation(delta_time);
         }
         // 衰减激活状态（连线流动效果）
         self.workflow.decay_activation(0.05);
 
-        // 自动执行
-        let interval = 1.0 / self.execution_speed.max(0.1);
-        if self.auto_execute && self.last_execute_time.elapsed().as_secs_f32() >= interval {
-            self.last_execute_time = std::time::Instant::now();
-            self.run_workflow();
+        // 自动执行（与编辑器相同的固定步长循环）
+        if self.auto_execute {
+            for _ in 0..self.step.due() {
+                self.run_workflow();
+            }
         }
 
         // 顶部控制栏
@@ -279,15 +381,25 @@ impl eframe::App for PlayerApp {
                 let play_text = if self.auto_execute { "⏸ 暂停" } else { "▶ 运行" };
                 if ui.button(play_text).clicked() {
                     self.auto_execute = !self.auto_execute;
+                    self.step.restart();
                 }
 
                 ui.label("速度:");
-                ui.add(egui::Slider::new(&mut self.execution_speed, 1.0..=60.0).suffix(" Hz"));
+                if ui.add(egui::Slider::new(&mut self.execution_speed, 1.0..=60.0).suffix(" Hz")).changed() {
+                    self.step.set_rate(self.execution_speed);
+                }
+                ui.label(format!("tick {} · {:.1}s", self.executor.clock.tick, self.executor.clock.elapsed));
 
                 if ui.button("⏯ 单步").clicked() {
                     self.run_workflow();
                 }
 
+                // 录制执行过程，回放在编辑器中进行
+                let record_text = if self.executor.trace.is_some() { "⏹ 停止录制" } else { "⏺ 录制" };
+                if ui.button(record_text).clicked() {
+                    self.toggle_trace();
+                }
+
                 ui.separator();
                 let log_text = if self.show_log_panel { "📋" } else { "📋 日志" };
                 if ui.button(log_text).clicked() {
@@ -494,20 +606,58 @@ fn scan_lpack_files() -> Vec<PathBuf> {
     files
 }
 
-/// 解析 lpack 文件获取游戏名（用于选择界面）
-fn get_package_info(path: &PathBuf) -> Option<(String, String)> {
-    match GamePackage::load(path) {
-        Ok(pkg) => Some((pkg.name, pkg.version)),
-        Err(_) => None,
+/// 是否因口令错误而无法解密
+fn is_locked(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<CryptoError>(), Some(CryptoError::WrongPassphrase))
+}
+
+/// 发布者标记
+fn publisher_marker(publisher: &Publisher) -> &'static str {
+    if publisher.is_trusted() { "✅" } else { "⚠" }
+}
+
+/// 解析 lpack 文件获取显示名称（用于选择界面，加密包显示文件名，签名无效的包不列出）
+fn get_package_label(path: &Path, trust: &TrustStore) -> Option<String> {
+    let publisher = match GamePackage::inspect(path, trust) {
+        Ok(publisher) => publisher,
+        Err(e) => {
+            log::warn!("跳过游戏包 {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    let title = match GamePackage::load(path, "", trust) {
+        Ok(pkg) => format!("{} (v{})", pkg.name, pkg.version),
+        Err(e) if is_locked(&e) => format!("🔒 {}", path.file_stem()?.to_string_lossy()),
+        Err(_) => return None,
+    };
+    Some(format!("{}  ·  {} {}", title, publisher_marker(&publisher), publisher.label()))
+}
+
+/// 加载游戏包（进入权限确认界面，口令错误时进入口令输入界面）
+fn load_package(path: &Path, passphrase: &str, trust: &TrustStore) -> LaunchMode {
+    log::info!("加载游戏包: {}", path.display());
+    match GamePackage::load(path, passphrase, trust) {
+        Ok(package) => LaunchMode::Confirming(Box::new(package)),
+        Err(e) if is_locked(&e) => LaunchMode::Unlocking {
+            path: path.to_path_buf(),
+            passphrase: String::new(),
+            error: (!passphrase.is_empty()).then(|| "口令错误".to_string()),
+        },
+        Err(e) => LaunchMode::Error(format!("加载失败: {}", e)),
     }
 }
 
 /// 启动模式
 enum LaunchMode {
     /// 选择游戏
-    Selecting { games: Vec<(PathBuf, String, String)> },
+    Selecting { games: Vec<(PathBuf, String)> },
+    /// 等待输入游戏包口令
+    Unlocking { path: PathBuf, passphrase: String, error: Option<String> },
+    /// 展示能力清单，等待用户确认
+    Confirming(Box<GamePackage>),
     /// 运行游戏
-    Playing(PlayerApp),
+    Playing(Box<PlayerApp>),
     /// 加载失败
     Error(String),
 }
@@ -515,28 +665,32 @@ enum LaunchMode {
 /// 统一应用（支持选择和运行两种模式）
 struct UnifiedApp {
     mode: LaunchMode,
+    /// 本地发布者信任列表
+    trust: TrustStore,
 }
 
 impl UnifiedApp {
     fn new_selector(files: Vec<PathBuf>) -> Self {
+        let trust = TrustStore::load_default();
         let games: Vec<_> = files.into_iter()
             .filter_map(|path| {
-                get_package_info(&path).map(|(name, ver)| (path, name, ver))
+                get_package_label(&path, &trust).map(|label| (path, label))
             })
             .collect();
-        Self { mode: LaunchMode::Selecting { games } }
+        Self { mode: LaunchMode::Selecting { games }, trust }
     }
 
     fn new_player(path: PathBuf) -> Self {
-        match PlayerApp::new(path) {
-            Ok(app) => Self { mode: LaunchMode::Playing(app) },
-            Err(e) => Self { mode: LaunchMode::Error(format!("加载失败: {}", e)) },
-        }
+        let trust = TrustStore::load_default();
+        Self { mode: load_package(&path, "", &trust), trust }
     }
 }
 
 impl eframe::App for UnifiedApp {
     fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        // 权限确认后选择的沙箱策略
+        let mut chosen_policy: Option<SandboxPolicy> = None;
+
         match &mut self.mode {
             LaunchMode::Selecting { games } => {
                 let mut selected_path: Option<PathBuf> = None;
@@ -547,9 +701,8 @@ impl eframe::App for UnifiedApp {
                         ui.heading("🎮 选择游戏");
                         ui.add_space(20.0);
 
-                        for (path, name, version) in games.iter() {
-                            let btn_text = format!("{} (v{})", name, version);
-                            if ui.add_sized([300.0, 40.0], egui::Button::new(&btn_text)).clicked() {
+                        for (path, label) in games.iter() {
+                            if ui.add_sized([300.0, 40.0], egui::Button::new(label.as_str())).clicked() {
                                 selected_path = Some(path.clone());
                             }
                             ui.add_space(8.0);
@@ -566,14 +719,100 @@ impl eframe::App for UnifiedApp {
                     });
                 });
 
-                // 选择后切换到播放模式
+                // 选择后进入权限确认
                 if let Some(path) = selected_path {
-                    match PlayerApp::new(path) {
-                        Ok(app) => self.mode = LaunchMode::Playing(app),
-                        Err(e) => self.mode = LaunchMode::Error(format!("加载失败: {}", e)),
-                    }
+                    self.mode = load_package(&path, "", &self.trust);
+                }
+            }
+            LaunchMode::Unlocking { path, passphrase, error } => {
+                let mut submitted = false;
+
+                CentralPanel::default().show(ctx, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(40.0);
+                        ui.heading("🔒 游戏包已加密");
+                        ui.add_space(20.0);
+                        ui.label("请输入口令：");
+                        let response = ui.add_sized([200.0, 24.0], egui::TextEdit::singleline(passphrase).password(true));
+                        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            submitted = true;
+                        }
+
+                        if let Some(error) = error {
+                            ui.add_space(8.0);
+                            ui.colored_label(egui::Color32::RED, error.as_str());
+                        }
+
+                        ui.add_space(20.0);
+                        if ui.add_sized([200.0, 32.0], egui::Button::new("🔓 解锁")).clicked() {
+                            submitted = true;
+                        }
+                        ui.add_space(8.0);
+                        if ui.button("退出").clicked() {
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                        }
+                    });
+                });
+
+                if submitted {
+                    let (path, passphrase) = (path.clone(), passphrase.clone());
+                    self.mode = load_package(&path, &passphrase, &self.trust);
                 }
             }
+            LaunchMode::Confirming(package) => {
+                CentralPanel::default().show(ctx, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(40.0);
+                        ui.heading(format!("🎮 {} (v{})", package.name, package.version));
+                        ui.add_space(8.0);
+                        match &package.publisher {
+                            Publisher::Trusted { name } => {
+                                ui.colored_label(egui::Color32::from_rgb(120, 200, 120), format!("✅ 已验证的发布者: {}", name));
+                            }
+                            Publisher::Untrusted { claimed_name, fingerprint } => {
+                                ui.colored_label(egui::Color32::from_rgb(255, 180, 80), format!("⚠ 发布者不在信任列表中: {} ({})", claimed_name, fingerprint));
+                            }
+                            Publisher::Unsigned => {
+                                ui.colored_label(egui::Color32::from_rgb(255, 180, 80), "⚠ 游戏包未签名，无法确认来源");
+                            }
+                        }
+                        ui.add_space(20.0);
+
+                        if package.capabilities.is_empty() {
+                            ui.label("该游戏不需要额外权限，将在沙箱中运行。");
+                        } else {
+                            ui.label("该游戏请求以下权限：");
+                            ui.add_space(8.0);
+                            for capability in &package.capabilities {
+                                ui.colored_label(egui::Color32::from_rgb(255, 180, 80), capability.label());
+                            }
+                            ui.add_space(8.0);
+                            ui.label(egui::RichText::new("仅在信任游戏来源时允许。").weak());
+                        }
+
+                        ui.add_space(20.0);
+                        if package.capabilities.is_empty() {
+                            if ui.add_sized([200.0, 32.0], egui::Button::new("▶ 运行")).clicked() {
+                                chosen_policy = Some(SandboxPolicy::restricted());
+                            }
+                        } else {
+                            if ui.add_sized([200.0, 32.0], egui::Button::new("✅ 允许并运行")).clicked() {
+                                chosen_policy = Some(SandboxPolicy::with_capabilities(package.capabilities.iter().copied()));
+                            }
+                            ui.add_space(8.0);
+                            if ui.add_sized([200.0, 32.0], egui::Button::new("🔒 拒绝（受限运行）")).clicked() {
+                                chosen_policy = Some(SandboxPolicy::restricted());
+                            }
+                        }
+
+                        ui.add_space(8.0);
+                        if ui.button("退出").clicked() {
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                        }
+                    });
+                });
+
+            }
             LaunchMode::Playing(app) => {
                 app.update(ctx, frame);
             }
@@ -592,6 +831,16 @@ impl eframe::App for UnifiedApp {
                 });
             }
         }
+
+        // 确认后切换到播放模式
+        if let Some(policy) = chosen_policy {
+            if let LaunchMode::Confirming(package) = std::mem::replace(&mut self.mode, LaunchMode::Error(String::new())) {
+                self.mode = match PlayerApp::new(*package, &policy) {
+                    Ok(app) => LaunchMode::Playing(Box::new(app)),
+                    Err(e) => LaunchMode::Error(format!("加载失败: {}", e)),
+                };
+            }
+        }
     }
 }
 
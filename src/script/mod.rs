@@ -3,12 +3,14 @@
 mod loader;
 mod parser;
 mod registry;
+mod sandbox;
 mod types;
 mod watcher;
 
 pub use loader::ScriptLoader;
 pub use parser::ScriptParser;
 pub use registry::ScriptRegistry;
+pub use sandbox::{Capability, SandboxPolicy};
 pub use types::*;
 pub use watcher::ScriptWatcher;
 
@@ -1,6 +1,7 @@
 //! 脚本解析器 - 从Lua脚本解析Block定义
 
 use crate::script::types::*;
+use crate::script::SandboxPolicy;
 use crate::usb::register_usb_module;
 use anyhow::{anyhow, Result};
 use mlua::{Lua, Table, Value as LuaValue};
@@ -17,12 +18,19 @@ pub struct ScriptParser {
 }
 
 impl ScriptParser {
+    /// 创建解析器（信任本地脚本，开放全部标准库）
     pub fn new() -> Result<Self> {
+        Self::with_policy(&SandboxPolicy::trusted())
+    }
+
+    /// 创建受沙箱策略限制的解析器（解析时会执行脚本顶层代码）
+    pub fn with_policy(policy: &SandboxPolicy) -> Result<Self> {
         let lua = Lua::new();
         // 注册 USB 模块，使脚本可以引用 usb 全局变量
         if let Err(e) = register_usb_module(&lua) {
             log::warn!("注册USB模块到解析器失败: {}", e);
         }
+        policy.apply(&lua).map_err(lua_err)?;
         Ok(Self { lua })
     }
 
@@ -118,6 +126,7 @@ impl ScriptParser {
 
         // 解析 hideable 属性
         let hideable: bool = meta.get("hideable").unwrap_or(false);
+        let always_run: bool = meta.get("always_run").unwrap_or(false);
 
         Ok(BlockMeta {
             id,
@@ -131,6 +140,7 @@ impl ScriptParser {
             placeholder,
             options,
             hideable,
+            always_run,
         })
     }
 
@@ -178,6 +188,11 @@ impl ScriptParser {
                 .map(|s| Self::parse_data_type(&s)),
             min: table.get("min").ok(),
             max: table.get("max").ok(),
+            out_of_range: Self::parse_range_policy(table),
+            coerce: match table.get::<String>("coerce").ok().as_deref() {
+                Some("strict") => CoercionMode::Strict,
+                _ => CoercionMode::Lenient,
+            },
         })
     }
 
@@ -208,9 +223,17 @@ impl ScriptParser {
             description: table.get("description").unwrap_or_default(),
             min: table.get("min").ok(),
             max: table.get("max").ok(),
+            out_of_range: Self::parse_range_policy(table),
         })
     }
 
+    fn parse_range_policy(table: &Table) -> RangePolicy {
+        match table.get::<String>("out_of_range").ok().as_deref() {
+            Some("reject") => RangePolicy::Reject,
+            _ => RangePolicy::Clamp,
+        }
+    }
+
     fn lua_to_value(&self, lua_val: Option<LuaValue>) -> Result<Value> {
         match lua_val {
             None => Ok(Value::Nil),
@@ -11,6 +11,8 @@ pub struct ScriptRegistry {
     definitions: HashMap<String, BlockDefinition>,
     /// 分类索引 (category -> [meta.id])
     categories: HashMap<String, Vec<String>>,
+    /// 当前工作流的子图定义 (key = "subgraph:<UUID>")
+    subgraphs: HashMap<String, BlockDefinition>,
     /// 脚本目录
     script_dir: PathBuf,
     /// 解析器
@@ -25,6 +27,7 @@ impl ScriptRegistry {
         let mut registry = Self {
             definitions: HashMap::new(),
             categories: HashMap::new(),
+            subgraphs: HashMap::new(),
             script_dir,
             parser,
         };
@@ -45,6 +48,7 @@ impl ScriptRegistry {
                 log::warn!("加载脚本失败 {}: {}", script_path.display(), e);
             }
         }
+        self.index_subgraphs();
 
         log::info!(
             "已加载 {} 个Block定义, {} 个分类",
@@ -75,8 +79,8 @@ impl ScriptRegistry {
         Ok(id)
     }
 
-    /// 重新加载单个脚本(热重载)
-    pub fn reload_script(&mut self, path: &Path) -> Result<()> {
+    /// 重新加载单个脚本(热重载)，返回新的 script_id
+    pub fn reload_script(&mut self, path: &Path) -> Result<String> {
         // 先移除旧的定义
         if let Some(old_def) = self.find_by_path(path) {
             let old_id = old_def.meta.id.clone();
@@ -89,8 +93,26 @@ impl ScriptRegistry {
         }
 
         // 加载新定义
-        self.load_script(path)?;
-        Ok(())
+        self.load_script(path)
+    }
+
+    /// 替换子图定义（切换或编辑工作流时调用）
+    pub fn set_subgraphs(&mut self, definitions: Vec<BlockDefinition>) {
+        for ids in self.categories.values_mut() {
+            ids.retain(|id| !self.subgraphs.contains_key(id));
+        }
+        self.categories.retain(|_, ids| !ids.is_empty());
+        self.subgraphs = definitions.into_iter().map(|def| (def.meta.id.clone(), def)).collect();
+        self.index_subgraphs();
+    }
+
+    /// 将子图加入分类索引
+    fn index_subgraphs(&mut self) {
+        let mut ids: Vec<(&String, &String)> = self.subgraphs.values().map(|def| (&def.meta.category, &def.meta.id)).collect();
+        ids.sort();
+        for (category, id) in ids {
+            self.categories.entry(category.clone()).or_default().push(id.clone());
+        }
     }
 
     /// 根据路径查找定义
@@ -103,12 +125,12 @@ impl ScriptRegistry {
 
     /// 获取Block定义
     pub fn get(&self, id: &str) -> Option<&BlockDefinition> {
-        self.definitions.get(id)
+        self.definitions.get(id).or_else(|| self.subgraphs.get(id))
     }
 
     /// 获取所有定义
     pub fn all(&self) -> impl Iterator<Item = &BlockDefinition> {
-        self.definitions.values()
+        self.definitions.values().chain(self.subgraphs.values())
     }
 
     /// 获取所有分类
@@ -122,7 +144,7 @@ impl ScriptRegistry {
             .get(category)
             .map(|ids| {
                 ids.iter()
-                    .filter_map(|id| self.definitions.get(id))
+                    .filter_map(|id| self.get(id))
                     .collect()
             })
             .unwrap_or_default()
@@ -0,0 +1,186 @@
+//! 脚本沙箱 - 限制Lua脚本可访问的标准库
+//!
+//! 编辑器默认信任本地脚本；播放器运行下载的游戏包时，
+//! 只开放包清单中声明并经用户确认的能力。
+
+use mlua::{Lua, Table, Value as LuaValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// 始终可用的 os 函数（只读取时间，无副作用）
+const SAFE_OS_FUNCTIONS: &[&str] = &["clock", "date", "difftime", "time"];
+
+/// 脚本能力（需要显式授权的库）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// os.execute / os.remove / os.getenv 等（时间函数始终可用）
+    Os,
+    /// io 文件读写
+    Io,
+    /// debug 库
+    Debug,
+    /// require / package
+    Package,
+    /// load 动态执行代码
+    Load,
+    /// dofile / loadfile
+    DoFile,
+    /// usb 设备访问
+    Usb,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 7] = [
+        Capability::Os,
+        Capability::Io,
+        Capability::Debug,
+        Capability::Package,
+        Capability::Load,
+        Capability::DoFile,
+        Capability::Usb,
+    ];
+
+    /// 显示名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            Capability::Os => "os - 执行系统命令、删除文件、读取环境变量",
+            Capability::Io => "io - 读写本地文件",
+            Capability::Debug => "debug - 调试库",
+            Capability::Package => "require - 加载外部模块",
+            Capability::Load => "load - 动态执行代码",
+            Capability::DoFile => "dofile - 执行本地脚本文件",
+            Capability::Usb => "usb - 访问USB设备",
+        }
+    }
+
+    /// 该能力控制的全局变量
+    fn globals(&self) -> &'static [&'static str] {
+        match self {
+            Capability::Os => &["os"],
+            Capability::Io => &["io"],
+            Capability::Debug => &["debug"],
+            Capability::Package => &["package", "require"],
+            Capability::Load => &["load"],
+            Capability::DoFile => &["dofile", "loadfile"],
+            Capability::Usb => &["usb"],
+        }
+    }
+
+    /// 从脚本源码粗略推断需要的能力（宁多勿少，注释中的引用也会被计入）
+    pub fn detect(source: &str) -> BTreeSet<Capability> {
+        let mut found = BTreeSet::new();
+        let tokens = source.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'));
+
+        for token in tokens {
+            let (root, member) = match token.split_once('.') {
+                Some((root, member)) => (root, Some(member)),
+                None => (token, None),
+            };
+            let capability = match root {
+                "os" => match member {
+                    Some(name) if SAFE_OS_FUNCTIONS.contains(&name) => None,
+                    _ => Some(Capability::Os),
+                },
+                "io" => Some(Capability::Io),
+                "debug" => Some(Capability::Debug),
+                "package" | "require" => Some(Capability::Package),
+                "load" => Some(Capability::Load),
+                "dofile" | "loadfile" => Some(Capability::DoFile),
+                "usb" => Some(Capability::Usb),
+                _ => None,
+            };
+            found.extend(capability);
+        }
+
+        found
+    }
+}
+
+/// 沙箱策略：列出允许的能力，其余全部移除
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxPolicy {
+    pub capabilities: BTreeSet<Capability>,
+}
+
+impl SandboxPolicy {
+    /// 完全信任（编辑器中的本地脚本）
+    pub fn trusted() -> Self {
+        Self::with_capabilities(Capability::ALL)
+    }
+
+    /// 完全受限：只保留纯计算相关的标准库
+    pub fn restricted() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capabilities(capabilities: impl IntoIterator<Item = Capability>) -> Self {
+        Self {
+            capabilities: capabilities.into_iter().collect(),
+        }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    /// 将策略应用到Lua运行时的全局表（应在注册 usb 等模块之后调用）
+    pub fn apply(&self, lua: &Lua) -> mlua::Result<()> {
+        let globals = lua.globals();
+
+        for capability in Capability::ALL {
+            if self.allows(capability) {
+                continue;
+            }
+
+            if capability == Capability::Os {
+                // 只保留时间函数
+                if let Ok(os) = globals.get::<Table>("os") {
+                    let safe_os = lua.create_table()?;
+                    for name in SAFE_OS_FUNCTIONS {
+                        safe_os.set(*name, os.get::<LuaValue>(*name)?)?;
+                    }
+                    globals.set("os", safe_os)?;
+                }
+                continue;
+            }
+
+            for name in capability.globals() {
+                globals.set(*name, LuaValue::Nil)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restricted_policy_strips_globals() {
+        let lua = Lua::new();
+        SandboxPolicy::restricted().apply(&lua).unwrap();
+
+        let stripped: bool = lua
+            .load("return io == nil and require == nil and load == nil and dofile == nil and os.execute == nil")
+            .eval()
+            .unwrap();
+        assert!(stripped);
+
+        let has_time: bool = lua.load("return type(os.time()) == 'number' and math.floor ~= nil").eval().unwrap();
+        assert!(has_time);
+    }
+
+    #[test]
+    fn test_detect_capabilities() {
+        let source = "local t = os.time()\nos.execute('ls')\nlocal f = io.open('x')\nusb.devices()";
+        let detected = Capability::detect(source);
+        assert_eq!(
+            detected,
+            [Capability::Os, Capability::Io, Capability::Usb].into_iter().collect()
+        );
+        assert!(Capability::detect("return os.clock() + math.random()").is_empty());
+    }
+}
@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use thiserror::Error;
 
 /// 数据类型枚举
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -21,8 +22,27 @@ impl Default for DataType {
     }
 }
 
+impl DataType {
+    /// 类型名称（与脚本中的写法一致）
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataType::Number => "number",
+            DataType::String => "string",
+            DataType::Boolean => "boolean",
+            DataType::Event => "event",
+            DataType::Array => "array",
+            DataType::Any => "any",
+        }
+    }
+
+    /// 两个类型能否相连（Any 为通配）
+    pub fn is_compatible(&self, other: &DataType) -> bool {
+        self == other || *self == DataType::Any || *other == DataType::Any
+    }
+}
+
 /// 运行时值
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Value {
     Nil,
@@ -30,6 +50,7 @@ pub enum Value {
     String(String),
     Boolean(bool),
     Array(Vec<Value>),
+    #[serde(serialize_with = "crate::workflow::ordered::map")]
     Object(HashMap<String, Value>),
 }
 
@@ -60,6 +81,162 @@ impl Value {
             _ => None,
         }
     }
+
+    /// 值的类型名称
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    /// 值是否已经是目标类型（Any/Event 接受任意值）
+    fn matches(&self, data_type: &DataType) -> bool {
+        matches!(
+            (self, data_type),
+            (_, DataType::Any)
+                | (_, DataType::Event)
+                | (Value::Number(_), DataType::Number)
+                | (Value::String(_), DataType::String)
+                | (Value::Boolean(_), DataType::Boolean)
+                | (Value::Array(_), DataType::Array)
+        )
+    }
+}
+
+/// 端口值转换模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoercionMode {
+    /// 类型必须一致，否则丢弃该值
+    Strict,
+    /// 按转换表隐式转换
+    #[default]
+    Lenient,
+}
+
+/// 数值超出 min/max 时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RangePolicy {
+    /// 截断到边界
+    #[default]
+    Clamp,
+    /// 丢弃该值，改用默认值
+    Reject,
+}
+
+/// 数值超出范围（reject 策略）
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{value} 超出范围 [{}, {}]", format_bound(*.min, f64::NEG_INFINITY), format_bound(*.max, f64::INFINITY))]
+pub struct RangeError {
+    pub value: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+fn format_bound(bound: Option<f64>, unbounded: f64) -> String {
+    format_number(bound.unwrap_or(unbounded))
+}
+
+/// 按 min/max 约束数值，非数值原样返回
+pub fn constrain(value: &Value, min: Option<f64>, max: Option<f64>, policy: RangePolicy) -> Result<Value, RangeError> {
+    let Value::Number(n) = value else {
+        return Ok(value.clone());
+    };
+    let clamped = n.max(min.unwrap_or(f64::NEG_INFINITY)).min(max.unwrap_or(f64::INFINITY));
+    if clamped == *n {
+        return Ok(value.clone());
+    }
+    match policy {
+        RangePolicy::Clamp => Ok(Value::Number(clamped)),
+        RangePolicy::Reject => Err(RangeError { value: *n, min, max }),
+    }
+}
+
+/// 值转换失败
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("无法将 {from} {value} 转换为 {to}")]
+pub struct CoercionError {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub value: String,
+}
+
+/// 转换表（lenient 模式）：
+///
+/// | 目标      | 来源                                          |
+/// |-----------|-----------------------------------------------|
+/// | number    | string（可解析为数字）、boolean（1/0）        |
+/// | string    | number                                        |
+/// | array     | 单个标量（包装为单元素数组）                  |
+/// | any/event | 任意值                                        |
+///
+/// nil 总是原样传递；数组的元素按 element_type 逐个转换。
+/// 连线时仍按声明的类型检查（见 [`PortDefinition::can_connect_to`]），转换只作用于 any 输出等运行时类型不确定的值。
+pub fn coerce(value: &Value, data_type: &DataType, element_type: Option<&DataType>, mode: CoercionMode) -> Result<Value, CoercionError> {
+    if *value == Value::Nil {
+        return Ok(Value::Nil);
+    }
+
+    // 数组逐元素转换
+    if *data_type == DataType::Array {
+        let items = match value {
+            Value::Array(items) => items.clone(),
+            other @ (Value::Number(_) | Value::String(_) | Value::Boolean(_)) if mode == CoercionMode::Lenient => vec![other.clone()],
+            other => return Err(coercion_error(other, data_type)),
+        };
+        return match element_type {
+            Some(element) => items
+                .iter()
+                .map(|item| coerce(item, element, None, mode))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            None => Ok(Value::Array(items)),
+        };
+    }
+
+    if value.matches(data_type) {
+        return Ok(value.clone());
+    }
+    if mode == CoercionMode::Strict {
+        return Err(coercion_error(value, data_type));
+    }
+
+    let converted = match (value, data_type) {
+        (Value::String(s), DataType::Number) => s.trim().parse().ok().map(Value::Number),
+        (Value::Boolean(b), DataType::Number) => Some(Value::Number(if *b { 1.0 } else { 0.0 })),
+        (Value::Number(n), DataType::String) => Some(Value::String(format_number(*n))),
+        _ => None,
+    };
+    converted.ok_or_else(|| coercion_error(value, data_type))
+}
+
+fn coercion_error(value: &Value, data_type: &DataType) -> CoercionError {
+    let text = match value {
+        Value::String(s) => format!("{:?}", s),
+        Value::Number(n) => format_number(*n),
+        Value::Boolean(b) => b.to_string(),
+        _ => String::new(),
+    };
+    CoercionError {
+        from: value.type_name(),
+        to: data_type.name(),
+        value: text,
+    }
+}
+
+/// 数字转字符串，整数不带小数点（与 Lua tostring 一致）
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        n.to_string()
+    }
 }
 
 /// Block交互控件类型
@@ -103,6 +280,9 @@ pub struct BlockMeta {
     /// 预览模式下可隐藏（有连线时隐藏，孤立时显示）
     #[serde(default)]
     pub hideable: bool,
+    /// 每个 tick 都执行（计时器等有状态Block），否则只在输入/属性变化时执行
+    #[serde(default)]
+    pub always_run: bool,
 }
 
 fn default_color() -> String {
@@ -130,6 +310,40 @@ pub struct PortDefinition {
     pub min: Option<f64>,
     #[serde(default)]
     pub max: Option<f64>,
+    /// 超出 min/max 时的处理方式
+    #[serde(default)]
+    pub out_of_range: RangePolicy,
+    /// 连线值转换模式
+    #[serde(default)]
+    pub coerce: CoercionMode,
+}
+
+impl PortDefinition {
+    /// 类型描述，数组带元素类型，如 array<number>
+    pub fn type_label(&self) -> String {
+        match (&self.data_type, &self.element_type) {
+            (DataType::Array, Some(element)) => format!("array<{}>", element.name()),
+            (data_type, _) => data_type.name().to_string(),
+        }
+    }
+
+    /// 将连线传入的值转换为本端口类型
+    pub fn coerce(&self, value: &Value) -> Result<Value, CoercionError> {
+        coerce(value, &self.data_type, self.element_type.as_ref(), self.coerce)
+    }
+
+    /// 输出端口能否连接到输入端口（数组还需元素类型兼容，未声明元素类型视为 any）
+    pub fn can_connect_to(&self, input: &PortDefinition) -> bool {
+        if !self.data_type.is_compatible(&input.data_type) {
+            return false;
+        }
+        match (&self.element_type, &input.element_type) {
+            (Some(from), Some(to)) if self.data_type == DataType::Array && input.data_type == DataType::Array => {
+                from.is_compatible(to)
+            }
+            _ => true,
+        }
+    }
 }
 
 /// 属性定义
@@ -147,6 +361,9 @@ pub struct PropertyDefinition {
     pub min: Option<f64>,
     #[serde(default)]
     pub max: Option<f64>,
+    /// 超出 min/max 时的处理方式
+    #[serde(default)]
+    pub out_of_range: RangePolicy,
 }
 
 /// Block定义 - 从Lua解析出的完整定义
@@ -159,7 +376,57 @@ pub struct BlockDefinition {
     pub script_path: String,
 }
 
+/// 执行前按定义约束输入和属性的结果
+#[derive(Debug, Default, PartialEq)]
+pub struct ConstraintReport {
+    /// 未连接或为 nil 的必需输入（端口名称）
+    pub missing: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
 impl BlockDefinition {
+    /// 检查必需输入，按 min/max 截断或拒绝数值输入和属性（拒绝的值改用默认值）
+    pub fn apply_constraints(
+        &self,
+        inputs: &mut HashMap<String, Value>,
+        properties: &mut HashMap<String, Value>,
+    ) -> ConstraintReport {
+        let mut report = ConstraintReport::default();
+
+        for port in &self.inputs {
+            if let Some(value) = inputs.get(&port.id) {
+                match constrain(value, port.min, port.max, port.out_of_range) {
+                    Ok(value) => {
+                        inputs.insert(port.id.clone(), value);
+                    }
+                    Err(e) => {
+                        report.warnings.push(format!("{}: {}", port.name, e));
+                        inputs.insert(port.id.clone(), port.default.clone());
+                    }
+                }
+            }
+            if port.required && inputs.get(&port.id).is_none_or(|v| *v == Value::Nil) {
+                report.missing.push(port.name.clone());
+            }
+        }
+
+        for prop in &self.properties {
+            if let Some(value) = properties.get(&prop.id) {
+                match constrain(value, prop.min, prop.max, prop.out_of_range) {
+                    Ok(value) => {
+                        properties.insert(prop.id.clone(), value);
+                    }
+                    Err(e) => {
+                        report.warnings.push(format!("{}: {}", prop.name, e));
+                        properties.insert(prop.id.clone(), prop.default.clone());
+                    }
+                }
+            }
+        }
+
+        report
+    }
+
     /// 计算Block显示所需的高度
     pub fn calculate_height(&self) -> f32 {
         let port_count = self.inputs.len().max(self.outputs.len());
@@ -193,3 +460,35 @@ impl BlockDefinition {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coercion_table() {
+        let lenient = CoercionMode::Lenient;
+        let strict = CoercionMode::Strict;
+        let string = |s: &str| Value::String(s.to_string());
+
+        assert_eq!(coerce(&string(" 42 "), &DataType::Number, None, lenient), Ok(Value::Number(42.0)));
+        assert_eq!(coerce(&Value::Boolean(true), &DataType::Number, None, lenient), Ok(Value::Number(1.0)));
+        assert_eq!(coerce(&Value::Number(3.0), &DataType::String, None, lenient), Ok(string("3")));
+        assert_eq!(coerce(&Value::Number(0.5), &DataType::String, None, lenient), Ok(string("0.5")));
+        assert_eq!(
+            coerce(&string("7"), &DataType::Array, Some(&DataType::Number), lenient),
+            Ok(Value::Array(vec![Value::Number(7.0)]))
+        );
+        assert_eq!(coerce(&Value::Nil, &DataType::Number, None, strict), Ok(Value::Nil));
+
+        let err = coerce(&string("abc"), &DataType::Number, None, lenient).unwrap_err();
+        assert_eq!(err.to_string(), "无法将 string \"abc\" 转换为 number");
+        assert!(coerce(&Value::Number(1.0), &DataType::String, None, strict).is_err());
+        assert!(coerce(&Value::Number(1.0), &DataType::Array, None, strict).is_err());
+
+        // 转换表之外的组合不转换
+        assert!(coerce(&string("false"), &DataType::Boolean, None, lenient).is_err());
+        assert!(coerce(&Value::Number(1.0), &DataType::Boolean, None, lenient).is_err());
+        assert!(coerce(&Value::Boolean(true), &DataType::String, None, lenient).is_err());
+    }
+}
@@ -22,6 +22,8 @@ impl BlockWidget {
     const ROUNDING: f32 = 6.0;
     const MINI_WIDTH: f32 = 80.0;
     const MINI_HEIGHT: f32 = 36.0;
+    /// 故障Block的边框颜色
+    const FAULT_COLOR: Color32 = Color32::from_rgb(255, 150, 0);
 
     /// 绘制Block
     pub fn draw(
@@ -40,10 +42,12 @@ impl BlockWidget {
         let body_color = Color32::from_rgb(40, 40, 44);
         let border_color = if block.selected {
             Color32::from_rgb(255, 100, 100)  // 红色选中边框
+        } else if block.fault.is_some() {
+            Self::FAULT_COLOR
         } else {
             Color32::from_gray(70)
         };
-        let border_width = if block.selected { 2.5 } else { 1.0 };
+        let border_width = if block.selected || block.fault.is_some() { 2.5 } else { 1.0 };
 
         // 绘制阴影
         if viewport.zoom > 0.5 {
@@ -96,6 +100,26 @@ impl BlockWidget {
             Color32::WHITE,
         );
 
+        // 故障/警告标记
+        let marker = if block.fault.is_some() {
+            Some(("⚠", Self::FAULT_COLOR))
+        } else if !block.waiting_for.is_empty() {
+            Some(("⏳", Color32::from_rgb(150, 180, 255)))
+        } else if !block.warnings.is_empty() {
+            Some(("!", Color32::from_rgb(255, 220, 100)))
+        } else {
+            None
+        };
+        if let Some((text, color)) = marker {
+            painter.text(
+                Pos2::new(pos.x + size.x - 8.0 * viewport.zoom, pos.y + 6.0 * viewport.zoom),
+                egui::Align2::RIGHT_TOP,
+                text,
+                FontId::proportional(12.0 * viewport.zoom),
+                color,
+            );
+        }
+
         // 绘制端口
         let port_y_start = pos.y + Self::HEADER_HEIGHT * viewport.zoom;
 
@@ -134,10 +158,12 @@ impl BlockWidget {
         let header_color = Self::parse_color(&definition.meta.color);
         let border_color = if block.selected {
             Color32::from_rgb(255, 100, 100)
+        } else if block.fault.is_some() {
+            Self::FAULT_COLOR
         } else {
             Color32::from_gray(70)
         };
-        let border_width = if block.selected { 2.5 } else { 1.0 };
+        let border_width = if block.selected || block.fault.is_some() { 2.5 } else { 1.0 };
 
         // 绘制阴影
         if viewport.zoom > 0.5 {
@@ -112,6 +112,21 @@ impl ConnectionWidget {
         }
     }
 
+    /// 在连线中间绘制反馈标记（读取上一 tick 的值）
+    pub fn draw_feedback_marker(painter: &Painter, from: Pos2, to: Pos2) {
+        let mid = Pos2::new((from.x + to.x) / 2.0, (from.y + to.y) / 2.0);
+        let rect = egui::Rect::from_center_size(mid, egui::Vec2::new(22.0, 14.0));
+        painter.rect_filled(rect, egui::Rounding::same(3.0), Color32::from_rgba_unmultiplied(40, 40, 50, 220));
+        painter.rect_stroke(rect, egui::Rounding::same(3.0), Stroke::new(1.0, Color32::from_rgb(255, 180, 60)));
+        painter.text(
+            mid,
+            egui::Align2::CENTER_CENTER,
+            "⏱",
+            egui::FontId::proportional(10.0),
+            Color32::from_rgb(255, 180, 60),
+        );
+    }
+
     /// 绘制连线（根据模式）- 优化版
     fn draw_line(painter: &Painter, from: Pos2, to: Pos2, color: Color32, width: f32, flow_phase: f32) {
         let mode = Self::mode();
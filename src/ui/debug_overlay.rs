@@ -0,0 +1,47 @@
+//! 调试叠加层 - 在画布上标出断点和暂停中的 Block
+
+use super::Canvas;
+use crate::workflow::{Pause, Workflow};
+use egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Stroke};
+
+const BREAKPOINT_COLOR: Color32 = Color32::from_rgb(220, 60, 60);
+const PAUSED_COLOR: Color32 = Color32::from_rgb(255, 210, 40);
+
+/// 调试叠加层
+pub struct DebugOverlay;
+
+impl DebugOverlay {
+    /// 绘制断点标记（左上角红点，带条件时为空心）和暂停位置
+    pub fn draw(painter: &Painter, workflow: &Workflow, paused: Option<&Pause>, canvas_offset: Pos2) {
+        let viewport = &workflow.viewport;
+        let radius = 5.0 * viewport.zoom;
+
+        for block in workflow.blocks.values() {
+            let Some(breakpoint) = &block.breakpoint else { continue };
+            let center = Canvas::vec2_to_pos2(block.position, viewport, canvas_offset) + egui::vec2(-radius, -radius);
+            if breakpoint.condition.trim().is_empty() {
+                painter.circle_filled(center, radius, BREAKPOINT_COLOR);
+            } else {
+                painter.circle(center, radius, BREAKPOINT_COLOR.gamma_multiply(0.4), Stroke::new(2.0, BREAKPOINT_COLOR));
+            }
+        }
+
+        let Some(pause) = paused else { return };
+        let Some(block) = workflow.blocks.get(&pause.block_id) else { return };
+        let min = Canvas::vec2_to_pos2(block.position, viewport, canvas_offset);
+        let rect = Rect::from_min_size(min, egui::vec2(block.size.x, block.size.y) * viewport.zoom).expand(4.0);
+        painter.rect_stroke(rect, 8.0, Stroke::new(3.0, PAUSED_COLOR));
+
+        let label = match &pause.condition_error {
+            Some(error) => format!("⏸ {}", error),
+            None => "⏸ 暂停".to_string(),
+        };
+        painter.text(
+            Pos2::new(rect.min.x, rect.min.y - 4.0),
+            Align2::LEFT_BOTTOM,
+            label,
+            FontId::proportional(11.0 * viewport.zoom),
+            PAUSED_COLOR,
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! 差异叠加层 - 在画布上标出与上次保存版本的差异
+
+use super::{BlockWidget, Canvas};
+use crate::script::ScriptRegistry;
+use crate::workflow::{Block, ChangeKind, DiffTarget, Viewport, Workflow, WorkflowDiff};
+use egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Shape, Stroke};
+
+const ADDED_COLOR: Color32 = Color32::from_rgb(80, 200, 120);
+const REMOVED_COLOR: Color32 = Color32::from_rgb(230, 80, 80);
+const MODIFIED_COLOR: Color32 = Color32::from_rgb(240, 190, 60);
+const MOVED_COLOR: Color32 = Color32::from_rgb(160, 160, 180);
+/// 修改的 Block 下方最多列出的字段数
+const MAX_FIELD_LABELS: usize = 3;
+
+/// 差异叠加层
+pub struct DiffOverlay;
+
+impl DiffOverlay {
+    /// 绘制差异（saved 为上次保存的版本，current 为当前编辑的版本）
+    pub fn draw(
+        painter: &Painter,
+        diff: &WorkflowDiff,
+        saved: &Workflow,
+        current: &Workflow,
+        registry: &ScriptRegistry,
+        canvas_offset: Pos2,
+    ) {
+        let viewport = &current.viewport;
+
+        // 分组
+        for entry in diff.of(DiffTarget::Group) {
+            let Some(id) = entry.id else { continue };
+            let source = if entry.change == ChangeKind::Removed { saved } else { current };
+            if let Some(group) = source.groups.get(&id) {
+                let min = Canvas::vec2_to_pos2(group.position, viewport, canvas_offset);
+                let rect = Rect::from_min_size(min, egui::vec2(group.size.x, group.size.y) * viewport.zoom);
+                Self::outline(painter, rect, entry.change, 8.0);
+            }
+        }
+
+        // 连线（端点优先取当前版本中的 Block）
+        for entry in diff.of(DiffTarget::Connection) {
+            let Some(id) = entry.id else { continue };
+            let source = if entry.change == ChangeKind::Removed { saved } else { current };
+            let Some(conn) = source.connections.get(&id) else { continue };
+            let block = |block_id| current.blocks.get(block_id).or_else(|| saved.blocks.get(block_id));
+            let (Some(from_block), Some(to_block)) = (block(&conn.from_block), block(&conn.to_block)) else { continue };
+
+            let from_idx = registry.get(&from_block.script_id)
+                .and_then(|def| from_block.all_outputs(def).iter().position(|p| p.id == conn.from_port))
+                .unwrap_or(0);
+            let to_idx = registry.get(&to_block.script_id)
+                .and_then(|def| def.inputs.iter().position(|p| p.id == conn.to_port))
+                .unwrap_or(0);
+            let from = BlockWidget::get_port_screen_pos(from_block, from_idx, true, viewport, canvas_offset);
+            let to = BlockWidget::get_port_screen_pos(to_block, to_idx, false, viewport, canvas_offset);
+
+            match entry.change {
+                ChangeKind::Removed => {
+                    painter.extend(Shape::dashed_line(&[from, to], Stroke::new(2.0, REMOVED_COLOR), 6.0, 4.0));
+                }
+                change => {
+                    painter.line_segment([from, to], Stroke::new(4.0, Self::color(change).gamma_multiply(0.5)));
+                }
+            }
+        }
+
+        // Block
+        for entry in diff.of(DiffTarget::Block) {
+            let Some(id) = entry.id else { continue };
+            match entry.change {
+                ChangeKind::Added => {
+                    if let Some(block) = current.blocks.get(&id) {
+                        Self::outline(painter, Self::block_rect(block, viewport, canvas_offset), entry.change, 6.0);
+                    }
+                }
+                ChangeKind::Removed => {
+                    if let Some(block) = saved.blocks.get(&id) {
+                        let rect = Self::block_rect(block, viewport, canvas_offset);
+                        painter.rect_filled(rect, 6.0, REMOVED_COLOR.gamma_multiply(0.12));
+                        Self::outline(painter, rect, entry.change, 6.0);
+                        painter.text(
+                            rect.center(),
+                            Align2::CENTER_CENTER,
+                            format!("已删除: {}", entry.label),
+                            FontId::proportional(11.0 * viewport.zoom),
+                            REMOVED_COLOR,
+                        );
+                    }
+                }
+                ChangeKind::Modified => {
+                    let (Some(old), Some(block)) = (saved.blocks.get(&id), current.blocks.get(&id)) else { continue };
+                    let rect = Self::block_rect(block, viewport, canvas_offset);
+
+                    // 原位置的虚影和移动箭头
+                    if entry.moved() {
+                        let old_rect = Self::block_rect(old, viewport, canvas_offset);
+                        Self::dashed_rect(painter, old_rect, Stroke::new(1.5, MOVED_COLOR));
+                        painter.arrow(old_rect.center(), rect.center() - old_rect.center(), Stroke::new(1.5, MOVED_COLOR));
+                    }
+
+                    if entry.edited() {
+                        Self::outline(painter, rect, entry.change, 6.0);
+                        let mut fields: Vec<&str> = entry.fields.iter()
+                            .map(|f| f.field.as_str())
+                            .filter(|f| *f != crate::workflow::POSITION_FIELD)
+                            .collect();
+                        if fields.len() > MAX_FIELD_LABELS {
+                            fields.truncate(MAX_FIELD_LABELS);
+                            fields.push("…");
+                        }
+                        painter.text(
+                            Pos2::new(rect.min.x, rect.max.y + 4.0),
+                            Align2::LEFT_TOP,
+                            fields.join(", "),
+                            FontId::proportional(10.0 * viewport.zoom),
+                            MODIFIED_COLOR,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn color(change: ChangeKind) -> Color32 {
+        match change {
+            ChangeKind::Added => ADDED_COLOR,
+            ChangeKind::Removed => REMOVED_COLOR,
+            ChangeKind::Modified => MODIFIED_COLOR,
+        }
+    }
+
+    fn block_rect(block: &Block, viewport: &Viewport, canvas_offset: Pos2) -> Rect {
+        let min = Canvas::vec2_to_pos2(block.position, viewport, canvas_offset);
+        Rect::from_min_size(min, egui::vec2(block.size.x, block.size.y) * viewport.zoom)
+    }
+
+    /// 描边：删除的对象用虚线
+    fn outline(painter: &Painter, rect: Rect, change: ChangeKind, rounding: f32) {
+        let stroke = Stroke::new(2.5, Self::color(change));
+        if change == ChangeKind::Removed {
+            Self::dashed_rect(painter, rect, stroke);
+        } else {
+            painter.rect_stroke(rect.expand(3.0), rounding, stroke);
+        }
+    }
+
+    fn dashed_rect(painter: &Painter, rect: Rect, stroke: Stroke) {
+        let points = [rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom(), rect.left_top()];
+        painter.extend(Shape::dashed_line(&points, stroke, 6.0, 4.0));
+    }
+}
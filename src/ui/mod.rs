@@ -3,6 +3,8 @@
 mod block_widget;
 mod canvas;
 mod connection_widget;
+mod debug_overlay;
+mod diff_overlay;
 mod layer_panel;
 mod menu;
 mod property_panel;
@@ -10,6 +12,8 @@ mod property_panel;
 pub use block_widget::{BlockWidget, ConnectionIndicator};
 pub use canvas::Canvas;
 pub use connection_widget::{ConnectionMode, ConnectionWidget};
+pub use debug_overlay::DebugOverlay;
+pub use diff_overlay::DiffOverlay;
 pub use layer_panel::{LayerEvent, LayerPanel};
 pub use menu::{MenuEvent, SideMenu};
 pub use property_panel::PropertyPanel;
@@ -77,11 +77,21 @@ impl PropertyPanel {
                     .unwrap_or(0.0);
                 
                 let mut drag = DragValue::new(&mut val).speed(0.1);
-                if let Some(min) = min {
-                    drag = drag.range(min..=max.unwrap_or(f64::MAX));
+                if min.is_some() || max.is_some() {
+                    // 与执行时的约束一致
+                    drag = drag.range(min.unwrap_or(f64::MIN)..=max.unwrap_or(f64::MAX));
                 }
-                
-                if ui.add(drag).changed() {
+
+                let response = ui.add(drag);
+                let response = match (min, max) {
+                    (None, None) => response,
+                    (min, max) => response.on_hover_text(format!(
+                        "范围: {} ~ {}",
+                        min.map_or("-∞".to_string(), |v| v.to_string()),
+                        max.map_or("∞".to_string(), |v| v.to_string())
+                    )),
+                };
+                if response.changed() {
                     return Some(PropertyChange {
                         property_id: prop_id.to_string(),
                         new_value: Value::Number(val),
@@ -1,6 +1,7 @@
 //! Block实例 - 画布上的节点
 
-use crate::script::{BlockDefinition, DataType, PortDefinition, Value};
+use crate::script::{BlockDefinition, CoercionMode, DataType, PortDefinition, RangePolicy, Value};
+use super::Breakpoint;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -42,15 +43,20 @@ pub struct Block {
     #[serde(default)]
     pub custom_name: Option<String>,
 
-    // 运行时值
+    // 运行时值（输出每次执行重新计算，不保存）
+    #[serde(serialize_with = "super::ordered::map")]
     pub input_values: HashMap<String, Value>,
+    #[serde(default, skip_serializing)]
     pub output_values: HashMap<String, Value>,
+    #[serde(serialize_with = "super::ordered::map")]
     pub properties: HashMap<String, Value>,
 
     // 持久化状态（跨执行周期保留）
+    #[serde(serialize_with = "super::ordered::map")]
     pub state: HashMap<String, Value>,
 
-    // UI状态
+    // UI状态（选中状态不保存）
+    #[serde(skip)]
     pub selected: bool,
     pub collapsed: bool,
     pub group_id: Option<Uuid>,
@@ -66,25 +72,46 @@ pub struct Block {
     #[serde(skip)]
     pub animation_speed: f32,
 
-    /// 交互控件状态（用于输入框等）
-    #[serde(default)]
+    /// 交互控件状态（用于输入框等，默认值不保存）
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub widget_text: String,
     /// 控件是否正在编辑
     #[serde(skip)]
     pub widget_editing: bool,
     /// 下拉选择的索引
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_default")]
     pub widget_selected_index: usize,
     /// 复选框/按钮状态
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_default")]
     pub widget_checked: bool,
     /// 滑块值
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_default")]
     pub widget_slider_value: f32,
 
     /// 动态输出端口（运行时生成）
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub dynamic_outputs: Vec<PortDefinition>,
+
+    /// 故障信息（超出资源限制被中止后设置，执行引擎会跳过故障Block）
+    #[serde(skip)]
+    pub fault: Option<String>,
+
+    /// 最近一次执行的警告（如输入值转换失败）
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+
+    /// 等待中的必需输入（非空时跳过执行）
+    #[serde(skip)]
+    pub waiting_for: Vec<String>,
+
+    /// 断点（执行到该Block之前暂停）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakpoint: Option<Breakpoint>,
+}
+
+/// 是否为默认值（用于跳过保存）
+pub(super) fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
 }
 
 impl Block {
@@ -129,6 +156,10 @@ impl Block {
             widget_checked: false,
             widget_slider_value: 0.0,
             dynamic_outputs: Vec::new(),
+            fault: None,
+            warnings: Vec::new(),
+            waiting_for: Vec::new(),
+            breakpoint: None,
         }
     }
 
@@ -168,6 +199,8 @@ impl Block {
                     element_type: None,
                     min: None,
                     max: None,
+                    out_of_range: RangePolicy::Clamp,
+                    coerce: CoercionMode::Lenient,
                 });
             }
         }
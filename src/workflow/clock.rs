@@ -0,0 +1,153 @@
+//! 模拟时钟 - 固定步长推进的模拟时间
+//!
+//! 每个 tick 前进固定的 `dt`（乘以 `time_scale`），与帧率和执行速度无关，
+//! 同一工作流在编辑器、播放器和命令行中执行相同的 tick 数得到相同的结果。
+//! 脚本通过 `execute(self, inputs, ctx)` 的 ctx 读取 tick / elapsed / dt / time_scale。
+
+use mlua::{Lua, Table};
+use std::time::{Duration, Instant};
+
+/// 默认步长（秒），即每秒 10 个 tick
+pub const DEFAULT_DT: f64 = 0.1;
+
+/// 固定步长循环一次最多补执行的 tick 数（落后更多时丢弃，避免越追越慢）
+const MAX_CATCH_UP: u32 = 5;
+
+/// 模拟时钟
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationClock {
+    /// 当前 tick（第一个 tick 为 1，尚未执行时为 0）
+    pub tick: u64,
+    /// 已经过的模拟时间（秒，含时间缩放）
+    pub elapsed: f64,
+    /// 每个 tick 的固定步长（秒）
+    pub dt: f64,
+    /// 时间缩放（1 为正常速度，只影响脚本看到的 dt 和 elapsed）
+    pub time_scale: f64,
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self {
+            tick: 0,
+            elapsed: 0.0,
+            dt: DEFAULT_DT,
+            time_scale: 1.0,
+        }
+    }
+}
+
+impl SimulationClock {
+    /// 本 tick 的模拟时间增量
+    pub fn scaled_dt(&self) -> f64 {
+        self.dt * self.time_scale
+    }
+
+    /// 前进一个 tick
+    pub fn advance(&mut self) {
+        self.tick += 1;
+        self.elapsed += self.scaled_dt();
+    }
+
+    /// 回到第 0 个 tick（保留步长和时间缩放）
+    pub fn reset(&mut self) {
+        self.tick = 0;
+        self.elapsed = 0.0;
+    }
+
+    /// 构建传给脚本的 ctx 表
+    pub fn to_lua(self, lua: &Lua) -> mlua::Result<Table> {
+        let ctx = lua.create_table()?;
+        ctx.set("tick", self.tick)?;
+        ctx.set("elapsed", self.elapsed)?;
+        ctx.set("dt", self.scaled_dt())?;
+        ctx.set("time_scale", self.time_scale)?;
+        Ok(ctx)
+    }
+}
+
+/// 固定步长循环：按实际经过的时间决定要执行几个 tick（编辑器执行线程和播放器共用）
+#[derive(Debug, Clone)]
+pub struct FixedStep {
+    interval: Duration,
+    accumulated: Duration,
+    last: Instant,
+}
+
+impl FixedStep {
+    /// 每秒执行 hz 个 tick
+    pub fn new(hz: f32) -> Self {
+        Self {
+            interval: Self::interval_for(hz),
+            accumulated: Duration::ZERO,
+            last: Instant::now(),
+        }
+    }
+
+    fn interval_for(hz: f32) -> Duration {
+        Duration::from_secs_f32(1.0 / hz.max(0.1))
+    }
+
+    /// 修改执行速度
+    pub fn set_rate(&mut self, hz: f32) {
+        self.interval = Self::interval_for(hz);
+    }
+
+    /// 重新开始计时，下一次 `due` 立即执行一个 tick
+    pub fn restart(&mut self) {
+        self.accumulated = self.interval;
+        self.last = Instant::now();
+    }
+
+    /// 距离上次调用累积的 tick 数
+    pub fn due(&mut self) -> u32 {
+        let now = Instant::now();
+        self.accumulated += now - self.last;
+        self.last = now;
+
+        let mut ticks = 0;
+        while self.accumulated >= self.interval && ticks < MAX_CATCH_UP {
+            self.accumulated -= self.interval;
+            ticks += 1;
+        }
+        if ticks == MAX_CATCH_UP {
+            self.accumulated = Duration::ZERO;
+        }
+        ticks
+    }
+
+    /// 距离下一个 tick 的时间
+    pub fn until_next(&self) -> Duration {
+        self.interval.saturating_sub(self.accumulated + self.last.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_advances_with_time_scale() {
+        let mut clock = SimulationClock::default();
+        clock.advance();
+        clock.time_scale = 0.5;
+        clock.advance();
+        assert_eq!(clock.tick, 2);
+        assert!((clock.elapsed - 0.15).abs() < 1e-9);
+
+        clock.reset();
+        assert_eq!((clock.tick, clock.elapsed, clock.time_scale), (0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn test_fixed_step_catches_up_with_limit() {
+        let mut step = FixedStep::new(10.0);
+        step.restart();
+        assert_eq!(step.due(), 1);
+
+        // 落后 10 秒也只补执行有限个 tick
+        step.last -= Duration::from_secs(10);
+        assert_eq!(step.due(), MAX_CATCH_UP);
+        assert!(step.until_next() > Duration::from_millis(50));
+    }
+}
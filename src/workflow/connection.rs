@@ -1,8 +1,30 @@
 //! 连接 - 两个端口之间的连线
 
+use super::Block;
+use crate::script::ScriptRegistry;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
 use uuid::Uuid;
 
+/// 连线校验错误
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ConnectionError {
+    #[error("找不到Block: {0}")]
+    MissingBlock(Uuid),
+    #[error("找不到Block定义: {0}")]
+    MissingDefinition(String),
+    #[error("端口不存在: {block}.{port}")]
+    MissingPort { block: String, port: String },
+    #[error("类型不兼容: {from_port} ({from_type}) -> {to_port} ({to_type})")]
+    TypeMismatch {
+        from_port: String,
+        from_type: String,
+        to_port: String,
+        to_type: String,
+    },
+}
+
 /// 连接
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Connection {
@@ -11,7 +33,10 @@ pub struct Connection {
     pub from_port: String,
     pub to_block: Uuid,
     pub to_port: String,
-    #[serde(default)]
+    /// 反馈连线：读取来源上一 tick 的值，不参与执行排序（用于打破循环）
+    #[serde(default, skip_serializing_if = "super::block::is_default")]
+    pub delayed: bool,
+    #[serde(skip)]
     pub selected: bool,
 }
 
@@ -23,9 +48,49 @@ impl Connection {
             from_port,
             to_block,
             to_port,
+            delayed: false,
             selected: false,
         }
     }
+
+    /// 检查两端端口存在且数据类型兼容
+    pub fn validate(&self, blocks: &HashMap<Uuid, Block>, registry: &ScriptRegistry) -> Result<(), ConnectionError> {
+        let from_block = blocks.get(&self.from_block).ok_or(ConnectionError::MissingBlock(self.from_block))?;
+        let to_block = blocks.get(&self.to_block).ok_or(ConnectionError::MissingBlock(self.to_block))?;
+        let from_def = registry
+            .get(&from_block.script_id)
+            .ok_or_else(|| ConnectionError::MissingDefinition(from_block.script_id.clone()))?;
+        let to_def = registry
+            .get(&to_block.script_id)
+            .ok_or_else(|| ConnectionError::MissingDefinition(to_block.script_id.clone()))?;
+
+        let output = from_block
+            .all_outputs(from_def)
+            .into_iter()
+            .find(|p| p.id == self.from_port)
+            .ok_or_else(|| ConnectionError::MissingPort {
+                block: from_block.script_id.clone(),
+                port: self.from_port.clone(),
+            })?;
+        let input = to_def
+            .inputs
+            .iter()
+            .find(|p| p.id == self.to_port)
+            .ok_or_else(|| ConnectionError::MissingPort {
+                block: to_block.script_id.clone(),
+                port: self.to_port.clone(),
+            })?;
+
+        if !output.can_connect_to(input) {
+            return Err(ConnectionError::TypeMismatch {
+                from_port: self.from_port.clone(),
+                from_type: output.type_label(),
+                to_port: self.to_port.clone(),
+                to_type: input.type_label(),
+            });
+        }
+        Ok(())
+    }
 }
 
 /// 拖拽中的临时连接
@@ -37,3 +102,56 @@ pub struct DraggingConnection {
     pub mouse_pos: (f32, f32),
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::test_support::TestRegistry;
+    use crate::workflow::{Vec2, Workflow};
+
+    const SOURCE_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.source", name = "来源" },
+    outputs = {
+        { id = "text", name = "文本", type = "string" },
+        { id = "numbers", name = "数列", type = "array", element_type = "number" },
+        { id = "anything", name = "任意", type = "any" },
+    },
+    execute = function(self, inputs) return {} end
+}
+"#;
+
+    const SINK_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.sink", name = "目标" },
+    inputs = {
+        { id = "value", name = "数值", type = "number" },
+        { id = "names", name = "名称", type = "array", element_type = "string" },
+    },
+    execute = function(self, inputs) return {} end
+}
+"#;
+
+    #[test]
+    fn test_add_connection_checks_types() {
+        let registry = TestRegistry::new("connection", &[("source.lua", SOURCE_SCRIPT), ("sink.lua", SINK_SCRIPT)]);
+
+        let mut workflow = Workflow::new("test");
+        let source = workflow.add_block(Block::new(registry.get("test.source").unwrap(), Vec2::new(0.0, 0.0)));
+        let sink = workflow.add_block(Block::new(registry.get("test.sink").unwrap(), Vec2::new(200.0, 0.0)));
+        let mut connect = |from: &str, to: &str| {
+            workflow.add_connection(Connection::new(source, from.into(), sink, to.into()), &registry)
+        };
+
+        assert!(matches!(connect("text", "value"), Err(ConnectionError::TypeMismatch { .. })));
+        assert!(matches!(connect("numbers", "names"), Err(ConnectionError::TypeMismatch { .. })));
+        assert!(matches!(connect("missing", "value"), Err(ConnectionError::MissingPort { .. })));
+        assert!(connect("anything", "value").is_ok());
+        assert_eq!(workflow.connections.len(), 1);
+
+        // 旧文件中的不合法连线由校验报告
+        let bad = Connection::new(source, "text".into(), sink, "value".into());
+        workflow.connections.insert(bad.id, bad);
+        assert_eq!(workflow.validate(&registry).len(), 1);
+    }
+}
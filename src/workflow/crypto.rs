@@ -0,0 +1,217 @@
+//! 口令加密容器
+//!
+//! .LZ 和 .lpak 共用的加密载荷：
+//! [1字节 KDF: 1 = Argon2id]
+//! [4字节 内存(KiB)] [4字节 迭代次数] [4字节 并行度]
+//! [16字节 随机盐]
+//! [12字节 随机 nonce]
+//! [N字节 AES-256-GCM 密文 + 16字节认证标签]
+//!
+//! 调用者的文件头（魔数 + 版本）作为附加认证数据，篡改任何字节都会解密失败。
+//! 旧版固定密钥 AES-128-CBC 只保留解密，用于读取历史文件。
+
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use aes_gcm::aead::{rand_core::RngCore, Aead, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use thiserror::Error;
+
+/// KDF 标识：Argon2id
+const KDF_ARGON2ID: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+/// KDF 字段长度（标识 + 三个参数）
+const KDF_LEN: usize = 1 + 4 * 3;
+const HEADER_LEN: usize = KDF_LEN + SALT_LEN + NONCE_LEN;
+/// 读取文件时允许的最大 KDF 参数（防止恶意文件在认证失败前耗尽内存或 CPU）
+const MAX_MEMORY_KIB: u32 = 256 * 1024;
+const MAX_ITERATIONS: u32 = 16;
+const MAX_PARALLELISM: u32 = 16;
+
+/// 加密容器错误
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("加密数据不完整")]
+    Truncated,
+    #[error("不支持的密钥派生算法: {0}")]
+    UnsupportedKdf(u8),
+    #[error("密钥派生失败: {0}")]
+    Kdf(String),
+    #[error("密码错误或文件已损坏")]
+    WrongPassphrase,
+    #[error("加密失败")]
+    Encrypt,
+}
+
+/// Argon2id 参数（写入文件头，调整默认值不影响旧文件）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    /// 检查文件头中的参数是否在允许范围内
+    fn check(&self) -> Result<(), CryptoError> {
+        if self.memory_kib > MAX_MEMORY_KIB {
+            return Err(CryptoError::Kdf(format!("内存参数过大: {} KiB", self.memory_kib)));
+        }
+        if self.iterations > MAX_ITERATIONS {
+            return Err(CryptoError::Kdf(format!("迭代次数过大: {}", self.iterations)));
+        }
+        if self.parallelism > MAX_PARALLELISM {
+            return Err(CryptoError::Kdf(format!("并行度过大: {}", self.parallelism)));
+        }
+        Ok(())
+    }
+
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> Result<[u8; KEY_LEN], CryptoError> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_LEN))
+            .map_err(|e| CryptoError::Kdf(e.to_string()))?;
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| CryptoError::Kdf(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+/// 使用口令加密，`aad` 为调用者的文件头
+pub fn seal(plaintext: &[u8], passphrase: &str, aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    seal_with(plaintext, passphrase, aad, KdfParams::default())
+}
+
+/// 使用指定 KDF 参数加密
+pub fn seal_with(plaintext: &[u8], passphrase: &str, aad: &[u8], params: KdfParams) -> Result<Vec<u8>, CryptoError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut header = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+    header.push(KDF_ARGON2ID);
+    header.extend_from_slice(&params.memory_kib.to_le_bytes());
+    header.extend_from_slice(&params.iterations.to_le_bytes());
+    header.extend_from_slice(&params.parallelism.to_le_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let key = params.derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &authenticated(aad, &header) })
+        .map_err(|_| CryptoError::Encrypt)?;
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+/// 使用口令解密
+pub fn open(data: &[u8], passphrase: &str, aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if data.len() < HEADER_LEN {
+        return Err(CryptoError::Truncated);
+    }
+    if data[0] != KDF_ARGON2ID {
+        return Err(CryptoError::UnsupportedKdf(data[0]));
+    }
+
+    let read_u32 = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+    let params = KdfParams {
+        memory_kib: read_u32(1),
+        iterations: read_u32(5),
+        parallelism: read_u32(9),
+    };
+    params.check()?;
+    let salt = &data[KDF_LEN..KDF_LEN + SALT_LEN];
+    let nonce = &data[KDF_LEN + SALT_LEN..HEADER_LEN];
+
+    let key = params.derive_key(passphrase, salt)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: &data[HEADER_LEN..], aad: &authenticated(aad, &data[..HEADER_LEN]) })
+        .map_err(|_| CryptoError::WrongPassphrase)
+}
+
+/// 附加认证数据 = 文件头 + KDF/盐/nonce
+fn authenticated(aad: &[u8], header: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(aad.len() + header.len());
+    buf.extend_from_slice(aad);
+    buf.extend_from_slice(header);
+    buf
+}
+
+/// 旧版 AES-128-CBC 解密（固定密钥和 IV）
+pub fn legacy_decrypt(data: &[u8], key: [u8; 16], iv: [u8; 16]) -> Option<Vec<u8>> {
+    let mut buf = data.to_vec();
+    cbc::Decryptor::<aes::Aes128>::new(&key.into(), &iv.into())
+        .decrypt_padded_mut::<Pkcs7>(&mut buf)
+        .ok()
+        .map(|pt| pt.to_vec())
+}
+
+/// 旧版 AES-128-CBC 加密（仅用于生成历史格式的测试数据）
+#[cfg(test)]
+pub fn legacy_encrypt(data: &[u8], key: [u8; 16], iv: [u8; 16]) -> Vec<u8> {
+    use aes::cipher::BlockEncryptMut;
+    let mut buf = vec![0u8; (data.len() / 16 + 1) * 16];
+    buf[..data.len()].copy_from_slice(data);
+    cbc::Encryptor::<aes::Aes128>::new(&key.into(), &iv.into())
+        .encrypt_padded_mut::<Pkcs7>(&mut buf, data.len())
+        .unwrap()
+        .to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试用低成本参数
+    const FAST: KdfParams = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+
+    #[test]
+    fn test_seal_open() {
+        let sealed = seal_with(b"hello", "secret", b"HDR", FAST).unwrap();
+        assert_eq!(open(&sealed, "secret", b"HDR").unwrap(), b"hello");
+
+        // 每次加密使用新的盐和 nonce
+        let again = seal_with(b"hello", "secret", b"HDR", FAST).unwrap();
+        assert_ne!(sealed, again);
+
+        assert!(matches!(open(&sealed, "wrong", b"HDR"), Err(CryptoError::WrongPassphrase)));
+        assert!(matches!(open(&sealed, "secret", b"OTHER"), Err(CryptoError::WrongPassphrase)));
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(open(&tampered, "secret", b"HDR"), Err(CryptoError::WrongPassphrase)));
+    }
+
+    #[test]
+    fn test_open_rejects_excessive_kdf_params() {
+        let sealed = seal_with(b"hello", "secret", b"HDR", FAST).unwrap();
+        // 内存、迭代次数、并行度分别位于偏移 1、5、9
+        for (offset, value, field) in [
+            (1, MAX_MEMORY_KIB + 1, "内存参数过大"),
+            (5, MAX_ITERATIONS + 1, "迭代次数过大"),
+            (9, MAX_PARALLELISM + 1, "并行度过大"),
+        ] {
+            let mut crafted = sealed.clone();
+            crafted[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            match open(&crafted, "secret", b"HDR") {
+                Err(CryptoError::Kdf(message)) => assert!(message.contains(field), "{}", message),
+                other => panic!("{}: {:?}", field, other),
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! 断点调试 - 在Block执行前暂停 tick
+//!
+//! 断点保存在Block上，可带 Lua 条件表达式。执行引擎按 `StepMode` 推进 tick：
+//! 命中断点或单步时停在Block执行之前并保留本 tick 的进度，继续时从该Block开始。
+
+use crate::script::{BlockDefinition, Value};
+use crate::workflow::Workflow;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Block断点
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    /// 条件表达式（可使用 inputs / state / properties / ctx，为空时总是暂停）
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub condition: String,
+}
+
+/// 推进 tick 的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    /// 执行到下一个命中的断点或 tick 结束
+    Continue,
+    /// 停在下一个要执行的Block之前
+    Block,
+    /// 忽略断点，执行完当前 tick
+    Tick,
+}
+
+/// 暂停时的Block信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pause {
+    pub block_id: Uuid,
+    /// 即将传给脚本的输入（含收到的事件负载）
+    pub inputs: HashMap<String, Value>,
+    /// 条件表达式出错时的错误信息（出错同样暂停）
+    pub condition_error: Option<String>,
+}
+
+/// tick 推进结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum TickStatus {
+    Completed,
+    Paused(Pause),
+}
+
+impl Workflow {
+    /// Block执行时将收到的输入（不取走事件）
+    pub fn preview_inputs(&self, block_id: Uuid, definition: &BlockDefinition) -> HashMap<String, Value> {
+        let mut inputs = self.blocks.get(&block_id).map(|b| b.input_values.clone()).unwrap_or_default();
+        inputs.extend(self.connected_inputs(block_id, definition).0);
+        for ((id, port), payload) in &self.pending_events {
+            if *id == block_id {
+                inputs.insert(port.clone(), payload.clone());
+            }
+        }
+        inputs
+    }
+
+    /// 设置或移除Block断点
+    pub fn set_breakpoint(&mut self, block_id: Uuid, breakpoint: Option<Breakpoint>) {
+        if let Some(block) = self.blocks.get_mut(&block_id) {
+            block.breakpoint = breakpoint;
+            self.revision += 1;
+        }
+    }
+}
@@ -0,0 +1,378 @@
+//! 工作流语义差异
+//!
+//! 按 UUID 对齐两个工作流，报告 Block 的新增、删除、移动和字段修改，以及连线、分组、图层的变化。
+//! 字段在保存后的 JSON 上比较（与 .L 文件内容一致），运行时状态和派生的尺寸不参与比较：
+//! `state` 和由连线提供的 `input_values` 每次执行都会改写，只比较未连线端口的输入值。
+//! `properties` / `input_values` 按键比较，字段名记为 "properties.hp"。
+
+use super::{Block, Workflow};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use uuid::Uuid;
+
+/// 按键比较的字段
+const KEYED_FIELDS: [&str; 2] = ["properties", "input_values"];
+/// Block 移动记录的字段
+pub const POSITION_FIELD: &str = "position";
+
+/// 差异对象类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffTarget {
+    /// 工作流自身的字段（名称、只读、密码）
+    Workflow,
+    Block,
+    Connection,
+    Group,
+    Layer,
+    Subgraph,
+}
+
+/// 变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// 字段变化（不存在为 null）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// 单个对象的差异
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffEntry {
+    pub target: DiffTarget,
+    /// 对象 UUID（工作流字段为空）
+    pub id: Option<Uuid>,
+    pub change: ChangeKind,
+    /// 可读名称，如 "英雄 (lite.hero)"、"lite.hero.hp → lite.enemy.damage"
+    pub label: String,
+    /// 修改的字段（仅 Modified）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldChange>,
+}
+
+impl DiffEntry {
+    /// Block 是否移动过
+    pub fn moved(&self) -> bool {
+        self.fields.iter().any(|f| f.field == POSITION_FIELD)
+    }
+
+    /// 除移动外是否还有其他修改
+    pub fn edited(&self) -> bool {
+        self.fields.iter().any(|f| f.field != POSITION_FIELD)
+    }
+}
+
+/// 两个工作流之间的差异（旧 → 新）
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WorkflowDiff {
+    pub entries: Vec<DiffEntry>,
+}
+
+impl WorkflowDiff {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 某类对象的差异
+    pub fn of(&self, target: DiffTarget) -> impl Iterator<Item = &DiffEntry> {
+        self.entries.iter().filter(move |e| e.target == target)
+    }
+
+    /// 按变化类型计数 (新增, 删除, 修改)
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let count = |kind| self.entries.iter().filter(|e| e.change == kind).count();
+        (count(ChangeKind::Added), count(ChangeKind::Removed), count(ChangeKind::Modified))
+    }
+}
+
+impl Workflow {
+    /// 与另一个工作流比较（self 为旧版本）
+    pub fn diff(&self, other: &Workflow) -> WorkflowDiff {
+        let mut entries = Vec::new();
+
+        let fields = diff_fields(&workflow_fields(self), &workflow_fields(other), &[]);
+        if !fields.is_empty() {
+            entries.push(DiffEntry {
+                target: DiffTarget::Workflow,
+                id: None,
+                change: ChangeKind::Modified,
+                label: other.name.clone(),
+                fields,
+            });
+        }
+
+        let block_label = |id: &Uuid| {
+            other.blocks.get(id).or_else(|| self.blocks.get(id)).map(block_label).unwrap_or_else(|| short_id(id))
+        };
+
+        // 任一版本中有连线的输入端口，值由执行产生
+        let connected: HashSet<(Uuid, &str)> = self.connections.values()
+            .chain(other.connections.values())
+            .map(|c| (c.to_block, c.to_port.as_str()))
+            .collect();
+        diff_collection(
+            &mut entries,
+            DiffTarget::Block,
+            &authored_blocks(self, &connected),
+            &authored_blocks(other, &connected),
+            &["id", "size", "state"],
+            |b| block_label(&b.id),
+        );
+        diff_collection(&mut entries, DiffTarget::Connection, &self.connections, &other.connections, &["id"], |c| {
+            format!("{}.{} → {}.{}", block_label(&c.from_block), c.from_port, block_label(&c.to_block), c.to_port)
+        });
+        // 分组的位置和尺寸由成员自动计算
+        diff_collection(&mut entries, DiffTarget::Group, &self.groups, &other.groups, &["id", "position", "size"], |g| g.name.clone());
+
+        let layers = |workflow: &Workflow| workflow.layers.iter().map(|l| (l.id, l.clone())).collect::<HashMap<_, _>>();
+        diff_collection(&mut entries, DiffTarget::Layer, &layers(self), &layers(other), &["id"], |l| l.name.clone());
+
+        // 子图内部的变化按对象列出，名称前加子图名
+        diff_collection(&mut entries, DiffTarget::Subgraph, &self.subgraphs, &other.subgraphs, &["id", "blocks", "connections"], |s| s.name.clone());
+        let mut subgraphs: Vec<_> = other.subgraphs.values().collect();
+        subgraphs.sort_by_key(|s| s.id);
+        for new in subgraphs {
+            let Some(old) = self.subgraphs.get(&new.id) else { continue };
+            let inner = old.to_workflow().diff(&new.to_workflow());
+            entries.extend(inner.entries.into_iter().filter(|e| e.target != DiffTarget::Workflow).map(|mut e| {
+                e.label = format!("{} › {}", new.name, e.label);
+                e
+            }));
+        }
+
+        WorkflowDiff { entries }
+    }
+}
+
+/// Block 的可读名称：自定义名称 (script_id)
+fn block_label(block: &Block) -> String {
+    match &block.custom_name {
+        Some(name) if !name.is_empty() => format!("{} ({})", name, block.script_id),
+        _ => block.script_id.clone(),
+    }
+}
+
+/// 去掉由连线提供的输入值，只保留编辑器中填写的部分
+fn authored_blocks(workflow: &Workflow, connected: &HashSet<(Uuid, &str)>) -> HashMap<Uuid, Block> {
+    workflow.blocks.iter()
+        .map(|(id, block)| {
+            let mut block = block.clone();
+            block.input_values.retain(|port, _| !connected.contains(&(*id, port.as_str())));
+            (*id, block)
+        })
+        .collect()
+}
+
+fn short_id(id: &Uuid) -> String {
+    id.simple().to_string()[..8].to_string()
+}
+
+/// 参与比较的工作流字段（密码只比较是否设置，不暴露哈希）
+fn workflow_fields(workflow: &Workflow) -> Map<String, Value> {
+    let mut map = Map::new();
+    map.insert("name".into(), Value::from(workflow.name.clone()));
+    map.insert("readonly".into(), Value::from(workflow.readonly));
+    map.insert("seed".into(), Value::from(workflow.seed));
+    map.insert("password".into(), Value::from(workflow.has_password()));
+    map
+}
+
+/// 比较按 UUID 索引的对象集合
+fn diff_collection<T: Serialize>(
+    entries: &mut Vec<DiffEntry>,
+    target: DiffTarget,
+    old: &HashMap<Uuid, T>,
+    new: &HashMap<Uuid, T>,
+    ignored: &[&str],
+    label: impl Fn(&T) -> String,
+) {
+    let ids: BTreeSet<&Uuid> = old.keys().chain(new.keys()).collect();
+    for id in ids {
+        let (change, fields, item) = match (old.get(id), new.get(id)) {
+            (None, Some(item)) => (ChangeKind::Added, Vec::new(), item),
+            (Some(item), None) => (ChangeKind::Removed, Vec::new(), item),
+            (Some(before), Some(after)) => {
+                let fields = diff_fields(&to_map(before), &to_map(after), ignored);
+                if fields.is_empty() {
+                    continue;
+                }
+                (ChangeKind::Modified, fields, after)
+            }
+            (None, None) => continue,
+        };
+        entries.push(DiffEntry { target, id: Some(*id), change, label: label(item), fields });
+    }
+}
+
+fn to_map<T: Serialize>(item: &T) -> Map<String, Value> {
+    match serde_json::to_value(item) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    }
+}
+
+/// 逐字段比较
+fn diff_fields(old: &Map<String, Value>, new: &Map<String, Value>, ignored: &[&str]) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let fields: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for field in fields.into_iter().filter(|f| !ignored.contains(&f.as_str())) {
+        let (before, after) = (old.get(field), new.get(field));
+        if before == after {
+            continue;
+        }
+
+        if let (true, Some(Value::Object(b)), Some(Value::Object(a))) = (KEYED_FIELDS.contains(&field.as_str()), before, after) {
+            let keys: BTreeSet<&String> = b.keys().chain(a.keys()).collect();
+            for key in keys.into_iter().filter(|k| b.get(*k) != a.get(*k)) {
+                changes.push(FieldChange {
+                    field: format!("{}.{}", field, key),
+                    old: b.get(key).cloned().unwrap_or(Value::Null),
+                    new: a.get(key).cloned().unwrap_or(Value::Null),
+                });
+            }
+        } else {
+            changes.push(FieldChange {
+                field: field.clone(),
+                old: before.cloned().unwrap_or(Value::Null),
+                new: after.cloned().unwrap_or(Value::Null),
+            });
+        }
+    }
+    changes
+}
+
+impl fmt::Display for WorkflowDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            let sign = match entry.change {
+                ChangeKind::Added => '+',
+                ChangeKind::Removed => '-',
+                ChangeKind::Modified => '~',
+            };
+            let target = match entry.target {
+                DiffTarget::Workflow => "工作流",
+                DiffTarget::Block => "Block",
+                DiffTarget::Connection => "连线",
+                DiffTarget::Group => "分组",
+                DiffTarget::Layer => "图层",
+                DiffTarget::Subgraph => "子图",
+            };
+            match entry.id {
+                Some(id) => writeln!(f, "{} {} {} [{}]", sign, target, entry.label, short_id(&id))?,
+                None => writeln!(f, "{} {} {}", sign, target, entry.label)?,
+            }
+
+            for change in &entry.fields {
+                if change.field == POSITION_FIELD {
+                    writeln!(f, "    移动: {} → {}", format_position(&change.old), format_position(&change.new))?;
+                } else {
+                    writeln!(f, "    {}: {} → {}", change.field, change.old, change.new)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_position(value: &Value) -> String {
+    let coord = |axis: &str| value.get(axis).and_then(Value::as_f64).unwrap_or(0.0);
+    format!("({}, {})", coord("x"), coord("y"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::Value as ScriptValue;
+    use crate::workflow::{BlockGroup, BlueprintStorage, Connection, Layer, Vec2};
+    use std::path::Path;
+
+    fn load() -> Workflow {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("workflows").join("battle_demo.L");
+        BlueprintStorage::load(&path, None).unwrap()
+    }
+
+    fn find(diff: &WorkflowDiff, target: DiffTarget, id: Uuid) -> Option<&DiffEntry> {
+        diff.of(target).find(|e| e.id == Some(id))
+    }
+
+    #[test]
+    fn test_diff_identical_is_empty() {
+        let mut workflow = load();
+        let copy = workflow.clone();
+        // 选中状态、输出值、运行时状态、由连线提供的输入值、派生尺寸都不算修改
+        let connected: Vec<(Uuid, String)> = workflow.connections.values().map(|c| (c.to_block, c.to_port.clone())).collect();
+        assert!(!connected.is_empty());
+        for (id, port) in &connected {
+            workflow.blocks.get_mut(id).unwrap().input_values.insert(port.clone(), ScriptValue::Number(42.0));
+        }
+        for block in workflow.blocks.values_mut() {
+            block.selected = true;
+            block.size = Vec2::new(1.0, 1.0);
+            block.output_values.insert("hp".into(), ScriptValue::Number(1.0));
+            block.state.insert("turn".into(), ScriptValue::Number(3.0));
+        }
+        assert!(copy.diff(&workflow).is_empty());
+
+        // 未连线端口的输入值是编辑器中填写的，算作修改
+        let id = *workflow.blocks.keys().min().unwrap();
+        workflow.blocks.get_mut(&id).unwrap().input_values.insert("unconnected".into(), ScriptValue::Number(-1.0));
+        let diff = copy.diff(&workflow);
+        let fields: Vec<&str> = find(&diff, DiffTarget::Block, id).unwrap().fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, vec!["input_values.unconnected"]);
+    }
+
+    #[test]
+    fn test_diff_reports_changes() {
+        let old = load();
+        let mut new = old.clone();
+        let mut ids: Vec<Uuid> = old.blocks.keys().copied().collect();
+        ids.sort();
+        let (moved, removed) = (ids[0], ids[1]);
+
+        let block = new.blocks.get_mut(&moved).unwrap();
+        block.position.x += 40.0;
+        block.custom_name = Some("主角".into());
+        block.properties.insert("hp".into(), ScriptValue::Number(999.0));
+        let script_id = block.script_id.clone();
+
+        new.remove_block(removed);
+        let mut copy = old.blocks[&removed].clone();
+        copy.id = Uuid::new_v4();
+        let added = new.add_block(copy);
+        let connection = Connection::new(moved, "out".into(), added, "in".into());
+        let connection_id = connection.id;
+        new.connections.insert(connection_id, connection);
+        let group = BlockGroup::new("战斗".into(), [moved].into());
+        new.groups.insert(group.id, group);
+        new.layers.push(Layer::new("新图层", Vec2::default(), Vec2::new(100.0, 100.0)));
+
+        let diff = old.diff(&new);
+        let entry = find(&diff, DiffTarget::Block, moved).unwrap();
+        assert_eq!(entry.change, ChangeKind::Modified);
+        assert_eq!(entry.label, format!("主角 ({})", script_id));
+        assert!(entry.moved() && entry.edited());
+        assert!(entry.fields.iter().any(|f| f.field == "properties.hp" && f.new == serde_json::json!(999.0)));
+
+        assert_eq!(find(&diff, DiffTarget::Block, removed).unwrap().change, ChangeKind::Removed);
+        assert_eq!(find(&diff, DiffTarget::Block, added).unwrap().change, ChangeKind::Added);
+        let connection = find(&diff, DiffTarget::Connection, connection_id).unwrap();
+        assert!(connection.label.starts_with("主角 ("), "{}", connection.label);
+        assert_eq!(diff.of(DiffTarget::Group).count(), 1);
+        assert_eq!(diff.of(DiffTarget::Layer).count(), 1);
+
+        let text = diff.to_string();
+        assert!(text.contains("移动: ("), "{}", text);
+        assert!(text.contains("properties.hp"), "{}", text);
+    }
+}
@@ -0,0 +1,87 @@
+//! 事件 - 只传递一次的端口值
+//!
+//! `event` 类型的输出端口返回非 nil（且非 false）的值即触发事件，返回值就是事件负载（无负载时为 true）。
+//! 触发后输出立即清空，负载投递给订阅该端口的下游输入端口；
+//! 订阅者在下一次执行时收到一次，随后清除。事件只触发订阅它的 Block 执行。
+
+use super::Workflow;
+use crate::script::{BlockDefinition, DataType, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// 值是否表示触发了事件
+pub fn is_fired(value: &Value) -> bool {
+    !matches!(value, Value::Nil | Value::Boolean(false))
+}
+
+impl Workflow {
+    /// 向 Block 的输入端口投递事件（界面交互、子图输入等外部来源）
+    pub fn fire_event(&mut self, block_id: Uuid, port: &str, payload: Value) {
+        self.pending_events.insert((block_id, port.to_string()), payload);
+    }
+
+    /// 将输出端口的事件投递给所有订阅者（经反馈连线的下一 tick 才投递）
+    pub fn deliver_event(&mut self, block_id: Uuid, port: &str, payload: &Value) {
+        let targets: Vec<(Uuid, String, bool)> = self.connections.values()
+            .filter(|c| c.from_block == block_id && c.from_port == port)
+            .map(|c| (c.to_block, c.to_port.clone(), c.delayed))
+            .collect();
+        for (to_block, to_port, delayed) in targets {
+            let pending = if delayed { &mut self.feedback_events } else { &mut self.pending_events };
+            pending.insert((to_block, to_port), payload.clone());
+        }
+    }
+
+    /// 发出 Block 刚产生的事件并清空对应输出
+    pub fn emit_events(&mut self, block_id: Uuid, definition: &BlockDefinition) {
+        let Some(block) = self.blocks.get_mut(&block_id) else {
+            return;
+        };
+
+        let mut fired = Vec::new();
+        for port in definition.outputs.iter().filter(|p| p.data_type == DataType::Event) {
+            if let Some(value) = block.output_values.get_mut(&port.id) {
+                if is_fired(value) {
+                    fired.push((port.id.clone(), std::mem::replace(value, Value::Nil)));
+                }
+            }
+        }
+
+        for (port, payload) in fired {
+            self.deliver_event(block_id, &port, &payload);
+        }
+    }
+
+    /// 取出 Block 收到的事件写入 inputs，没有收到事件的 event 端口为 nil
+    /// 返回收到的事件（端口 → 负载）
+    pub fn take_events(&mut self, block_id: Uuid, definition: &BlockDefinition, inputs: &mut HashMap<String, Value>) -> HashMap<String, Value> {
+        for port in definition.inputs.iter().filter(|p| p.data_type == DataType::Event) {
+            inputs.remove(&port.id);
+        }
+
+        let ports: Vec<String> = self.pending_events.keys()
+            .filter(|(id, _)| *id == block_id)
+            .map(|(_, port)| port.clone())
+            .collect();
+        let mut received = HashMap::new();
+        for port in ports {
+            if let Some(payload) = self.pending_events.remove(&(block_id, port.clone())) {
+                inputs.insert(port.clone(), payload.clone());
+                received.insert(port, payload);
+            }
+        }
+        received
+    }
+
+    /// 放回 take_events 取出的事件（Block 等待必需输入时保留，输入齐全后再处理）
+    pub fn requeue_events(&mut self, block_id: Uuid, events: HashMap<String, Value>) {
+        for (port, payload) in events {
+            self.pending_events.entry((block_id, port)).or_insert(payload);
+        }
+    }
+
+    /// Block 是否有尚未处理的事件
+    pub fn has_pending_events(&self, block_id: Uuid) -> bool {
+        self.pending_events.keys().any(|(id, _)| *id == block_id)
+    }
+}
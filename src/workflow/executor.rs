@@ -1,83 +1,696 @@
 //! 工作流执行引擎 - 实时执行Lua脚本
 
-use crate::script::{ScriptRegistry, Value};
+use crate::script::{BlockDefinition, DataType, SandboxPolicy, ScriptLoader, ScriptRegistry, Value};
 use crate::usb::register_usb_module;
-use crate::workflow::Workflow;
+use super::subgraph::{port_name, GRAPH_INPUT, GRAPH_OUTPUT, VALUE_PORT};
+use crate::workflow::{
+    instance_of, is_port_block, Block, BlockRng, History, Interaction, Pause, SimulationClock, StepMode, Subgraph, TickFrame,
+    TickStatus, TraceRecorder, Workflow, MAX_SUBGRAPH_DEPTH,
+};
 use anyhow::{anyhow, Result};
-use mlua::{Lua, Table, Value as LuaValue};
-use std::collections::HashMap;
+use mlua::{ChunkMode, HookTriggers, Lua, Table, Value as LuaValue, VmState};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+use thiserror::Error;
 use uuid::Uuid;
 
+/// 指令计数钩子的触发间隔
+const HOOK_INTERVAL: u32 = 1000;
+
 /// 将mlua错误转换为anyhow错误
 fn lua_err(e: mlua::Error) -> anyhow::Error {
     anyhow!("Lua执行错误: {}", e)
 }
 
+/// 是否为内存不足错误（可能被回调错误包裹）
+fn is_memory_error(e: &mlua::Error) -> bool {
+    match e {
+        mlua::Error::MemoryError(_) => true,
+        mlua::Error::CallbackError { cause, .. } => is_memory_error(cause),
+        _ => false,
+    }
+}
+
+/// Block执行被中止的原因
+#[derive(Debug, Error)]
+pub enum BlockError {
+    #[error("Block {block_id} ({script_id}) 超出指令预算 {budget} 条，已中止")]
+    InstructionBudgetExceeded { block_id: Uuid, script_id: String, budget: u64 },
+    #[error("Block {block_id} ({script_id}) 超出内存限制 {limit} 字节，已中止")]
+    MemoryLimitExceeded { block_id: Uuid, script_id: String, limit: usize },
+}
+
+/// 执行资源限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    /// 单个Block单次执行可用的Lua指令数
+    pub instruction_budget: u64,
+    /// Lua虚拟机的内存上限（字节，0表示不限制）
+    pub memory_limit: usize,
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        Self {
+            instruction_budget: 10_000_000,
+            memory_limit: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// Lua虚拟机的资源限制：指令计数钩子和内存上限
+pub struct LuaLimiter {
+    limits: ExecutionLimits,
+    /// 当前Block剩余的指令预算（由指令钩子递减）
+    remaining: Rc<Cell<u64>>,
+}
+
+impl LuaLimiter {
+    /// 在虚拟机上安装指令计数钩子并设置内存上限
+    pub fn install(lua: &Lua, limits: ExecutionLimits) -> Result<Self> {
+        let remaining = Rc::new(Cell::new(limits.instruction_budget));
+
+        // 指令预算：耗尽时抛出错误中止当前脚本，避免死循环卡住界面
+        let counter = remaining.clone();
+        lua.set_hook(HookTriggers::new().every_nth_instruction(HOOK_INTERVAL), move |_, _| {
+            let left = counter.get();
+            if left < HOOK_INTERVAL as u64 {
+                counter.set(0);
+                return Err(mlua::Error::runtime("指令预算耗尽"));
+            }
+            counter.set(left - HOOK_INTERVAL as u64);
+            Ok(VmState::Continue)
+        });
+
+        let mut limiter = Self { limits, remaining };
+        limiter.set_limits(lua, limits)?;
+        Ok(limiter)
+    }
+
+    /// 更换资源限制
+    pub fn set_limits(&mut self, lua: &Lua, limits: ExecutionLimits) -> Result<()> {
+        lua.set_memory_limit(limits.memory_limit)
+            .map_err(|e| anyhow!("设置内存限制失败: {}", e))?;
+        self.limits = limits;
+        self.reset();
+        Ok(())
+    }
+
+    /// 重置指令预算（每个Block执行前调用）
+    pub fn reset(&self) {
+        self.remaining.set(self.limits.instruction_budget);
+    }
+
+    /// Lua错误是否因超出资源限制引起
+    pub fn exceeded(&self, e: &mlua::Error, block_id: Uuid, script_id: &str) -> Option<BlockError> {
+        if self.remaining.get() == 0 {
+            Some(BlockError::InstructionBudgetExceeded {
+                block_id,
+                script_id: script_id.to_string(),
+                budget: self.limits.instruction_budget,
+            })
+        } else if is_memory_error(e) {
+            Some(BlockError::MemoryLimitExceeded {
+                block_id,
+                script_id: script_id.to_string(),
+                limit: self.limits.memory_limit,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// 编译后的脚本（按 script_id 缓存）
+struct CachedScript {
+    /// 编译时的脚本路径，定义换了文件时缓存自动失效
+    script_path: String,
+    /// 字节码，每次执行在Block自己的新环境中加载
+    bytecode: Vec<u8>,
+}
+
+/// Block实例跨 tick 保留的数据（脚本环境每次执行重建，全局变量不会带到下一次执行）
+struct BlockInstance {
+    script_id: String,
+    /// 该Block的随机数流（env 中的 math.random）
+    rng: BlockRng,
+}
+
+/// 进行中的 tick（断点暂停时保留，继续时从 next 开始）
+struct TickProgress {
+    order: Vec<Uuid>,
+    /// 下一个检查的Block在 order 中的位置
+    next: usize,
+    /// 本 tick 收到反馈值的Block
+    fed: HashSet<Uuid>,
+    /// 每个Block的输入来源 (上游Block, 输出端口)
+    upstream: HashMap<Uuid, Vec<(Uuid, String)>>,
+    /// 本 tick 中值发生变化的输出端口
+    changed: HashSet<(Uuid, String)>,
+    /// 是否执行 always_run Block（在当前 tick 内重新执行时为假）
+    always_run: bool,
+}
+
+/// 子图实例的嵌套作用域
+struct SubgraphScope {
+    /// 创建作用域时的子图定义，定义变化时重建
+    fingerprint: String,
+    workflow: Workflow,
+}
+
 /// 工作流执行引擎
 pub struct WorkflowExecutor {
     lua: Lua,
+    /// 字节码缓存，避免每个tick重新读取和编译脚本
+    script_cache: HashMap<String, CachedScript>,
+    /// 每个Block的随机数流
+    instances: HashMap<Uuid, BlockInstance>,
+    limiter: LuaLimiter,
+    /// 子图实例的作用域 (实例 Block UUID -> 作用域)
+    scopes: HashMap<Uuid, SubgraphScope>,
+    /// 当前子图嵌套层数
+    depth: usize,
+    /// 模拟时钟（每次顶层执行前进一个 tick，子图与外层共用同一个 tick）
+    clock: SimulationClock,
+    /// 当前随机数种子（与工作流的 seed 不同时重置所有随机数流）
+    seed: u64,
+    /// 执行录制（只记录顶层Block）
+    trace: Option<TraceRecorder>,
+    /// 停在断点处的 tick
+    paused: Option<TickProgress>,
+    /// 每个 tick 结束时的运行时快照（默认不保存）
+    history: History,
 }
 
 impl WorkflowExecutor {
+    /// 创建执行引擎（信任本地脚本，开放全部标准库）
     pub fn new() -> Result<Self> {
+        Self::with_policy(&SandboxPolicy::trusted())
+    }
+
+    /// 创建受沙箱策略限制的执行引擎
+    pub fn with_policy(policy: &SandboxPolicy) -> Result<Self> {
         let lua = Lua::new();
         // 注册 USB 模块
         register_usb_module(&lua).map_err(|e| anyhow!("注册USB模块失败: {}", e))?;
+        policy.apply(&lua).map_err(|e| anyhow!("应用沙箱策略失败: {}", e))?;
 
         // 验证 USB 模块已注册
         let has_usb: bool = lua.load("return usb ~= nil").eval().unwrap_or(false);
-        log::info!("USB 模块注册状态: {}", if has_usb { "成功" } else { "失败" });
+        log::info!("USB 模块注册状态: {}", if has_usb { "成功" } else { "未启用" });
+
+        let limiter = LuaLimiter::install(&lua, ExecutionLimits::default())?;
+
+        Ok(Self {
+            lua,
+            script_cache: HashMap::new(),
+            instances: HashMap::new(),
+            limiter,
+            scopes: HashMap::new(),
+            depth: 0,
+            clock: SimulationClock::default(),
+            seed: 0,
+            trace: None,
+            paused: None,
+            history: History::new(0),
+        })
+    }
+
+    /// 设置执行资源限制
+    pub fn set_limits(&mut self, limits: ExecutionLimits) -> Result<()> {
+        self.limiter.set_limits(&self.lua, limits)
+    }
+
+    /// 当前模拟时钟
+    pub fn clock(&self) -> SimulationClock {
+        self.clock
+    }
+
+    /// 设置每个 tick 的步长（秒）
+    pub fn set_dt(&mut self, dt: f64) {
+        self.clock.dt = dt.max(0.0);
+    }
+
+    /// 设置时间缩放
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.clock.time_scale = time_scale.max(0.0);
+    }
+
+    /// 从头开始运行：放弃暂停中的 tick 和执行历史，时钟回到第 0 个 tick，所有随机数流回到初始状态（加载新工作流时调用）
+    pub fn restart(&mut self) {
+        self.paused = None;
+        self.history.clear();
+        self.clock.reset();
+        for instance in self.instances.values() {
+            instance.rng.restart();
+        }
+    }
+
+    /// 执行历史
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// 设置保留的 tick 数，0 表示不保存执行历史
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history.set_capacity(capacity);
+    }
+
+    /// 回到某个 tick 结束时的状态，之后的执行从这里继续
+    pub fn rewind(&mut self, workflow: &mut Workflow, tick: u64) -> Result<()> {
+        let frame = self.history.get(tick).ok_or_else(|| anyhow!("tick {} 不在执行历史中", tick))?;
+        frame.restore(workflow);
+        // 子图作用域一同回退，快照之后才创建的作用域丢弃后重建
+        self.scopes.retain(|id, _| frame.scopes.contains_key(id));
+        for (id, scope) in &mut self.scopes {
+            frame.scopes[id].restore(&mut scope.workflow);
+        }
+        for (id, instance) in &self.instances {
+            if let Some(&position) = frame.rng.get(id) {
+                instance.rng.seek(position);
+            }
+        }
+        self.clock.tick = frame.clock.tick;
+        self.clock.elapsed = frame.clock.elapsed;
+        self.paused = None;
+        log::info!("回退到 tick {}", tick);
+        Ok(())
+    }
 
-        Ok(Self { lua })
+    /// 保存顶层 tick 结束时的快照
+    fn record_history(&mut self, workflow: &Workflow) {
+        if self.depth > 0 || self.history.capacity() == 0 {
+            return;
+        }
+        let rng = self.instances.iter().map(|(id, instance)| (*id, instance.rng.position())).collect();
+        let mut frame = TickFrame::capture(workflow, self.clock, rng);
+        frame.scopes = self.scopes.iter()
+            .map(|(id, scope)| (*id, TickFrame::capture(&scope.workflow, self.clock, HashMap::new())))
+            .collect();
+        self.history.record(frame);
+    }
+
+    /// 工作流的种子变化时重置所有随机数流（子图沿用外层的种子）
+    fn sync_seed(&mut self, seed: u64) {
+        if self.depth > 0 || self.seed == seed {
+            return;
+        }
+        self.seed = seed;
+        for instance in self.instances.values() {
+            instance.rng.reseed(seed);
+        }
+    }
+
+    /// 顶层执行前进一个 tick，子图内部的执行沿用外层的 tick
+    fn advance_clock(&mut self) {
+        if self.depth == 0 {
+            self.clock.advance();
+            if let Some(trace) = &mut self.trace {
+                trace.begin_tick(self.clock);
+            }
+        }
+    }
+
+    /// 开始录制（替换正在进行的录制）
+    pub fn start_trace(&mut self, recorder: TraceRecorder) {
+        self.trace = Some(recorder);
+    }
+
+    /// 结束录制，返回录制器（调用 finish 写出文件）
+    pub fn take_trace(&mut self) -> Option<TraceRecorder> {
+        self.trace.take()
+    }
+
+    /// 记录控件交互（未在录制时忽略）
+    pub fn record_interaction(&mut self, interaction: Interaction) {
+        if let Some(trace) = &mut self.trace {
+            trace.record_interaction(interaction);
+        }
+    }
+
+    /// 将Lua错误转换为带Block信息的错误（超出资源限制时为 BlockError）
+    fn block_err(&self, e: mlua::Error, block_id: Uuid, script_id: &str) -> anyhow::Error {
+        match self.limiter.exceeded(&e, block_id, script_id) {
+            Some(block_error) => block_error.into(),
+            None => lua_err(e),
+        }
+    }
+
+    /// 使某个脚本的缓存失效（热重载后调用）
+    /// Block的环境表会保留，下次执行时用新脚本重新加载
+    pub fn invalidate_script(&mut self, script_id: &str) {
+        if self.script_cache.remove(script_id).is_some() {
+            log::debug!("脚本缓存已失效: {}", script_id);
+        }
+    }
+
+    /// 清空所有脚本缓存
+    #[allow(dead_code)]
+    pub fn clear_script_cache(&mut self) {
+        self.script_cache.clear();
+    }
+
+    /// 获取编译后的脚本，未缓存时读取并编译
+    fn compiled_script(&mut self, script_id: &str, definition: &BlockDefinition) -> Result<&CachedScript> {
+        let stale = self
+            .script_cache
+            .get(script_id)
+            .is_none_or(|cached| cached.script_path != definition.script_path);
+
+        if stale {
+            let script_content = ScriptLoader::load(&definition.script_path)?;
+            let bytecode = self
+                .lua
+                .load(&script_content)
+                .set_name(format!("@{}", definition.script_path))
+                .into_function()
+                .map_err(lua_err)?
+                .dump(false);
+
+            self.script_cache.insert(
+                script_id.to_string(),
+                CachedScript {
+                    script_path: definition.script_path.clone(),
+                    bytecode,
+                },
+            );
+        }
+
+        Ok(&self.script_cache[script_id])
+    }
+
+    /// 创建Block的环境表：可读取标准库和 usb 模块，写入只落在自身，math.random 使用该Block的随机数流
+    fn create_block_env(&self, rng: &BlockRng) -> Result<Table> {
+        let env = self.lua.create_table().map_err(lua_err)?;
+        let meta = self.lua.create_table().map_err(lua_err)?;
+        meta.set("__index", self.lua.globals()).map_err(lua_err)?;
+        env.set_metatable(Some(meta));
+        // _G 指向自身，避免通过 _G 写入共享全局表
+        env.set("_G", env.clone()).map_err(lua_err)?;
+        rng.install(&self.lua, &env).map_err(lua_err)?;
+        Ok(env)
+    }
+
+    /// 在该Block的新环境中加载脚本，返回脚本表（含 execute 函数）
+    fn block_script_table(&mut self, block_id: Uuid, script_id: &str, definition: &BlockDefinition) -> Result<Table> {
+        let bytecode = self.compiled_script(script_id, definition)?.bytecode.clone();
+
+        // 随机数流跨 tick 保留，脚本切换时重建
+        let rng = match self.instances.get(&block_id) {
+            Some(instance) if instance.script_id == script_id => instance.rng.clone(),
+            _ => {
+                let rng = BlockRng::new(self.seed, block_id);
+                self.instances.insert(block_id, BlockInstance { script_id: script_id.to_string(), rng: rng.clone() });
+                rng
+            }
+        };
+
+        let env = self.create_block_env(&rng)?;
+        self.lua
+            .load(&bytecode[..])
+            .set_mode(ChunkMode::Binary)
+            .set_name(format!("@{}", definition.script_path))
+            .set_environment(env)
+            .eval()
+            .map_err(|e| self.block_err(e, block_id, script_id))
+    }
+
+    /// 清理已删除Block的环境和子图作用域（子图内部的Block随所属实例保留）
+    fn prune_instances(&mut self, workflow: &Workflow) {
+        if self.depth > 0 {
+            return;
+        }
+        let mut live: HashSet<Uuid> = workflow.blocks.keys().copied().collect();
+        loop {
+            let count = live.len();
+            for (id, scope) in &self.scopes {
+                if live.contains(id) {
+                    live.extend(scope.workflow.blocks.keys().copied());
+                }
+            }
+            if live.len() == count {
+                break;
+            }
+        }
+        self.scopes.retain(|id, _| live.contains(id));
+        self.instances.retain(|id, _| live.contains(id));
     }
 
     /// 执行整个工作流(按拓扑顺序)
     pub fn execute_all(
-        &self,
+        &mut self,
         workflow: &mut Workflow,
         registry: &ScriptRegistry,
     ) -> Result<()> {
+        self.prune_instances(workflow);
+        self.sync_seed(workflow.seed);
+        self.advance_clock();
+        workflow.latch_feedback();
         let order = workflow.execution_order.clone();
-        
+
         for block_id in order {
             self.execute_block(workflow, registry, block_id)?;
         }
 
         workflow.dirty_blocks.clear();
+        self.record_history(workflow);
         Ok(())
     }
 
-    /// 只执行脏Block
+    /// 增量执行：只执行脏Block、always_run Block、收到事件或反馈值变化的Block，以及输入来源输出发生变化的下游Block
     pub fn execute_dirty(
-        &self,
+        &mut self,
         workflow: &mut Workflow,
         registry: &ScriptRegistry,
     ) -> Result<()> {
-        // 按拓扑顺序执行所有Block
-        let order = workflow.execution_order.clone();
+        let mut progress = self.begin_tick(workflow);
+        self.continue_tick(workflow, registry, &mut progress, StepMode::Tick, false)?;
+        Ok(())
+    }
+
+    /// 在当前 tick 内重新执行编辑、事件或故障清除影响到的Block：不前进时钟，不重复执行 always_run Block
+    pub fn rerun_dirty(
+        &mut self,
+        workflow: &mut Workflow,
+        registry: &ScriptRegistry,
+    ) -> Result<()> {
+        if self.clock.tick == 0 {
+            return self.execute_dirty(workflow, registry);
+        }
+        self.prune_instances(workflow);
+        self.sync_seed(workflow.seed);
+        let mut progress = Self::tick_progress(workflow, HashSet::new());
+        progress.always_run = false;
+        self.continue_tick(workflow, registry, &mut progress, StepMode::Tick, false)?;
+        Ok(())
+    }
 
-        // Debug: 打印执行顺序
-        if !order.is_empty() {
-            let names: Vec<String> = order.iter()
-                .filter_map(|id| workflow.blocks.get(id))
-                .map(|b| b.script_id.clone())
-                .collect();
-            log::debug!("执行顺序: {:?}", names);
+    /// 带断点的增量执行：有暂停中的 tick 时从暂停的Block继续，否则开始新的 tick
+    pub fn run_tick(
+        &mut self,
+        workflow: &mut Workflow,
+        registry: &ScriptRegistry,
+        mode: StepMode,
+    ) -> Result<TickStatus> {
+        let (mut progress, resumed) = match self.paused.take() {
+            Some(progress) => (progress, true),
+            None => (self.begin_tick(workflow), false),
+        };
+        let status = self.continue_tick(workflow, registry, &mut progress, mode, resumed)?;
+        if matches!(status, TickStatus::Paused(_)) {
+            self.paused = Some(progress);
         }
+        Ok(status)
+    }
+
+    /// 是否停在某个 tick 的中途
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// 开始新的 tick：前进时钟、锁存反馈值，记录执行顺序和输入来源
+    fn begin_tick(&mut self, workflow: &mut Workflow) -> TickProgress {
+        self.prune_instances(workflow);
+        self.sync_seed(workflow.seed);
+        self.advance_clock();
+
+        // 反馈连线的值在 tick 开始时更新，之后不再随来源变化
+        let fed = workflow.latch_feedback();
+        Self::tick_progress(workflow, fed)
+    }
+
+    /// 记录执行顺序和输入来源，fed 为本 tick 收到反馈值的Block
+    fn tick_progress(workflow: &Workflow, fed: HashSet<Uuid>) -> TickProgress {
+        // 每个Block的输入来源 (上游Block, 输出端口)
+        let mut upstream: HashMap<Uuid, Vec<(Uuid, String)>> = HashMap::new();
+        for conn in workflow.connections.values().filter(|c| !c.delayed) {
+            upstream.entry(conn.to_block).or_default().push((conn.from_block, conn.from_port.clone()));
+        }
+
+        TickProgress {
+            order: workflow.execution_order.clone(),
+            next: 0,
+            fed,
+            upstream,
+            changed: HashSet::new(),
+            always_run: true,
+        }
+    }
+
+    /// 按执行顺序推进 tick，resumed 为真时不在第一个要执行的Block处再次暂停
+    fn continue_tick(
+        &mut self,
+        workflow: &mut Workflow,
+        registry: &ScriptRegistry,
+        progress: &mut TickProgress,
+        mode: StepMode,
+        mut resumed: bool,
+    ) -> Result<TickStatus> {
+        while let Some(&block_id) = progress.order.get(progress.next) {
+            let Some(block) = workflow.blocks.get(&block_id) else {
+                progress.next += 1;
+                continue;
+            };
+            let always_run = progress.always_run && registry.get(&block.script_id).is_some_and(|d| d.meta.always_run);
+            let inputs_changed = progress.upstream
+                .get(&block_id)
+                .is_some_and(|sources| sources.iter().any(|source| progress.changed.contains(source)));
+            let triggered = progress.fed.contains(&block_id) || workflow.has_pending_events(block_id);
+            if !always_run && !inputs_changed && !triggered && !workflow.dirty_blocks.contains(&block_id) {
+                progress.next += 1;
+                continue;
+            }
+
+            // 在Block执行前检查断点（子图内部不暂停）
+            if resumed {
+                resumed = false;
+            } else if self.depth == 0 {
+                let pause = match mode {
+                    StepMode::Block => Some(self.pause_before(workflow, registry, block_id, None)),
+                    StepMode::Continue => self.check_breakpoint(workflow, registry, block_id),
+                    StepMode::Tick => None,
+                };
+                if let Some(pause) = pause {
+                    return Ok(TickStatus::Paused(pause));
+                }
+            }
+
+            let previous_outputs = workflow.blocks[&block_id].output_values.clone();
 
-        for block_id in order {
             // 激活Block（用于流动动画）
             workflow.activate_block(block_id);
+            progress.next += 1;
             self.execute_block(workflow, registry, block_id)?;
+
+            if let Some(block) = workflow.blocks.get(&block_id) {
+                for (port, value) in &block.output_values {
+                    if previous_outputs.get(port) != Some(value) {
+                        progress.changed.insert((block_id, port.clone()));
+                    }
+                }
+            }
         }
 
+        log::debug!("增量执行: {} 个输出端口变化", progress.changed.len());
         workflow.dirty_blocks.clear();
-        Ok(())
+        self.record_history(workflow);
+        Ok(TickStatus::Completed)
     }
 
-    /// 执行单个Block
-    fn execute_block(
+    /// 暂停在Block执行之前，记录即将传入的输入
+    fn pause_before(
         &self,
+        workflow: &Workflow,
+        registry: &ScriptRegistry,
+        block_id: Uuid,
+        condition_error: Option<String>,
+    ) -> Pause {
+        let inputs = workflow.blocks.get(&block_id)
+            .and_then(|block| registry.get(&block.script_id))
+            .map(|definition| workflow.preview_inputs(block_id, definition))
+            .unwrap_or_default();
+        Pause { block_id, inputs, condition_error }
+    }
+
+    /// 检查Block的断点是否命中（条件出错时同样暂停并带上错误）
+    fn check_breakpoint(&self, workflow: &Workflow, registry: &ScriptRegistry, block_id: Uuid) -> Option<Pause> {
+        let block = workflow.blocks.get(&block_id)?;
+        let condition = block.breakpoint.as_ref()?.condition.trim();
+        if condition.is_empty() {
+            return Some(self.pause_before(workflow, registry, block_id, None));
+        }
+
+        let definition = registry.get(&block.script_id)?;
+        let inputs = workflow.preview_inputs(block_id, definition);
+        match self.eval_condition(condition, &inputs, block) {
+            Ok(true) => Some(Pause { block_id, inputs, condition_error: None }),
+            Ok(false) => None,
+            Err(e) => Some(Pause { block_id, inputs, condition_error: Some(e.to_string()) }),
+        }
+    }
+
+    /// 计算断点条件（与脚本相同的指令预算，结果按 Lua 真值判断）
+    fn eval_condition(&self, condition: &str, inputs: &HashMap<String, Value>, block: &Block) -> Result<bool> {
+        let env = self.lua.create_table().map_err(lua_err)?;
+        let meta = self.lua.create_table().map_err(lua_err)?;
+        meta.set("__index", self.lua.globals()).map_err(lua_err)?;
+        env.set_metatable(Some(meta));
+        env.set("inputs", self.value_to_lua(&Value::Object(inputs.clone()))?).map_err(lua_err)?;
+        env.set("state", self.value_to_lua(&Value::Object(block.state.clone()))?).map_err(lua_err)?;
+        env.set("properties", self.value_to_lua(&Value::Object(block.properties.clone()))?).map_err(lua_err)?;
+        env.set("ctx", self.clock.to_lua(&self.lua).map_err(lua_err)?).map_err(lua_err)?;
+
+        self.limiter.reset();
+        let result: LuaValue = self.lua
+            .load(format!("return ({})", condition))
+            .set_name("断点条件")
+            .set_environment(env)
+            .eval()
+            .map_err(|e| anyhow!("断点条件出错: {}", e))?;
+        Ok(!matches!(result, LuaValue::Nil | LuaValue::Boolean(false)))
+    }
+
+    /// 执行单个Block（超出资源限制时标记为故障并继续执行其他Block，故障Block之后跳过直到被清除）
+    fn execute_block(
+        &mut self,
+        workflow: &mut Workflow,
+        registry: &ScriptRegistry,
+        block_id: Uuid,
+    ) -> Result<()> {
+        if workflow.blocks.get(&block_id).is_some_and(|b| b.fault.is_some()) {
+            return Ok(());
+        }
+
+        self.limiter.reset();
+        let result = self.run_block(workflow, registry, block_id);
+
+        // 录制事件清空前的输出
+        if let (Some(trace), Some(block), 0) = (&mut self.trace, workflow.blocks.get(&block_id), self.depth) {
+            trace.record_block(block);
+        }
+
+        // 发出本次产生的事件
+        if result.is_ok() {
+            if let Some(definition) = workflow.blocks.get(&block_id).and_then(|b| registry.get(&b.script_id)) {
+                workflow.emit_events(block_id, definition);
+            }
+        }
+
+        if let Err(e) = &result {
+            if let Some(block_error) = e.downcast_ref::<BlockError>() {
+                log::warn!("{}", block_error);
+                if let Some(block) = workflow.blocks.get_mut(&block_id) {
+                    block.fault = Some(block_error.to_string());
+                }
+                return Ok(());
+            }
+        }
+        result
+    }
+
+    fn run_block(
+        &mut self,
         workflow: &mut Workflow,
         registry: &ScriptRegistry,
         block_id: Uuid,
@@ -98,26 +711,40 @@ impl WorkflowExecutor {
         // 收集输入值(从连接获取)
         let mut inputs: HashMap<String, Value> = block.input_values.clone();
 
-        // 收集需要更新的连接值
-        let input_connections: Vec<(String, Value)> = workflow.get_input_connections(block_id)
-            .iter()
-            .filter_map(|conn| {
-                workflow.blocks.get(&conn.from_block)
-                    .and_then(|source_block| {
-                        source_block.output_values.get(&conn.from_port)
-                            .map(|v| (conn.to_port.clone(), v.clone()))
-                    })
-            })
-            .collect();
+        // 收集需要更新的连接值（按端口类型转换）
+        let (input_connections, mut warnings) = workflow.connected_inputs(block_id, definition);
 
         for (port_id, value) in &input_connections {
             inputs.insert(port_id.clone(), value.clone());
         }
 
-        // 更新Block的input_values以便UI显示
+        // 检查必需输入，约束数值范围
+        let mut properties = block.properties.clone();
+        // 事件只在收到的这次执行中有值
+        let mut events = workflow.take_events(block_id, definition, &mut inputs);
+        let report = definition.apply_constraints(&mut inputs, &mut properties);
+        warnings.extend(report.warnings);
+        // 等待必需输入时事件放回队列，输入齐全后再处理
+        if !report.missing.is_empty() {
+            workflow.requeue_events(block_id, std::mem::take(&mut events));
+        }
+
+        // 更新Block的input_values以便UI显示（event 端口不保留）
         if let Some(block) = workflow.blocks.get_mut(&block_id) {
-            for (port_id, value) in input_connections {
-                block.input_values.insert(port_id, value);
+            let is_event = |port: &str| definition.inputs.iter().any(|p| p.id == port && p.data_type == DataType::Event);
+            for (port_id, value) in input_connections.into_iter().chain(events) {
+                if !is_event(&port_id) {
+                    block.input_values.insert(port_id, value);
+                }
+            }
+            for warning in &warnings {
+                log::warn!("[{}] {}", block.script_id, warning);
+            }
+            block.warnings = warnings;
+            block.waiting_for = report.missing;
+            if !block.waiting_for.is_empty() {
+                log::debug!("[{}] 等待输入: {:?}", block.script_id, block.waiting_for);
+                return Ok(());
             }
         }
 
@@ -127,16 +754,17 @@ impl WorkflowExecutor {
             None => return Ok(()),
         };
 
-        // 加载并执行Lua脚本
-        let script_content = crate::script::ScriptLoader::load(&definition.script_path)?;
-
-        // 调试：检查 usb 模块在脚本执行前是否存在
-        let usb_check: bool = self.lua.load("return usb ~= nil").eval().unwrap_or(false);
-        if !usb_check {
-            log::warn!("[{}] 脚本执行前 usb 模块不存在！", block.script_id);
+        // 子图输入/输出 Block 只传递数据，子图实例执行嵌套作用域
+        if is_port_block(&block.script_id) {
+            return Ok(());
+        }
+        if let Some(subgraph_id) = instance_of(&block.script_id) {
+            return self.run_subgraph(workflow, registry, block_id, subgraph_id, definition, inputs);
         }
 
-        let script_table: Table = self.lua.load(&script_content).eval().map_err(lua_err)?;
+        // 在该Block的新环境中加载脚本（字节码已缓存）
+        let script_id = block.script_id.clone();
+        let script_table = self.block_script_table(block_id, &script_id, definition)?;
 
         // 获取block的state用于传递给Lua
         let block_state = block.state.clone();
@@ -145,7 +773,7 @@ impl WorkflowExecutor {
         let self_table = self.lua.create_table().map_err(lua_err)?;
 
         let props_table = self.lua.create_table().map_err(lua_err)?;
-        for (key, value) in &block.properties {
+        for (key, value) in &properties {
             props_table.set(key.as_str(), self.value_to_lua(value)?).map_err(lua_err)?;
         }
         self_table.set("properties", props_table).map_err(lua_err)?;
@@ -167,7 +795,7 @@ impl WorkflowExecutor {
         if let Ok(execute_fn) = script_table.get::<mlua::Function>("execute") {
             // Debug: 打印输入和properties
             log::debug!("[{}] inputs: {:?}", block.script_id, inputs);
-            log::debug!("[{}] properties: {:?}", block.script_id, block.properties);
+            log::debug!("[{}] properties: {:?}", block.script_id, properties);
 
             // 验证 self_table 是否正确设置
             if let Ok(props) = self_table.get::<Table>("properties") {
@@ -176,7 +804,10 @@ impl WorkflowExecutor {
                 log::warn!("[{}] self.properties NOT SET!", block.script_id);
             }
 
-            let result: Table = execute_fn.call((self_table.clone(), inputs_table)).map_err(lua_err)?;
+            let ctx_table = self.clock.to_lua(&self.lua).map_err(lua_err)?;
+            let result: Table = execute_fn
+                .call((self_table.clone(), inputs_table, ctx_table))
+                .map_err(|e| self.block_err(e, block_id, &script_id))?;
 
             // Debug: 打印输出
             let mut outputs: HashMap<String, Value> = HashMap::new();
@@ -225,6 +856,92 @@ impl WorkflowExecutor {
         Ok(())
     }
 
+    /// 执行子图实例：输入写入子图输入 Block，增量执行作用域后读取子图输出 Block
+    fn run_subgraph(
+        &mut self,
+        workflow: &mut Workflow,
+        registry: &ScriptRegistry,
+        block_id: Uuid,
+        subgraph_id: Uuid,
+        definition: &BlockDefinition,
+        inputs: HashMap<String, Value>,
+    ) -> Result<()> {
+        if self.depth >= MAX_SUBGRAPH_DEPTH {
+            return Err(anyhow!("子图嵌套超过 {} 层（是否引用了自身？）", MAX_SUBGRAPH_DEPTH));
+        }
+        let Some(subgraph) = workflow.subgraphs.get(&subgraph_id) else {
+            log::warn!("找不到子图: {}", subgraph_id);
+            return Ok(());
+        };
+
+        // 任意子图定义变化都会影响嵌套的实例，作用域随之重建
+        let library: BTreeMap<&Uuid, &Subgraph> = workflow.subgraphs.iter().collect();
+        let fingerprint = serde_json::to_string(&library)?;
+        let mut scope = match self.scopes.remove(&block_id) {
+            Some(scope) if scope.fingerprint == fingerprint => scope,
+            _ => {
+                let mut scoped = subgraph.instantiate(block_id, &workflow.subgraphs);
+                scoped.dirty_blocks = scoped.blocks.keys().copied().collect();
+                SubgraphScope { fingerprint, workflow: scoped }
+            }
+        };
+
+        let is_event = |ports: &[crate::script::PortDefinition], port: &str| {
+            ports.iter().any(|p| p.id == port && p.data_type == DataType::Event)
+        };
+
+        // 事件端口转为子图内的事件，其余端口写入子图输入 Block 的输出
+        let mut events = Vec::new();
+        let mut changed = Vec::new();
+        for block in scope.workflow.blocks.values().filter(|b| b.script_id == GRAPH_INPUT) {
+            let port = port_name(block);
+            if is_event(&definition.inputs, &port) {
+                if let Some(payload) = inputs.get(&port) {
+                    events.push((block.id, payload.clone()));
+                }
+                continue;
+            }
+            let value = inputs.get(&port).cloned().unwrap_or(Value::Nil);
+            if block.output_values.get(VALUE_PORT) != Some(&value) {
+                changed.push((block.id, value));
+            }
+        }
+        for (id, payload) in events {
+            scope.workflow.deliver_event(id, VALUE_PORT, &payload);
+        }
+        for (id, value) in changed {
+            if let Some(block) = scope.workflow.blocks.get_mut(&id) {
+                block.output_values.insert(VALUE_PORT.to_string(), value);
+            }
+            scope.workflow.mark_dirty(id);
+        }
+
+        self.depth += 1;
+        let result = self.execute_dirty(&mut scope.workflow, registry);
+        self.depth -= 1;
+
+        // 事件端口取走收到的负载，避免下次重复触发
+        let outputs: Vec<(String, Value)> = scope.workflow.blocks.values_mut()
+            .filter(|b| b.script_id == GRAPH_OUTPUT)
+            .map(|b| {
+                let port = port_name(b);
+                let value = if is_event(&definition.outputs, &port) {
+                    b.input_values.remove(VALUE_PORT)
+                } else {
+                    b.input_values.get(VALUE_PORT).cloned()
+                };
+                (port, value.unwrap_or(Value::Nil))
+            })
+            .collect();
+        self.scopes.insert(block_id, scope);
+        result?;
+
+        if let Some(block) = workflow.blocks.get_mut(&block_id) {
+            block.output_values.extend(outputs);
+        }
+        Ok(())
+    }
+
     fn value_to_lua(&self, value: &Value) -> Result<LuaValue> {
         Ok(match value {
             Value::Nil => LuaValue::Nil,
@@ -256,12 +973,33 @@ impl WorkflowExecutor {
             LuaValue::Number(n) => Value::Number(n),
             LuaValue::String(s) => Value::String(s.to_str().map_err(lua_err)?.to_string()),
             LuaValue::Table(t) => {
-                let mut map = HashMap::new();
-                for pair in t.pairs::<String, LuaValue>() {
-                    let (k, v) = pair.map_err(lua_err)?;
-                    map.insert(k, self.lua_to_value(v)?);
+                // 只有正整数键的非空表视为数组（与播放器一致）
+                let mut is_array = true;
+                let mut max_idx = 0usize;
+                for pair in t.clone().pairs::<LuaValue, LuaValue>() {
+                    let (k, _) = pair.map_err(lua_err)?;
+                    match k {
+                        LuaValue::Integer(i) if i > 0 => max_idx = max_idx.max(i as usize),
+                        _ => {
+                            is_array = false;
+                            break;
+                        }
+                    }
+                }
+                if is_array && max_idx > 0 {
+                    let mut arr = Vec::with_capacity(max_idx);
+                    for i in 1..=max_idx {
+                        arr.push(self.lua_to_value(t.get::<LuaValue>(i).map_err(lua_err)?)?);
+                    }
+                    Value::Array(arr)
+                } else {
+                    let mut map = HashMap::new();
+                    for pair in t.pairs::<String, LuaValue>() {
+                        let (k, v) = pair.map_err(lua_err)?;
+                        map.insert(k, self.lua_to_value(v)?);
+                    }
+                    Value::Object(map)
                 }
-                Value::Object(map)
             }
             _ => Value::Nil,
         })
@@ -274,3 +1012,637 @@ impl Default for WorkflowExecutor {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::test_support::{chain_workflow, TestRegistry, ADD_SCRIPT, GRAPH_PORT_SCRIPTS};
+    use crate::workflow::{Block, Breakpoint, Connection, Vec2};
+    use std::time::{Duration, Instant};
+
+    const COUNTER_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.counter", name = "计数", always_run = true },
+    outputs = {
+        { id = "count", name = "计数", type = "number" },
+        { id = "leaked", name = "泄漏", type = "boolean" },
+    },
+    execute = function(self, inputs)
+        counter = (counter or 0) + 1
+        return { count = counter, leaked = rawget(_G, "counter") ~= counter or string == nil }
+    end
+}
+"#;
+
+    const LOOP_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.loop", name = "死循环" },
+    execute = function(self, inputs)
+        while true do end
+    end
+}
+"#;
+
+    const GATE_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.gate", name = "闸门" },
+    inputs = {
+        { id = "on", name = "事件", type = "event" },
+        { id = "value", name = "数值", type = "number", required = true },
+    },
+    outputs = { { id = "last", name = "负载", type = "number" } },
+    execute = function(self, inputs)
+        return { last = inputs.on }
+    end
+}
+"#;
+
+    const RANGE_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.range", name = "范围" },
+    properties = { { id = "scale", name = "倍率", type = "number", default = 1, min = 1, max = 5 } },
+    inputs = {
+        { id = "value", name = "数值", type = "number", required = true, min = 0, max = 10 },
+        { id = "limit", name = "上限", type = "number", default = 1, max = 3, out_of_range = "reject" },
+    },
+    outputs = {
+        { id = "value", name = "数值", type = "number" },
+        { id = "limit", name = "上限", type = "number" },
+        { id = "scale", name = "倍率", type = "number" },
+    },
+    execute = function(self, inputs)
+        return { value = inputs.value, limit = inputs.limit, scale = self.properties.scale }
+    end
+}
+"#;
+
+    const EMIT_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.emit", name = "发出事件", always_run = true },
+    outputs = { { id = "fired", name = "事件", type = "event" } },
+    execute = function(self, inputs)
+        self.state.tick = (self.state.tick or 0) + 1
+        if self.state.tick % 2 == 1 then
+            return { fired = self.state.tick }
+        end
+        return {}
+    end
+}
+"#;
+
+    const LISTEN_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.listen", name = "订阅事件" },
+    inputs = { { id = "on", name = "事件", type = "event" } },
+    outputs = {
+        { id = "count", name = "次数", type = "number" },
+        { id = "last", name = "负载", type = "number" },
+    },
+    execute = function(self, inputs)
+        if inputs.on then
+            self.state.count = (self.state.count or 0) + 1
+            self.state.last = inputs.on
+        end
+        return { count = self.state.count or 0, last = self.state.last }
+    end
+}
+"#;
+
+    const CLOCK_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.clock", name = "时钟", always_run = true },
+    outputs = {
+        { id = "tick", name = "tick", type = "number" },
+        { id = "elapsed", name = "时间", type = "number" },
+        { id = "dt", name = "步长", type = "number" },
+    },
+    execute = function(self, inputs, ctx)
+        return { tick = ctx.tick, elapsed = ctx.elapsed, dt = ctx.dt }
+    end
+}
+"#;
+
+    const DICE_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.dice", name = "骰子", always_run = true },
+    outputs = { { id = "roll", name = "点数", type = "number" } },
+    execute = function(self, inputs)
+        return { roll = math.random(1000000) }
+    end
+}
+"#;
+
+    const LIST_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.list", name = "列表" },
+    properties = { { id = "bad", name = "坏数据", type = "boolean", default = false } },
+    outputs = { { id = "items", name = "列表", type = "array", element_type = "number" } },
+    execute = function(self, inputs)
+        if self.properties.bad then
+            return { items = { 1, "x" } }
+        end
+        return { items = { 1, "2", 3 } }
+    end
+}
+"#;
+
+    const SUM_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.sum", name = "求和" },
+    inputs = { { id = "items", name = "列表", type = "array", element_type = "number" } },
+    outputs = { { id = "sum", name = "和", type = "number" } },
+    execute = function(self, inputs)
+        local sum = 0
+        for _, n in ipairs(inputs.items or {}) do
+            sum = sum + n
+        end
+        return { sum = sum }
+    end
+}
+"#;
+
+    /// 创建只包含测试脚本的注册表
+    fn test_registry(name: &str) -> TestRegistry {
+        TestRegistry::new(&format!("executor_{}", name), &[
+            ("add.lua", ADD_SCRIPT),
+            ("counter.lua", COUNTER_SCRIPT),
+            ("loop.lua", LOOP_SCRIPT),
+            ("range.lua", RANGE_SCRIPT),
+            ("emit.lua", EMIT_SCRIPT),
+            ("listen.lua", LISTEN_SCRIPT),
+            ("gate.lua", GATE_SCRIPT),
+            ("clock.lua", CLOCK_SCRIPT),
+            ("dice.lua", DICE_SCRIPT),
+            ("list.lua", LIST_SCRIPT),
+            ("sum.lua", SUM_SCRIPT),
+            GRAPH_PORT_SCRIPTS[0],
+            GRAPH_PORT_SCRIPTS[1],
+        ])
+    }
+
+    #[test]
+    fn test_execute_chain_with_cache() {
+        let registry = test_registry("chain");
+        let (mut workflow, _) = chain_workflow(&registry, 5);
+        let mut executor = WorkflowExecutor::new().unwrap();
+
+        executor.execute_all(&mut workflow, &registry).unwrap();
+        executor.execute_all(&mut workflow, &registry).unwrap();
+
+        let last = *workflow.execution_order.last().unwrap();
+        assert_eq!(workflow.blocks[&last].get_output("result").and_then(|v| v.as_number()), Some(5.0));
+        assert_eq!(executor.script_cache.len(), 1);
+
+        executor.invalidate_script("test.add");
+        assert!(executor.script_cache.is_empty());
+    }
+
+    #[test]
+    fn test_execute_dirty_is_incremental() {
+        let registry = test_registry("incremental");
+        let (mut workflow, _) = chain_workflow(&registry, 3);
+        let counter = workflow.add_block(Block::new(registry.get("test.counter").unwrap(), Vec2::new(0.0, 200.0)));
+        let order = workflow.execution_order.clone();
+        let chain: Vec<uuid::Uuid> = order.iter().copied().filter(|id| *id != counter).collect();
+        let mut executor = WorkflowExecutor::new().unwrap();
+
+        let mut executed = |workflow: &mut Workflow| {
+            workflow.active_blocks.clear();
+            executor.execute_dirty(workflow, &registry).unwrap();
+            let mut ids: Vec<uuid::Uuid> = workflow.active_blocks.keys().copied().collect();
+            ids.sort();
+            ids
+        };
+
+        // 首次执行全部，之后只执行 always_run Block
+        assert_eq!(executed(&mut workflow).len(), 4);
+        assert_eq!(executed(&mut workflow), vec![counter]);
+
+        // 重新执行但输出未变，下游不执行
+        workflow.dirty_blocks.insert(chain[0]);
+        let mut expected = vec![chain[0], counter];
+        expected.sort();
+        assert_eq!(executed(&mut workflow), expected);
+
+        // 输入变化后沿下游传播
+        workflow.blocks.get_mut(&chain[0]).unwrap().input_values.insert("a".into(), Value::Number(10.0));
+        workflow.dirty_blocks.insert(chain[0]);
+        assert_eq!(executed(&mut workflow).len(), 4);
+        assert_eq!(workflow.blocks[&chain[2]].get_output("result").and_then(|v| v.as_number()), Some(13.0));
+
+        // 在当前 tick 内重新执行：不推进时钟，不重复执行 always_run Block
+        let tick = executor.clock().tick;
+        workflow.blocks.get_mut(&chain[0]).unwrap().input_values.insert("a".into(), Value::Number(20.0));
+        workflow.dirty_blocks.insert(chain[0]);
+        workflow.active_blocks.clear();
+        executor.rerun_dirty(&mut workflow, &registry).unwrap();
+        assert_eq!(workflow.active_blocks.len(), 3);
+        assert!(!workflow.active_blocks.contains_key(&counter));
+        assert_eq!(workflow.blocks[&chain[2]].get_output("result").and_then(|v| v.as_number()), Some(23.0));
+        assert_eq!(executor.clock().tick, tick);
+    }
+
+    #[test]
+    fn test_required_inputs_and_ranges() {
+        let registry = test_registry("range");
+        let mut workflow = Workflow::new("test");
+        let id = workflow.add_block(Block::new(registry.get("test.range").unwrap(), Vec2::new(0.0, 0.0)));
+        let mut executor = WorkflowExecutor::new().unwrap();
+
+        // 必需输入为 nil 时等待，不执行
+        executor.execute_all(&mut workflow, &registry).unwrap();
+        assert_eq!(workflow.blocks[&id].waiting_for, vec!["数值".to_string()]);
+        assert_eq!(workflow.blocks[&id].get_output("scale"), Some(&Value::Nil));
+
+        let block = workflow.blocks.get_mut(&id).unwrap();
+        block.input_values.insert("value".into(), Value::Number(20.0));
+        block.input_values.insert("limit".into(), Value::Number(7.0));
+        block.properties.insert("scale".into(), Value::Number(100.0));
+        executor.execute_all(&mut workflow, &registry).unwrap();
+
+        let block = &workflow.blocks[&id];
+        assert!(block.waiting_for.is_empty());
+        assert_eq!(block.get_output("value").and_then(|v| v.as_number()), Some(10.0));
+        assert_eq!(block.get_output("limit").and_then(|v| v.as_number()), Some(1.0));
+        assert_eq!(block.get_output("scale").and_then(|v| v.as_number()), Some(5.0));
+        assert_eq!(block.warnings.len(), 1);
+
+        // 由连线提供的必需输入断开后重新等待，不沿用旧值
+        workflow.blocks.get_mut(&id).unwrap().input_values.remove("value");
+        let source = workflow.add_block(Block::new(registry.get("test.add").unwrap(), Vec2::new(-200.0, 0.0)));
+        let conn = workflow.add_connection(Connection::new(source, "result".into(), id, "value".into()), &registry).unwrap();
+        executor.execute_dirty(&mut workflow, &registry).unwrap();
+        assert!(workflow.blocks[&id].waiting_for.is_empty());
+        assert_eq!(workflow.blocks[&id].get_output("value").and_then(|v| v.as_number()), Some(1.0));
+
+        workflow.remove_connection(conn);
+        assert!(!workflow.blocks[&id].input_values.contains_key("value"));
+        executor.execute_dirty(&mut workflow, &registry).unwrap();
+        assert_eq!(workflow.blocks[&id].waiting_for, vec!["数值".to_string()]);
+    }
+
+    #[test]
+    fn test_lua_arrays_pass_through_connections() {
+        let registry = test_registry("array");
+        let mut workflow = Workflow::new("test");
+        let list = workflow.add_block(Block::new(registry.get("test.list").unwrap(), Vec2::new(0.0, 0.0)));
+        let sum = workflow.add_block(Block::new(registry.get("test.sum").unwrap(), Vec2::new(200.0, 0.0)));
+        workflow.add_connection(Connection::new(list, "items".into(), sum, "items".into()), &registry).unwrap();
+        let mut executor = WorkflowExecutor::new().unwrap();
+        let total = |workflow: &Workflow| workflow.blocks[&sum].get_output("sum").and_then(|v| v.as_number());
+
+        // Lua 序列表作为数组传递，元素按 element_type 转换
+        executor.execute_all(&mut workflow, &registry).unwrap();
+        assert!(matches!(workflow.blocks[&list].get_output("items"), Some(Value::Array(items)) if items.len() == 3));
+        assert_eq!(total(&workflow), Some(6.0));
+        assert!(workflow.blocks[&sum].warnings.is_empty());
+
+        // 转换失败时使用端口默认值，不沿用上一次的输入
+        workflow.blocks.get_mut(&list).unwrap().properties.insert("bad".into(), Value::Boolean(true));
+        workflow.mark_dirty(list);
+        executor.execute_dirty(&mut workflow, &registry).unwrap();
+        assert_eq!(total(&workflow), Some(0.0));
+        assert_eq!(workflow.blocks[&sum].warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_block_globals_are_isolated() {
+        let registry = test_registry("isolation");
+        let definition = registry.get("test.counter").unwrap();
+        let mut workflow = Workflow::new("test");
+        let a = workflow.add_block(Block::new(definition, Vec2::new(0.0, 0.0)));
+        let b = workflow.add_block(Block::new(definition, Vec2::new(0.0, 100.0)));
+        let mut executor = WorkflowExecutor::new().unwrap();
+
+        for _ in 0..3 {
+            executor.execute_all(&mut workflow, &registry).unwrap();
+        }
+
+        // 全局变量既不在Block之间共享，也不带到下一个 tick
+        for id in [a, b] {
+            let block = &workflow.blocks[&id];
+            assert_eq!(block.get_output("count").and_then(|v| v.as_number()), Some(1.0));
+            assert_eq!(block.get_output("leaked").and_then(|v| v.as_bool()), Some(false));
+        }
+        let global: Option<f64> = executor.lua.globals().get("counter").unwrap();
+        assert_eq!(global, None);
+    }
+
+    #[test]
+    fn test_instruction_budget_faults_block() {
+        let registry = test_registry("budget");
+        let mut workflow = Workflow::new("test");
+        let id = workflow.add_block(Block::new(registry.get("test.loop").unwrap(), Vec2::new(0.0, 0.0)));
+        let counter = workflow.add_block(Block::new(registry.get("test.counter").unwrap(), Vec2::new(200.0, 0.0)));
+        let mut executor = WorkflowExecutor::new().unwrap();
+        executor
+            .set_limits(ExecutionLimits { instruction_budget: 100_000, ..Default::default() })
+            .unwrap();
+
+        // 故障只中止该Block，同一 tick 的其他Block照常执行
+        executor.execute_all(&mut workflow, &registry).unwrap();
+        let fault = workflow.blocks[&id].fault.clone().unwrap();
+        assert!(fault.contains("指令预算"), "{}", fault);
+        assert_eq!(workflow.blocks[&counter].output_values.get("count"), Some(&Value::Number(1.0)));
+
+        // 故障Block在清除前不再执行
+        executor.execute_all(&mut workflow, &registry).unwrap();
+
+        workflow.clear_faults(Some("test.loop"));
+        executor.execute_all(&mut workflow, &registry).unwrap();
+        assert!(workflow.blocks[&id].fault.is_some());
+    }
+
+    #[test]
+    fn test_event_fires_once_for_subscribers() {
+        let registry = test_registry("event");
+        let mut workflow = Workflow::new("test");
+        let emit = workflow.add_block(Block::new(registry.get("test.emit").unwrap(), Vec2::new(0.0, 0.0)));
+        let listen = workflow.add_block(Block::new(registry.get("test.listen").unwrap(), Vec2::new(200.0, 0.0)));
+        workflow.add_block(Block::new(registry.get("test.add").unwrap(), Vec2::new(0.0, 200.0)));
+        workflow
+            .add_connection(Connection::new(emit, "fired".into(), listen, "on".into()), &registry)
+            .unwrap();
+        let mut executor = WorkflowExecutor::new().unwrap();
+
+        let mut executed = |workflow: &mut Workflow| {
+            workflow.active_blocks.clear();
+            executor.execute_dirty(workflow, &registry).unwrap();
+            workflow.active_blocks.keys().copied().collect::<HashSet<_>>()
+        };
+
+        // 首次全部执行，订阅者收到负载 1
+        assert_eq!(executed(&mut workflow).len(), 3);
+        // 事件只持续一次传播：输出被清空，订阅者不再被触发
+        assert_eq!(workflow.blocks[&emit].get_output("fired"), Some(&Value::Nil));
+        assert_eq!(executed(&mut workflow), HashSet::from([emit]));
+        // 再次触发时只执行订阅者，不影响无关Block
+        assert_eq!(executed(&mut workflow), HashSet::from([emit, listen]));
+        assert!(!workflow.has_pending_events(listen));
+
+        let block = &workflow.blocks[&listen];
+        assert_eq!(block.get_output("count").and_then(|v| v.as_number()), Some(2.0));
+        assert_eq!(block.get_output("last").and_then(|v| v.as_number()), Some(3.0));
+    }
+
+    #[test]
+    fn test_events_wait_for_required_inputs() {
+        let registry = test_registry("gate");
+        let mut workflow = Workflow::new("test");
+        let gate = workflow.add_block(Block::new(registry.get("test.gate").unwrap(), Vec2::new(0.0, 0.0)));
+        let mut executor = WorkflowExecutor::new().unwrap();
+        executor.execute_all(&mut workflow, &registry).unwrap();
+
+        // 必需输入未连接时收到的事件保留在队列中
+        workflow.fire_event(gate, "on", Value::Number(7.0));
+        executor.execute_dirty(&mut workflow, &registry).unwrap();
+        assert_eq!(workflow.blocks[&gate].waiting_for, vec!["数值".to_string()]);
+        assert!(workflow.has_pending_events(gate));
+
+        // 输入连接后处理之前收到的事件
+        let source = workflow.add_block(Block::new(registry.get("test.add").unwrap(), Vec2::new(-200.0, 0.0)));
+        workflow
+            .add_connection(Connection::new(source, "result".into(), gate, "value".into()), &registry)
+            .unwrap();
+        executor.execute_dirty(&mut workflow, &registry).unwrap();
+        let block = &workflow.blocks[&gate];
+        assert!(block.waiting_for.is_empty());
+        assert_eq!(block.get_output("last").and_then(|v| v.as_number()), Some(7.0));
+        assert!(!workflow.has_pending_events(gate));
+    }
+
+    #[test]
+    fn test_cycles_and_feedback_edges() {
+        let registry = test_registry("feedback");
+        let definition = registry.get("test.add").unwrap();
+        let build = |a_y: f32, b_y: f32| {
+            let mut workflow = Workflow::new("test");
+            let mut a = Block::new(definition, Vec2::new(0.0, a_y));
+            let mut b = Block::new(definition, Vec2::new(0.0, b_y));
+            a.id = uuid::Uuid::from_u128(1);
+            b.id = uuid::Uuid::from_u128(2);
+            let (a, b) = (workflow.add_block(a), workflow.add_block(b));
+            workflow.add_connection(Connection::new(a, "result".into(), b, "a".into()), &registry).unwrap();
+            let back = workflow.add_connection(Connection::new(b, "result".into(), a, "a".into()), &registry).unwrap();
+            (workflow, a, b, back)
+        };
+
+        // 未标记的循环被检测出来，执行顺序与画布位置无关
+        let (mut workflow, a, b, back) = build(0.0, 100.0);
+        let (swapped, ..) = build(100.0, 0.0);
+        assert_eq!(workflow.cycles, vec![vec![a, b]]);
+        assert_eq!(workflow.execution_order, swapped.execution_order);
+
+        // 反馈连线打破循环，读取上一 tick 的值
+        workflow.set_connection_delayed(back, true);
+        assert!(workflow.cycles.is_empty());
+        assert_eq!(workflow.execution_order, vec![a, b]);
+
+        let mut executor = WorkflowExecutor::new().unwrap();
+        let mut results = Vec::new();
+        for _ in 0..3 {
+            executor.execute_dirty(&mut workflow, &registry).unwrap();
+            let result = |id| workflow.blocks[&id].get_output("result").and_then(|v| v.as_number()).unwrap();
+            results.push((result(a), result(b)));
+        }
+        assert_eq!(results, vec![(1.0, 2.0), (3.0, 4.0), (5.0, 6.0)]);
+    }
+
+    #[test]
+    fn test_clock_is_passed_as_context() {
+        let registry = test_registry("clock");
+        let mut workflow = Workflow::new("test");
+        let id = workflow.add_block(Block::new(registry.get("test.clock").unwrap(), Vec2::new(0.0, 0.0)));
+        let mut executor = WorkflowExecutor::new().unwrap();
+        executor.set_time_scale(2.0);
+
+        for _ in 0..3 {
+            executor.execute_dirty(&mut workflow, &registry).unwrap();
+        }
+
+        let output = |port| workflow.blocks[&id].get_output(port).and_then(|v| v.as_number()).unwrap();
+        assert_eq!(output("tick"), 3.0);
+        assert!((output("dt") - 0.2).abs() < 1e-9);
+        assert!((output("elapsed") - 0.6).abs() < 1e-9);
+        assert_eq!(executor.clock().tick, 3);
+    }
+
+    #[test]
+    fn test_seeded_random_is_reproducible() {
+        let registry = test_registry("dice");
+        let definition = registry.get("test.dice").unwrap();
+        let mut workflow = Workflow::new("test");
+        let a = workflow.add_block(Block::new(definition, Vec2::new(0.0, 0.0)));
+        let b = workflow.add_block(Block::new(definition, Vec2::new(0.0, 100.0)));
+        workflow.seed = 42;
+        let mut executor = WorkflowExecutor::new().unwrap();
+
+        let rolls = |workflow: &mut Workflow, executor: &mut WorkflowExecutor| -> Vec<(f64, f64)> {
+            (0..3)
+                .map(|_| {
+                    executor.execute_dirty(workflow, &registry).unwrap();
+                    let roll = |id| workflow.blocks[&id].get_output("roll").and_then(|v| v.as_number()).unwrap();
+                    (roll(a), roll(b))
+                })
+                .collect()
+        };
+
+        let first = rolls(&mut workflow, &mut executor);
+        assert!(first.iter().all(|(x, y)| x != y));
+        executor.restart();
+        assert_eq!(rolls(&mut workflow, &mut executor), first);
+
+        // 换种子后得到不同的序列，换回来后从头重现
+        workflow.seed = 7;
+        assert_ne!(rolls(&mut workflow, &mut executor), first);
+        workflow.seed = 42;
+        assert_eq!(rolls(&mut workflow, &mut executor), first);
+    }
+
+    #[test]
+    fn test_rewind_resumes_from_history() {
+        let registry = test_registry("rewind");
+        let mut workflow = Workflow::new("test");
+        let dice = workflow.add_block(Block::new(registry.get("test.dice").unwrap(), Vec2::new(0.0, 0.0)));
+        let emit = workflow.add_block(Block::new(registry.get("test.emit").unwrap(), Vec2::new(0.0, 100.0)));
+        let listen = workflow.add_block(Block::new(registry.get("test.listen").unwrap(), Vec2::new(200.0, 100.0)));
+        workflow.add_connection(Connection::new(emit, "fired".into(), listen, "on".into()), &registry).unwrap();
+        let mut executor = WorkflowExecutor::new().unwrap();
+        executor.set_history_capacity(4);
+
+        let outputs = |workflow: &Workflow| {
+            let number = |id, port| workflow.blocks[&id].get_output(port).and_then(|v| v.as_number());
+            (number(dice, "roll"), number(listen, "count"))
+        };
+        let mut first_run = Vec::new();
+        for _ in 0..6 {
+            executor.execute_dirty(&mut workflow, &registry).unwrap();
+            first_run.push(outputs(&workflow));
+        }
+        assert_eq!(executor.history().range(), Some((3, 6)));
+
+        // 回退后状态、随机数流和时钟回到该 tick，继续执行得到相同的结果
+        executor.rewind(&mut workflow, 3).unwrap();
+        assert_eq!(executor.clock().tick, 3);
+        assert_eq!(outputs(&workflow), first_run[2]);
+        for expected in &first_run[3..] {
+            executor.execute_dirty(&mut workflow, &registry).unwrap();
+            assert_eq!(&outputs(&workflow), expected);
+        }
+        assert!(executor.rewind(&mut workflow, 1).is_err());
+    }
+
+    #[test]
+    fn test_rewind_restores_subgraph_scopes() {
+        let mut registry = test_registry("rewind_subgraph");
+        let mut workflow = Workflow::new("test");
+        let dice = workflow.add_block(Block::new(registry.get("test.dice").unwrap(), Vec2::new(0.0, 0.0)));
+        let emit = workflow.add_block(Block::new(registry.get("test.emit").unwrap(), Vec2::new(0.0, 100.0)));
+        let listen = workflow.add_block(Block::new(registry.get("test.listen").unwrap(), Vec2::new(200.0, 100.0)));
+        workflow.add_connection(Connection::new(emit, "fired".into(), listen, "on".into()), &registry).unwrap();
+        let selected: HashSet<Uuid> = [dice, emit, listen].into_iter().collect();
+        let instance = workflow.collapse_to_subgraph(&selected, "内部", &registry).unwrap();
+        workflow.register_subgraphs(&mut registry);
+        let mut executor = WorkflowExecutor::new().unwrap();
+        executor.set_history_capacity(8);
+
+        // 子图内部的输出、状态和随机数流
+        let inner = |executor: &WorkflowExecutor| {
+            let scope = &executor.scopes[&instance].workflow;
+            let block = |id: Uuid| &scope.blocks[&Uuid::new_v5(&instance, id.as_bytes())];
+            let roll = block(dice).get_output("roll").and_then(|v| v.as_number());
+            (roll, block(listen).state.get("count").and_then(|v| v.as_number()))
+        };
+        let mut first_run = Vec::new();
+        for _ in 0..6 {
+            executor.execute_dirty(&mut workflow, &registry).unwrap();
+            first_run.push(inner(&executor));
+        }
+
+        executor.rewind(&mut workflow, 3).unwrap();
+        assert_eq!(inner(&executor), first_run[2]);
+        for expected in &first_run[3..] {
+            executor.execute_dirty(&mut workflow, &registry).unwrap();
+            assert_eq!(&inner(&executor), expected);
+        }
+    }
+
+    #[test]
+    fn test_breakpoints_pause_and_resume_tick() {
+        let registry = test_registry("breakpoint");
+        let (mut workflow, chain) = chain_workflow(&registry, 3);
+        let mut executor = WorkflowExecutor::new().unwrap();
+        let result = |workflow: &Workflow, id: uuid::Uuid| workflow.blocks[&id].get_output("result").and_then(|v| v.as_number());
+
+        // 条件不满足的断点不暂停，满足时停在Block执行之前
+        workflow.set_breakpoint(chain[1], Some(Breakpoint { condition: "inputs.a > 5".into() }));
+        workflow.set_breakpoint(chain[2], Some(Breakpoint { condition: "inputs.a > 1 and ctx.tick == 1".into() }));
+        let TickStatus::Paused(pause) = executor.run_tick(&mut workflow, &registry, StepMode::Continue).unwrap() else {
+            panic!("应停在断点处");
+        };
+        assert_eq!(pause.block_id, chain[2]);
+        assert_eq!(pause.inputs.get("a").and_then(|v| v.as_number()), Some(2.0));
+        assert_eq!(result(&workflow, chain[1]), Some(2.0));
+        assert_eq!(result(&workflow, chain[2]), None);
+
+        // 继续时从暂停的Block开始，不再次命中同一断点
+        assert_eq!(executor.run_tick(&mut workflow, &registry, StepMode::Continue).unwrap(), TickStatus::Completed);
+        assert_eq!(result(&workflow, chain[2]), Some(3.0));
+        assert_eq!(executor.clock().tick, 1);
+
+        // 逐Block单步，暂停期间不前进时钟
+        workflow.dirty_blocks.extend(chain.iter().copied());
+        for id in &chain {
+            match executor.run_tick(&mut workflow, &registry, StepMode::Block).unwrap() {
+                TickStatus::Paused(pause) => assert_eq!(pause.block_id, *id),
+                TickStatus::Completed => panic!("应停在每个Block之前"),
+            }
+        }
+        assert_eq!(executor.run_tick(&mut workflow, &registry, StepMode::Tick).unwrap(), TickStatus::Completed);
+        assert_eq!(executor.clock().tick, 2);
+
+        // 条件出错同样暂停
+        workflow.set_breakpoint(chain[2], None);
+        workflow.set_breakpoint(chain[0], Some(Breakpoint { condition: "inputs.a.b > 0".into() }));
+        workflow.dirty_blocks.insert(chain[0]);
+        let TickStatus::Paused(pause) = executor.run_tick(&mut workflow, &registry, StepMode::Continue).unwrap() else {
+            panic!("条件出错时应暂停");
+        };
+        assert!(pause.condition_error.is_some());
+    }
+
+    /// 基准测试：200个Block时缓存前后的单tick耗时
+    /// 运行: cargo test --release bench_tick_time -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_tick_time() {
+        const BLOCKS: usize = 200;
+        const TICKS: u32 = 100;
+
+        let registry = test_registry("bench");
+        let (mut workflow, _) = chain_workflow(&registry, BLOCKS);
+        let mut executor = WorkflowExecutor::new().unwrap();
+
+        // 每个Block执行前清空缓存，等同于缓存前逐Block重新加载脚本
+        let mut uncached = Duration::ZERO;
+        for _ in 0..TICKS {
+            let start = Instant::now();
+            for block_id in workflow.execution_order.clone() {
+                executor.clear_script_cache();
+                executor.execute_block(&mut workflow, &registry, block_id).unwrap();
+            }
+            uncached += start.elapsed();
+        }
+
+        let mut cached = Duration::ZERO;
+        for _ in 0..TICKS {
+            let start = Instant::now();
+            executor.execute_all(&mut workflow, &registry).unwrap();
+            cached += start.elapsed();
+        }
+
+        println!("{} blocks, 平均每tick耗时:", BLOCKS);
+        println!("  缓存前(每Block重新加载脚本): {:?}", uncached / TICKS);
+        println!("  缓存后:                      {:?}", cached / TICKS);
+    }
+}
@@ -0,0 +1,55 @@
+//! 反馈连线 - 读取上一 tick 的值
+//!
+//! 标记为 `delayed` 的连线不参与执行排序，可以用来显式打破循环。
+//! 每个 tick 开始时锁存来源的输出，本 tick 内下游读到的都是上一 tick 结束时的值，与执行顺序无关。
+
+use super::Workflow;
+use crate::script::Value;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+impl Workflow {
+    /// tick 开始时锁存反馈连线的值并投递上一 tick 的事件，返回输入发生变化的Block
+    pub fn latch_feedback(&mut self) -> HashSet<Uuid> {
+        let mut changed = HashSet::new();
+        for conn in self.connections.values().filter(|c| c.delayed) {
+            // 上一 tick 没有值（如首个 tick）时不锁存，下游使用输入端口的默认值
+            let value = self
+                .blocks
+                .get(&conn.from_block)
+                .and_then(|source| source.output_values.get(&conn.from_port))
+                .filter(|value| !matches!(value, Value::Nil))
+                .cloned();
+            if self.feedback_values.get(&conn.id) != value.as_ref() {
+                match value {
+                    Some(value) => self.feedback_values.insert(conn.id, value),
+                    None => self.feedback_values.remove(&conn.id),
+                };
+                changed.insert(conn.to_block);
+            }
+        }
+        let connections = &self.connections;
+        self.feedback_values.retain(|id, _| connections.get(id).is_some_and(|c| c.delayed));
+
+        for (target, payload) in std::mem::take(&mut self.feedback_events) {
+            changed.insert(target.0);
+            self.pending_events.insert(target, payload);
+        }
+        changed
+    }
+
+    /// 设置连线是否为反馈连线
+    pub fn set_connection_delayed(&mut self, id: Uuid, delayed: bool) {
+        let Some(conn) = self.connections.get_mut(&id) else {
+            return;
+        };
+        if conn.delayed == delayed {
+            return;
+        }
+        conn.delayed = delayed;
+        let to_block = conn.to_block;
+        self.feedback_values.remove(&id);
+        self.mark_dirty(to_block);
+        self.update_execution_order();
+    }
+}
@@ -1,10 +1,16 @@
 //! 工作流图
 
-use super::{Block, BlockGroup, Connection, Layer, Vec2};
+use super::{Block, BlockGroup, Connection, ConnectionError, Layer, Subgraph, Vec2};
+use crate::script::{BlockDefinition, ScriptRegistry, Value};
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use uuid::Uuid;
 
+/// 旧版密码哈希前缀
+const LEGACY_HASH_PREFIX: &str = "LEGNA_";
+
 /// 画布视口
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Viewport {
@@ -48,8 +54,11 @@ impl Viewport {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Workflow {
     pub name: String,
+    #[serde(serialize_with = "super::ordered::map")]
     pub blocks: HashMap<Uuid, Block>,
+    #[serde(serialize_with = "super::ordered::map")]
     pub connections: HashMap<Uuid, Connection>,
+    #[serde(serialize_with = "super::ordered::map")]
     pub groups: HashMap<Uuid, BlockGroup>,
     pub viewport: Viewport,
 
@@ -65,21 +74,46 @@ pub struct Workflow {
     #[serde(default)]
     pub readonly: bool,
 
-    /// 打开密码（MD5哈希存储，空表示无密码）
+    /// 打开密码（Argon2id PHC 字符串，含算法、参数和盐；旧版为 "LEGNA_" 前缀；空表示无密码）
     #[serde(default)]
     pub password_hash: Option<String>,
 
+    /// 随机数种子（每个 Block 的 math.random 由种子和 Block UUID 决定）
+    #[serde(default, skip_serializing_if = "super::block::is_default")]
+    pub seed: u64,
+
+    /// 子图定义（子图实例 Block 的 script_id 为 "subgraph:<UUID>"）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "super::ordered::map")]
+    pub subgraphs: HashMap<Uuid, Subgraph>,
+
     // 执行相关(不序列化)
     #[serde(skip)]
     pub execution_order: Vec<Uuid>,
     #[serde(skip)]
     pub dirty_blocks: HashSet<Uuid>,
+    /// 修订号（增删Block/连线或标记脏时递增，用于同步后台执行线程）
+    #[serde(skip)]
+    pub revision: u64,
 
     /// 最近执行的Block和连线（用于流动动画）
     #[serde(skip)]
     pub active_blocks: HashMap<Uuid, f32>,  // block_id -> 激活强度 (0.0-1.0)
     #[serde(skip)]
     pub active_connections: HashMap<Uuid, f32>,  // connection_id -> 激活强度 (0.0-1.0)
+
+    /// 待处理的事件 ((Block, 输入端口) -> 负载)
+    #[serde(skip)]
+    pub pending_events: HashMap<(Uuid, String), Value>,
+
+    /// 循环依赖（不含反馈连线的强连通分量）
+    #[serde(skip)]
+    pub cycles: Vec<Vec<Uuid>>,
+    /// 反馈连线上一 tick 的值 (连线 -> 值)
+    #[serde(skip)]
+    pub feedback_values: HashMap<Uuid, Value>,
+    /// 经反馈连线发出、下一 tick 才投递的事件
+    #[serde(skip)]
+    pub feedback_events: HashMap<(Uuid, String), Value>,
 }
 
 impl Default for Workflow {
@@ -94,10 +128,17 @@ impl Default for Workflow {
             current_layer_index: None,
             readonly: false,
             password_hash: None,
+            seed: 0,
+            subgraphs: HashMap::new(),
             execution_order: Vec::new(),
             dirty_blocks: HashSet::new(),
+            revision: 0,
             active_blocks: HashMap::new(),
             active_connections: HashMap::new(),
+            pending_events: HashMap::new(),
+            cycles: Vec::new(),
+            feedback_values: HashMap::new(),
+            feedback_events: HashMap::new(),
         }
     }
 }
@@ -112,22 +153,51 @@ impl Workflow {
     pub fn verify_password(&self, password: &str) -> bool {
         match &self.password_hash {
             None => true, // 无密码
-            Some(hash) => &Self::hash_password(password) == hash,
+            Some(hash) if hash.starts_with(LEGACY_HASH_PREFIX) => Self::legacy_hash_password(password) == *hash,
+            Some(hash) => PasswordHash::new(hash)
+                .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+                .unwrap_or(false),
         }
     }
 
+    /// 验证密码并将旧版哈希升级为 Argon2id（打开文件时调用）
+    pub fn unlock(&mut self, password: &str) -> bool {
+        if !self.verify_password(password) {
+            return false;
+        }
+        if self.has_legacy_password() {
+            self.set_password(Some(password));
+            log::info!("打开密码已升级为 Argon2id");
+        }
+        true
+    }
+
+    /// 是否使用旧版密码哈希
+    pub fn has_legacy_password(&self) -> bool {
+        self.password_hash.as_deref().is_some_and(|h| h.starts_with(LEGACY_HASH_PREFIX))
+    }
+
     /// 是否有密码保护
     pub fn has_password(&self) -> bool {
         self.password_hash.is_some()
     }
 
-    /// 简单的密码哈希（生产环境应使用bcrypt）
+    /// 加盐密码哈希（Argon2id，PHC 字符串格式）
     fn hash_password(password: &str) -> String {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .expect("Argon2 默认参数哈希不会失败")
+            .to_string()
+    }
+
+    /// 旧版密码哈希（DefaultHasher，无盐，仅用于验证历史文件）
+    fn legacy_hash_password(password: &str) -> String {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
         let mut hasher = DefaultHasher::new();
         password.hash(&mut hasher);
-        "LEGNA_".to_string() + &format!("{:016x}", hasher.finish())
+        LEGACY_HASH_PREFIX.to_string() + &format!("{:016x}", hasher.finish())
     }
 
     pub fn new(name: impl Into<String>) -> Self {
@@ -150,7 +220,7 @@ impl Workflow {
     }
 
     /// 根据注册表更新所有Block的尺寸（加载后调用）
-    pub fn update_block_sizes(&mut self, registry: &crate::script::ScriptRegistry) {
+    pub fn update_block_sizes(&mut self, registry: &ScriptRegistry) {
         for block in self.blocks.values_mut() {
             if let Some(def) = registry.get(&block.script_id) {
                 block.size = Vec2::new(def.calculate_width(), def.calculate_height());
@@ -170,33 +240,69 @@ impl Workflow {
     /// 删除Block
     pub fn remove_block(&mut self, id: Uuid) {
         self.blocks.remove(&id);
-        // 删除相关连接
-        self.connections
-            .retain(|_, conn| conn.from_block != id && conn.to_block != id);
+        // 删除相关连接，下游Block不再收到该Block的输出
+        let removed: Vec<Connection> = self.connections.values()
+            .filter(|conn| conn.from_block == id || conn.to_block == id)
+            .cloned()
+            .collect();
+        for conn in &removed {
+            self.connections.remove(&conn.id);
+        }
+        for conn in &removed {
+            self.clear_disconnected_input(conn);
+        }
         // 从分组中移除
         for group in self.groups.values_mut() {
             group.blocks.remove(&id);
         }
+        self.dirty_blocks.remove(&id);
+        self.revision += 1;
         self.update_execution_order();
     }
 
-    /// 添加连接
-    pub fn add_connection(&mut self, connection: Connection) -> Uuid {
+    /// 添加连接（端口类型不兼容时拒绝）
+    pub fn add_connection(&mut self, connection: Connection, registry: &ScriptRegistry) -> Result<Uuid, ConnectionError> {
+        connection.validate(&self.blocks, registry)?;
         let id = connection.id;
         self.mark_dirty(connection.to_block);
         self.connections.insert(id, connection);
         self.update_execution_order();
-        id
+        Ok(id)
     }
 
     /// 删除连接
     pub fn remove_connection(&mut self, id: Uuid) {
         if let Some(conn) = self.connections.remove(&id) {
-            self.mark_dirty(conn.to_block);
+            self.clear_disconnected_input(&conn);
         }
+        self.revision += 1;
         self.update_execution_order();
     }
 
+    /// 连线删除后清除目标端口缓存的输入值（端口仍有其他连线时保留），下游重新执行
+    fn clear_disconnected_input(&mut self, conn: &Connection) {
+        let still_connected = self.connections.values()
+            .any(|c| c.to_block == conn.to_block && c.to_port == conn.to_port);
+        let Some(block) = self.blocks.get_mut(&conn.to_block) else {
+            return;
+        };
+        if !still_connected {
+            block.input_values.remove(&conn.to_port);
+        }
+        self.mark_dirty(conn.to_block);
+    }
+
+    /// 校验已有连线，返回所有不合法的连线（加载旧文件后调用）
+    pub fn validate(&self, registry: &ScriptRegistry) -> Vec<(Uuid, ConnectionError)> {
+        let mut errors: Vec<(Uuid, ConnectionError)> = self
+            .connections
+            .values()
+            .filter_map(|conn| conn.validate(&self.blocks, registry).err().map(|e| (conn.id, e)))
+            .collect();
+        errors.sort_by_key(|(id, _)| *id);
+        errors
+    }
+
     /// 获取Block的所有输入连接
     pub fn get_input_connections(&self, block_id: Uuid) -> Vec<&Connection> {
         self.connections
@@ -205,6 +311,47 @@ impl Workflow {
             .collect()
     }
 
+    /// 收集连线传入的输入值，按输入端口的 coerce 模式转换类型（失败时取端口默认值）
+    /// 返回 (端口, 值) 列表和转换失败的警告
+    pub fn connected_inputs(&self, block_id: Uuid, definition: &BlockDefinition) -> (Vec<(String, Value)>, Vec<String>) {
+        let mut values = Vec::new();
+        let mut warnings = Vec::new();
+
+        for conn in self.get_input_connections(block_id) {
+            // 反馈连线读取 tick 开始时锁存的值
+            let value = if conn.delayed {
+                self.feedback_values.get(&conn.id)
+            } else {
+                self.blocks
+                    .get(&conn.from_block)
+                    .and_then(|source| source.output_values.get(&conn.from_port))
+            };
+            let port = definition.inputs.iter().find(|p| p.id == conn.to_port);
+            let Some(value) = value else {
+                // 文件中不保存有连线端口的输入值，来源还没有输出时使用端口默认值
+                let saved = self.blocks.get(&block_id).is_some_and(|b| b.input_values.contains_key(&conn.to_port));
+                if let (Some(port), false) = (port, saved) {
+                    values.push((conn.to_port.clone(), port.default.clone()));
+                }
+                continue;
+            };
+
+            match port {
+                Some(port) => match port.coerce(value) {
+                    Ok(coerced) => values.push((conn.to_port.clone(), coerced)),
+                    Err(e) => {
+                        // 转换失败时使用端口默认值，不沿用上一次的输入
+                        warnings.push(format!("{}: {}", port.name, e));
+                        values.push((conn.to_port.clone(), port.default.clone()));
+                    }
+                },
+                None => values.push((conn.to_port.clone(), value.clone())),
+            }
+        }
+
+        (values, warnings)
+    }
+
     /// 获取Block的所有输出连接
     pub fn get_output_connections(&self, block_id: Uuid) -> Vec<&Connection> {
         self.connections
@@ -216,6 +363,7 @@ impl Workflow {
     /// 标记Block为脏(需要重新执行)
     pub fn mark_dirty(&mut self, block_id: Uuid) {
         self.dirty_blocks.insert(block_id);
+        self.revision += 1;
         // 标记所有下游Block
         let downstream: Vec<Uuid> = self
             .connections
@@ -230,6 +378,20 @@ impl Workflow {
         }
     }
 
+    /// 清除Block的故障标记，使其重新参与执行
+    /// script_id 为 None 时清除全部
+    pub fn clear_faults(&mut self, script_id: Option<&str>) {
+        let cleared: Vec<Uuid> = self
+            .blocks
+            .values_mut()
+            .filter(|block| script_id.is_none_or(|id| block.script_id == id))
+            .filter_map(|block| block.fault.take().map(|_| block.id))
+            .collect();
+        for id in cleared {
+            self.mark_dirty(id);
+        }
+    }
+
     /// 激活Block（执行时调用）
     pub fn activate_block(&mut self, block_id: Uuid) {
         self.active_blocks.insert(block_id, 1.0);
@@ -267,109 +429,97 @@ impl Workflow {
         self.active_connections.get(&conn_id).copied().unwrap_or(0.0)
     }
 
-    /// 更新执行顺序(拓扑排序)
+    /// 更新执行顺序(拓扑排序)，同时重新检测循环
     pub fn update_execution_order(&mut self) {
+        self.cycles = self.find_cycles();
+        if !self.cycles.is_empty() {
+            log::debug!("工作流存在 {} 个循环依赖，循环内按 id 确定顺序", self.cycles.len());
+        }
         self.execution_order = self.topological_sort();
     }
 
-    /// Kahn算法拓扑排序（稳定版：按位置排序保证确定性）
-    fn topological_sort(&self) -> Vec<Uuid> {
-        if self.blocks.is_empty() {
-            return Vec::new();
+    /// 执行依赖图：Block -> 下游Block（按 id 排序，忽略反馈连线和悬空连线）
+    fn dependency_graph(&self) -> BTreeMap<Uuid, Vec<Uuid>> {
+        let mut graph: BTreeMap<Uuid, Vec<Uuid>> = self.blocks.keys().map(|id| (*id, Vec::new())).collect();
+        for conn in self.connections.values().filter(|c| !c.delayed) {
+            if self.blocks.contains_key(&conn.to_block) {
+                if let Some(next) = graph.get_mut(&conn.from_block) {
+                    next.push(conn.to_block);
+                }
+            }
+        }
+        for next in graph.values_mut() {
+            next.sort();
+            next.dedup();
         }
+        graph
+    }
 
-        let mut in_degree: HashMap<Uuid, usize> = HashMap::new();
-        let mut adjacency: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    /// 检测循环依赖：返回包含环的强连通分量（分量内按 id 排序）
+    pub fn find_cycles(&self) -> Vec<Vec<Uuid>> {
+        let graph = self.dependency_graph();
+        let mut cycles: Vec<Vec<Uuid>> = strongly_connected_components(&graph)
+            .into_iter()
+            .filter(|component| component.len() > 1 || graph[&component[0]].contains(&component[0]))
+            .map(|mut component| {
+                component.sort();
+                component
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
 
-        // 初始化
-        for block_id in self.blocks.keys() {
-            in_degree.insert(*block_id, 0);
-            adjacency.insert(*block_id, Vec::new());
+    /// 拓扑排序：强连通分量缩点后按 Kahn 算法排序
+    /// 无依赖关系的分量按位置从上到下、从左到右排列；分量内部的顺序只取决于连线和 id，与画布位置无关
+    fn topological_sort(&self) -> Vec<Uuid> {
+        let graph = self.dependency_graph();
+        let components = strongly_connected_components(&graph);
+        let mut component_of: HashMap<Uuid, usize> = HashMap::new();
+        for (index, component) in components.iter().enumerate() {
+            for id in component {
+                component_of.insert(*id, index);
+            }
         }
 
-        // 构建图
-        for conn in self.connections.values() {
-            // 只处理有效连接（两端 block 都存在）
-            if self.blocks.contains_key(&conn.from_block) && self.blocks.contains_key(&conn.to_block) {
-                if let Some(degree) = in_degree.get_mut(&conn.to_block) {
-                    *degree += 1;
-                }
-                if let Some(adj) = adjacency.get_mut(&conn.from_block) {
-                    if !adj.contains(&conn.to_block) {
-                        adj.push(conn.to_block);
-                    }
+        // 缩点后的入度和邻接
+        let mut in_degree = vec![0usize; components.len()];
+        let mut adjacency: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); components.len()];
+        for (from, next) in &graph {
+            for to in next {
+                let (a, b) = (component_of[from], component_of[to]);
+                if a != b && adjacency[a].insert(b) {
+                    in_degree[b] += 1;
                 }
             }
         }
 
-        // 使用 BTreeMap 按位置排序，确保相同入度的节点有确定顺序
-        // 排序键：(位置Y, 位置X, UUID) 保证从上到下、从左到右
-        let get_sort_key = |id: &Uuid| -> (i32, i32, Uuid) {
-            if let Some(block) = self.blocks.get(id) {
-                ((block.position.y * 100.0) as i32, (block.position.x * 100.0) as i32, *id)
-            } else {
-                (i32::MAX, i32::MAX, *id)
-            }
-        };
-
-        // 收集入度为0的节点，按位置排序
-        let mut queue: VecDeque<Uuid> = {
-            let mut zero_degree: Vec<_> = in_degree
+        // 排序键：(位置Y, 位置X, UUID)，分量取其中最靠前的Block
+        let sort_key = |index: usize| -> (i32, i32, Uuid) {
+            components[index]
                 .iter()
-                .filter(|(_, &degree)| degree == 0)
-                .map(|(&id, _)| id)
-                .collect();
-            zero_degree.sort_by_key(get_sort_key);
-            zero_degree.into_iter().collect()
+                .filter_map(|id| self.blocks.get(id))
+                .map(|block| ((block.position.y * 100.0) as i32, (block.position.x * 100.0) as i32, block.id))
+                .min()
+                .unwrap_or((i32::MAX, i32::MAX, Uuid::nil()))
         };
 
-        let mut result = Vec::with_capacity(self.blocks.len());
+        let mut ready: BTreeSet<((i32, i32, Uuid), usize)> = (0..components.len())
+            .filter(|&index| in_degree[index] == 0)
+            .map(|index| (sort_key(index), index))
+            .collect();
 
-        while let Some(node) = queue.pop_front() {
-            result.push(node);
-
-            if let Some(neighbors) = adjacency.get(&node) {
-                // 收集新的零入度节点
-                let mut new_zero_degree = Vec::new();
-                for &neighbor in neighbors {
-                    if let Some(degree) = in_degree.get_mut(&neighbor) {
-                        *degree -= 1;
-                        if *degree == 0 {
-                            new_zero_degree.push(neighbor);
-                        }
-                    }
-                }
-                // 按位置排序后加入队列
-                new_zero_degree.sort_by_key(get_sort_key);
-                for id in new_zero_degree {
-                    queue.push_back(id);
+        let mut result = Vec::with_capacity(self.blocks.len());
+        while let Some((_, index)) = ready.pop_first() {
+            result.extend(component_order(&components[index], &graph));
+            for &next in &adjacency[index] {
+                in_degree[next] -= 1;
+                if in_degree[next] == 0 {
+                    ready.insert((sort_key(next), next));
                 }
             }
         }
 
-        // 检测环：如果结果数量少于节点数量，说明有环
-        // 游戏中双向交互（如英雄↔Boss）是正常的，仍然需要执行这些节点
-        if result.len() < self.blocks.len() {
-            log::debug!("工作流存在循环依赖，按位置顺序执行循环节点");
-
-            // 收集未处理的节点（循环中的节点）
-            let mut remaining: Vec<_> = self.blocks.keys()
-                .filter(|id| !result.contains(id))
-                .copied()
-                .collect();
-
-            // 按位置排序后加入结果
-            remaining.sort_by_key(|id| {
-                if let Some(block) = self.blocks.get(id) {
-                    ((block.position.y * 100.0) as i32, (block.position.x * 100.0) as i32, *id)
-                } else {
-                    (i32::MAX, i32::MAX, *id)
-                }
-            });
-
-            result.extend(remaining);
-        }
-
         result
     }
 
@@ -664,3 +814,125 @@ impl Workflow {
         self.current_layer_index.and_then(|i| self.layers.get(i))
     }
 }
+
+/// Tarjan 算法求强连通分量
+fn strongly_connected_components(graph: &BTreeMap<Uuid, Vec<Uuid>>) -> Vec<Vec<Uuid>> {
+    struct Tarjan<'a> {
+        graph: &'a BTreeMap<Uuid, Vec<Uuid>>,
+        index: HashMap<Uuid, usize>,
+        low: HashMap<Uuid, usize>,
+        stack: Vec<Uuid>,
+        on_stack: HashSet<Uuid>,
+        components: Vec<Vec<Uuid>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, node: Uuid) {
+            let index = self.index.len();
+            self.index.insert(node, index);
+            self.low.insert(node, index);
+            self.stack.push(node);
+            self.on_stack.insert(node);
+
+            for &next in &self.graph[&node] {
+                if !self.index.contains_key(&next) {
+                    self.visit(next);
+                    let low = self.low[&node].min(self.low[&next]);
+                    self.low.insert(node, low);
+                } else if self.on_stack.contains(&next) {
+                    let low = self.low[&node].min(self.index[&next]);
+                    self.low.insert(node, low);
+                }
+            }
+
+            if self.low[&node] == index {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack.remove(&member);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        graph,
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        components: Vec::new(),
+    };
+    for &node in graph.keys() {
+        if !tarjan.index.contains_key(&node) {
+            tarjan.visit(node);
+        }
+    }
+    tarjan.components
+}
+
+/// 分量内部的执行顺序：从最小 id 出发深度优先遍历，取逆后序
+/// 只有遍历中的回边指向已执行的Block，这些连线读到的是上一 tick 的值
+fn component_order(component: &[Uuid], graph: &BTreeMap<Uuid, Vec<Uuid>>) -> Vec<Uuid> {
+    if component.len() == 1 {
+        return component.to_vec();
+    }
+    let members: HashSet<Uuid> = component.iter().copied().collect();
+    let mut visited: HashSet<Uuid> = HashSet::new();
+    let mut postorder = Vec::with_capacity(component.len());
+
+    fn visit(node: Uuid, graph: &BTreeMap<Uuid, Vec<Uuid>>, members: &HashSet<Uuid>, visited: &mut HashSet<Uuid>, postorder: &mut Vec<Uuid>) {
+        visited.insert(node);
+        for &next in &graph[&node] {
+            if members.contains(&next) && !visited.contains(&next) {
+                visit(next, graph, members, visited, postorder);
+            }
+        }
+        postorder.push(node);
+    }
+
+    let mut start: Vec<Uuid> = component.to_vec();
+    start.sort();
+    for node in start {
+        if !visited.contains(&node) {
+            visit(node, graph, &members, &mut visited, &mut postorder);
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_hash_is_salted() {
+        let mut a = Workflow::new("a");
+        let mut b = Workflow::new("b");
+        a.set_password(Some("secret"));
+        b.set_password(Some("secret"));
+
+        assert!(a.password_hash.as_deref().unwrap().starts_with("$argon2id$"));
+        assert_ne!(a.password_hash, b.password_hash);
+        assert!(a.verify_password("secret"));
+        assert!(!a.verify_password("wrong"));
+    }
+
+    #[test]
+    fn test_legacy_password_migrates_on_unlock() {
+        let mut workflow = Workflow::new("old");
+        workflow.password_hash = Some(Workflow::legacy_hash_password("secret"));
+
+        assert!(!workflow.unlock("wrong"));
+        assert!(workflow.has_legacy_password());
+
+        assert!(workflow.unlock("secret"));
+        assert!(!workflow.has_legacy_password());
+        assert!(workflow.verify_password("secret"));
+    }
+}
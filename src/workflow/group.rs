@@ -11,6 +11,7 @@ pub struct BlockGroup {
     pub id: Uuid,
     pub name: String,
     pub color: [u8; 3],
+    #[serde(serialize_with = "super::ordered::set")]
     pub blocks: HashSet<Uuid>,
     pub position: Vec2,
     pub size: Vec2,
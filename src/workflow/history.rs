@@ -0,0 +1,171 @@
+//! 执行历史 - 每个 tick 结束后的完整运行时快照
+//!
+//! 快照保存在有上限的环形缓冲区中，超出上限时丢弃最早的 tick。
+//! 回退到某个 tick 后继续执行，会覆盖该 tick 之后的历史。
+
+use crate::script::{PortDefinition, Value};
+use crate::workflow::{SimulationClock, Workflow};
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+/// 编辑器默认保留的 tick 数
+pub const DEFAULT_HISTORY_CAPACITY: usize = 600;
+
+/// 单个Block在 tick 结束时的运行时数据
+#[derive(Debug, Clone)]
+struct BlockFrame {
+    input_values: HashMap<String, Value>,
+    output_values: HashMap<String, Value>,
+    state: HashMap<String, Value>,
+    dynamic_outputs: Vec<PortDefinition>,
+    fault: Option<String>,
+}
+
+/// 一个 tick 结束时的运行时快照
+#[derive(Debug, Clone)]
+pub struct TickFrame {
+    pub clock: SimulationClock,
+    blocks: HashMap<Uuid, BlockFrame>,
+    pending_events: HashMap<(Uuid, String), Value>,
+    feedback_values: HashMap<Uuid, Value>,
+    feedback_events: HashMap<(Uuid, String), Value>,
+    /// 每个Block随机数流的状态
+    pub rng: HashMap<Uuid, u64>,
+    /// 子图实例内部工作流的快照 (实例 Block UUID -> 快照)
+    pub scopes: HashMap<Uuid, TickFrame>,
+}
+
+impl TickFrame {
+    /// 保存工作流的运行时数据
+    pub fn capture(workflow: &Workflow, clock: SimulationClock, rng: HashMap<Uuid, u64>) -> Self {
+        let blocks = workflow.blocks.iter()
+            .map(|(id, block)| {
+                (*id, BlockFrame {
+                    input_values: block.input_values.clone(),
+                    output_values: block.output_values.clone(),
+                    state: block.state.clone(),
+                    dynamic_outputs: block.dynamic_outputs.clone(),
+                    fault: block.fault.clone(),
+                })
+            })
+            .collect();
+        Self {
+            clock,
+            blocks,
+            pending_events: workflow.pending_events.clone(),
+            feedback_values: workflow.feedback_values.clone(),
+            feedback_events: workflow.feedback_events.clone(),
+            rng,
+            scopes: HashMap::new(),
+        }
+    }
+
+    /// 写回工作流（快照之后新增的Block保持不变）
+    pub fn restore(&self, workflow: &mut Workflow) {
+        for (id, frame) in &self.blocks {
+            let Some(block) = workflow.blocks.get_mut(id) else {
+                continue;
+            };
+            block.input_values = frame.input_values.clone();
+            block.output_values = frame.output_values.clone();
+            block.state = frame.state.clone();
+            block.dynamic_outputs = frame.dynamic_outputs.clone();
+            block.fault = frame.fault.clone();
+            block.waiting_for.clear();
+            block.warnings.clear();
+        }
+        workflow.pending_events = self.pending_events.iter()
+            .filter(|((id, _), _)| workflow.blocks.contains_key(id))
+            .map(|(key, payload)| (key.clone(), payload.clone()))
+            .collect();
+        workflow.feedback_values = self.feedback_values.iter()
+            .filter(|(id, _)| workflow.connections.contains_key(*id))
+            .map(|(id, value)| (*id, value.clone()))
+            .collect();
+        workflow.feedback_events = self.feedback_events.iter()
+            .filter(|((id, _), _)| workflow.blocks.contains_key(id))
+            .map(|(key, payload)| (key.clone(), payload.clone()))
+            .collect();
+        workflow.dirty_blocks.clear();
+    }
+}
+
+/// 按 tick 排列的快照环形缓冲区
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    capacity: usize,
+    frames: VecDeque<TickFrame>,
+}
+
+impl History {
+    /// capacity 为 0 时不保存历史
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, frames: VecDeque::new() }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 修改上限（超出的最早快照被丢弃）
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.frames.len() > capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    /// 保存的最早和最晚 tick
+    pub fn range(&self) -> Option<(u64, u64)> {
+        Some((self.frames.front()?.clock.tick, self.frames.back()?.clock.tick))
+    }
+
+    /// 指定 tick 结束时的快照
+    pub fn get(&self, tick: u64) -> Option<&TickFrame> {
+        let index = self.frames.binary_search_by_key(&tick, |frame| frame.clock.tick).ok()?;
+        self.frames.get(index)
+    }
+
+    /// 记录新的 tick（丢弃同一 tick 及之后的旧快照）
+    pub fn record(&mut self, frame: TickFrame) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.frames.back().is_some_and(|last| last.clock.tick >= frame.clock.tick) {
+            self.frames.pop_back();
+        }
+        self.frames.push_back(frame);
+        if self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(tick: u64) -> TickFrame {
+        let clock = SimulationClock { tick, ..SimulationClock::default() };
+        TickFrame::capture(&Workflow::new("test"), clock, HashMap::new())
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_rewrites_future() {
+        let mut history = History::new(3);
+        for tick in 1..=5 {
+            history.record(frame(tick));
+        }
+        assert_eq!(history.range(), Some((3, 5)));
+        assert!(history.get(2).is_none());
+
+        // 回退到 tick 3 后重新执行，旧的 tick 4、5 被覆盖
+        history.record(frame(4));
+        assert_eq!(history.range(), Some((3, 4)));
+        assert!(history.get(5).is_none());
+    }
+}
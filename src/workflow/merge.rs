@@ -0,0 +1,411 @@
+//! 工作流三方合并
+//!
+//! 以 base 为共同祖先合并 ours 和 theirs：Block、连线、分组、图层按 UUID 对齐，
+//! 只有一方修改的字段直接采用，双方改成不同值时记录冲突并保留 ours。
+//! `properties` / `input_values` 按键合并，`position` 和连线端点整体比较。
+//! `state` 每次执行都会改写，直接采用 ours，不记录冲突；由连线提供的输入值不写入文档，不参与合并。
+//!
+//! 合并在 migration 输出的 JSON 文档上进行，三个输入的格式版本可以不同。
+
+use super::migration;
+use super::Workflow;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashSet};
+
+/// 按键合并的字段
+const KEYED_FIELDS: [&str; 2] = ["properties", "input_values"];
+/// Block 的运行时字段（采用 ours）
+const RUNTIME_FIELD: &str = "state";
+
+/// 冲突类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// 工作流自身的字段（名称、只读、密码）
+    Workflow,
+    Block,
+    Property,
+    Position,
+    Connection,
+    Group,
+    Layer,
+    Subgraph,
+}
+
+/// 合并冲突（已按 ours 解决）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergeConflict {
+    pub kind: ConflictKind,
+    /// 冲突对象的 UUID（工作流字段为空）
+    pub id: Option<String>,
+    /// 冲突字段，如 "properties.hp"（整个对象冲突时为空）
+    pub field: Option<String>,
+    pub message: String,
+    /// 三方的值（不存在为 null）
+    pub base: Value,
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+/// 合并结果
+pub struct MergeResult {
+    pub workflow: Workflow,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// 合并的对象集合
+#[derive(Clone, Copy)]
+struct Collection {
+    key: &'static str,
+    kind: ConflictKind,
+}
+
+const COLLECTIONS: [Collection; 4] = [
+    Collection { key: "blocks", kind: ConflictKind::Block },
+    Collection { key: "connections", kind: ConflictKind::Connection },
+    Collection { key: "groups", kind: ConflictKind::Group },
+    Collection { key: "subgraphs", kind: ConflictKind::Subgraph },
+];
+
+/// 三方合并
+pub fn merge(base: &Workflow, ours: &Workflow, theirs: &Workflow) -> Result<MergeResult> {
+    let base = migration::to_value(base)?;
+    let ours = migration::to_value(ours)?;
+    let theirs = migration::to_value(theirs)?;
+    let mut merger = Merger::default();
+
+    let mut merged = ours.as_object().cloned().unwrap_or_default();
+
+    // 工作流字段（视口属于界面状态，直接采用 ours）
+    for field in ["name", "readonly", "password_hash", "seed"] {
+        let value = merger.pick(
+            ConflictKind::Workflow, None, Some(field),
+            base.get(field), ours.get(field), theirs.get(field),
+        );
+        set_or_remove(&mut merged, field, value);
+    }
+
+    for collection in COLLECTIONS {
+        let result = merger.merge_collection(
+            collection,
+            base.get(collection.key).and_then(Value::as_object),
+            ours.get(collection.key).and_then(Value::as_object),
+            theirs.get(collection.key).and_then(Value::as_object),
+        );
+        merged.insert(collection.key.to_string(), Value::Object(result));
+    }
+
+    let layers = merger.merge_collection(
+        Collection { key: "layers", kind: ConflictKind::Layer },
+        layers_by_id(&base).as_ref(),
+        layers_by_id(&ours).as_ref(),
+        layers_by_id(&theirs).as_ref(),
+    );
+    let mut layers: Vec<Value> = layers.into_values().collect();
+    layers.sort_by_key(|layer| layer.get("order").and_then(Value::as_u64).unwrap_or(0));
+    merged.insert("layers".to_string(), Value::Array(layers));
+
+    merger.prune_dangling(&mut merged, base.get("connections").and_then(Value::as_object));
+
+    Ok(MergeResult {
+        workflow: migration::from_value(Value::Object(merged))?,
+        conflicts: merger.conflicts,
+    })
+}
+
+/// 图层数组转为按 id 索引的对象
+fn layers_by_id(doc: &Value) -> Option<Map<String, Value>> {
+    let layers = doc.get("layers")?.as_array()?;
+    Some(
+        layers.iter()
+            .filter_map(|layer| Some((layer.get("id")?.as_str()?.to_string(), layer.clone())))
+            .collect(),
+    )
+}
+
+fn set_or_remove(map: &mut Map<String, Value>, key: &str, value: Option<Value>) {
+    match value {
+        Some(value) => map.insert(key.to_string(), value),
+        None => map.remove(key),
+    };
+}
+
+#[derive(Default)]
+struct Merger {
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Merger {
+    /// 三方取值：一方未改采用另一方，双方改成不同值时冲突并采用 ours
+    fn pick(
+        &mut self,
+        kind: ConflictKind,
+        id: Option<&str>,
+        field: Option<&str>,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Option<Value> {
+        if ours == theirs || theirs == base {
+            return ours.cloned();
+        }
+        if ours == base {
+            return theirs.cloned();
+        }
+
+        let message = match (ours, theirs) {
+            (None, _) => "ours 删除，theirs 修改",
+            (_, None) => "ours 修改，theirs 删除",
+            _ if base.is_none() => "双方新增了不同的内容",
+            _ => "双方修改为不同的值",
+        };
+        self.conflicts.push(MergeConflict {
+            kind,
+            id: id.map(str::to_string),
+            field: field.map(str::to_string),
+            message: message.to_string(),
+            base: base.cloned().unwrap_or(Value::Null),
+            ours: ours.cloned().unwrap_or(Value::Null),
+            theirs: theirs.cloned().unwrap_or(Value::Null),
+        });
+
+        // 删除/修改冲突保留修改的一方，避免丢失工作
+        ours.or(theirs).cloned()
+    }
+
+    /// 合并按 UUID 索引的对象集合
+    fn merge_collection(
+        &mut self,
+        collection: Collection,
+        base: Option<&Map<String, Value>>,
+        ours: Option<&Map<String, Value>>,
+        theirs: Option<&Map<String, Value>>,
+    ) -> Map<String, Value> {
+        let ids: BTreeSet<&String> = [base, ours, theirs]
+            .into_iter()
+            .flatten()
+            .flat_map(|map| map.keys())
+            .collect();
+
+        let mut merged = Map::new();
+        for id in ids {
+            let (b, o, t) = (base.and_then(|m| m.get(id)), ours.and_then(|m| m.get(id)), theirs.and_then(|m| m.get(id)));
+            if let Some(value) = self.merge_entity(collection, id, b, o, t) {
+                merged.insert(id.clone(), value);
+            }
+        }
+        merged
+    }
+
+    /// 合并单个对象：双方都修改时逐字段合并
+    fn merge_entity(
+        &mut self,
+        collection: Collection,
+        id: &str,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Option<Value> {
+        let (Some(Value::Object(o)), Some(Value::Object(t))) = (ours, theirs) else {
+            return self.pick(collection.kind, Some(id), None, base, ours, theirs);
+        };
+        if ours == theirs || theirs == base {
+            return ours.cloned();
+        }
+        if ours == base {
+            return theirs.cloned();
+        }
+
+        let empty = Map::new();
+        let b = base.and_then(Value::as_object).unwrap_or(&empty);
+        let fields: BTreeSet<&String> = b.keys().chain(o.keys()).chain(t.keys()).collect();
+
+        let mut merged = Map::new();
+        for field in fields {
+            let value = if collection.kind == ConflictKind::Block && field == RUNTIME_FIELD {
+                o.get(field).cloned()
+            } else if KEYED_FIELDS.contains(&field.as_str()) {
+                self.merge_keyed(collection, id, field, b.get(field), o.get(field), t.get(field))
+            } else {
+                let kind = match (collection.kind, field.as_str()) {
+                    (ConflictKind::Block, "position") => ConflictKind::Position,
+                    (kind, _) => kind,
+                };
+                self.pick(kind, Some(id), Some(field), b.get(field), o.get(field), t.get(field))
+            };
+            set_or_remove(&mut merged, field, value);
+        }
+        Some(Value::Object(merged))
+    }
+
+    /// 按键合并属性类字段
+    fn merge_keyed(
+        &mut self,
+        collection: Collection,
+        id: &str,
+        field: &str,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Option<Value> {
+        let empty = Map::new();
+        let b = base.and_then(Value::as_object).unwrap_or(&empty);
+        let o = ours.and_then(Value::as_object).unwrap_or(&empty);
+        let t = theirs.and_then(Value::as_object).unwrap_or(&empty);
+        let kind = if collection.kind == ConflictKind::Block { ConflictKind::Property } else { collection.kind };
+
+        let keys: BTreeSet<&String> = b.keys().chain(o.keys()).chain(t.keys()).collect();
+        let mut merged = Map::new();
+        for key in keys {
+            let path = format!("{}.{}", field, key);
+            let value = self.pick(kind, Some(id), Some(&path), b.get(key), o.get(key), t.get(key));
+            set_or_remove(&mut merged, key, value);
+        }
+        Some(Value::Object(merged))
+    }
+
+    /// 移除指向已删除 Block 的连线和分组成员（新增或修改过的连线记为冲突）
+    fn prune_dangling(&mut self, merged: &mut Map<String, Value>, base_connections: Option<&Map<String, Value>>) {
+        let block_ids: HashSet<String> = merged.get("blocks")
+            .and_then(Value::as_object)
+            .map(|blocks| blocks.keys().cloned().collect())
+            .unwrap_or_default();
+        let exists = |value: Option<&Value>| value.and_then(Value::as_str).is_some_and(|id| block_ids.contains(id));
+
+        if let Some(connections) = merged.get_mut("connections").and_then(Value::as_object_mut) {
+            let dangling: Vec<String> = connections.iter()
+                .filter(|(_, c)| !exists(c.get("from_block")) || !exists(c.get("to_block")))
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in dangling {
+                let connection = connections.remove(&id).unwrap_or(Value::Null);
+                if base_connections.and_then(|c| c.get(&id)) == Some(&connection) {
+                    continue;
+                }
+                self.conflicts.push(MergeConflict {
+                    kind: ConflictKind::Connection,
+                    id: Some(id),
+                    field: None,
+                    message: "连线端点的 Block 已被另一方删除，连线已移除".to_string(),
+                    base: Value::Null,
+                    ours: connection,
+                    theirs: Value::Null,
+                });
+            }
+        }
+
+        if let Some(groups) = merged.get_mut("groups").and_then(Value::as_object_mut) {
+            for group in groups.values_mut() {
+                if let Some(Value::Array(members)) = group.get_mut("blocks") {
+                    members.retain(|id| exists(Some(id)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn block(id: &str, x: f64, hp: f64) -> Value {
+        json!({
+            "id": id, "script_id": "lite.hero",
+            "position": { "x": x, "y": 0.0 }, "size": { "x": 140.0, "y": 60.0 },
+            "input_values": {}, "properties": { "hp": hp, "atk": 5.0 }, "state": {},
+            "collapsed": false, "group_id": null
+        })
+    }
+
+    fn workflow(blocks: Vec<Value>, connections: Vec<Value>) -> Workflow {
+        let by_id = |items: Vec<Value>| -> Map<String, Value> {
+            items.into_iter().map(|v| (v["id"].as_str().unwrap().to_string(), v)).collect()
+        };
+        migration::from_value(json!({
+            "format_version": migration::FORMAT_VERSION,
+            "name": "合并测试",
+            "blocks": by_id(blocks),
+            "connections": by_id(connections),
+            "groups": {},
+            "viewport": { "offset": { "x": 0.0, "y": 0.0 }, "zoom": 1.0 }
+        }))
+        .unwrap()
+    }
+
+    const A: &str = "00000000-0000-0000-0000-00000000000a";
+    const B: &str = "00000000-0000-0000-0000-00000000000b";
+    const C: &str = "00000000-0000-0000-0000-00000000000c";
+    const AB: &str = "00000000-0000-0000-0000-0000000000ab";
+
+    fn connection(id: &str, from: &str, to: &str) -> Value {
+        json!({ "id": id, "from_block": from, "from_port": "out", "to_block": to, "to_port": "in" })
+    }
+
+    #[test]
+    fn test_merge_independent_edits() {
+        let base = workflow(vec![block(A, 0.0, 10.0), block(B, 100.0, 10.0)], vec![]);
+        let ours = workflow(vec![block(A, 50.0, 10.0), block(B, 100.0, 10.0)], vec![]);
+        let mut theirs_a = block(A, 0.0, 20.0);
+        theirs_a["properties"]["atk"] = json!(5.0);
+        let theirs = workflow(vec![theirs_a, block(B, 100.0, 10.0), block(C, 200.0, 1.0)], vec![connection(AB, A, B)]);
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert!(result.conflicts.is_empty(), "{:?}", result.conflicts);
+
+        let a = &result.workflow.blocks[&A.parse().unwrap()];
+        assert_eq!(a.position.x, 50.0);
+        assert_eq!(a.properties["hp"].as_number(), Some(20.0));
+        assert_eq!(result.workflow.blocks.len(), 3);
+        assert_eq!(result.workflow.connections.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_reports_conflicts() {
+        let base = workflow(vec![block(A, 0.0, 10.0), block(B, 100.0, 10.0)], vec![]);
+        // ours: 改 hp、移动 A、新增 A→B 连线；theirs: 改 hp、移动 A、删除 B
+        let ours = workflow(vec![block(A, 50.0, 30.0), block(B, 100.0, 10.0)], vec![connection(AB, A, B)]);
+        let theirs = workflow(vec![block(A, 80.0, 40.0)], vec![]);
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        let kinds: Vec<(ConflictKind, Option<&str>)> = result.conflicts.iter()
+            .map(|c| (c.kind, c.field.as_deref()))
+            .collect();
+        assert_eq!(kinds, vec![
+            (ConflictKind::Position, Some("position")),
+            (ConflictKind::Property, Some("properties.hp")),
+            (ConflictKind::Connection, None),
+        ]);
+
+        // 冲突保留 ours；B 只被 theirs 删除、ours 未修改，因此删除生效，连线随之移除
+        let a = &result.workflow.blocks[&A.parse().unwrap()];
+        assert_eq!(a.position.x, 50.0);
+        assert_eq!(a.properties["hp"].as_number(), Some(30.0));
+        assert!(!result.workflow.blocks.contains_key(&B.parse().unwrap()));
+        assert!(result.workflow.connections.is_empty());
+    }
+
+    #[test]
+    fn test_merge_keeps_ours_runtime_fields() {
+        let with_runtime = |hp: f64, fed: f64, manual: f64, turn: f64| {
+            let mut b = block(B, 100.0, hp);
+            b["input_values"] = json!({ "in": fed, "bonus": manual });
+            b["state"] = json!({ "turn": turn });
+            b
+        };
+        let base = workflow(vec![block(A, 0.0, 10.0), with_runtime(10.0, 1.0, 0.0, 1.0)], vec![connection(AB, A, B)]);
+        let ours = workflow(vec![block(A, 0.0, 10.0), with_runtime(10.0, 2.0, 0.0, 2.0)], vec![connection(AB, A, B)]);
+        let theirs = workflow(vec![block(A, 0.0, 10.0), with_runtime(20.0, 3.0, 5.0, 3.0)], vec![connection(AB, A, B)]);
+
+        // 运行时数据不冲突，采用 ours；编写的属性和未连线的输入值照常合并
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert!(result.conflicts.is_empty(), "{:?}", result.conflicts);
+        let b = &result.workflow.blocks[&B.parse().unwrap()];
+        assert_eq!(b.properties["hp"].as_number(), Some(20.0));
+        assert_eq!(b.input_values["bonus"].as_number(), Some(5.0));
+        assert!(!b.input_values.contains_key("in"));
+        assert_eq!(b.state["turn"].as_number(), Some(2.0));
+    }
+}
@@ -0,0 +1,206 @@
+//! .L 文件格式版本与迁移
+//!
+//! 文件顶层的 `format_version` 记录格式版本（没有该字段的旧文件视为 v0）。
+//! 加载时在 `serde_json::Value` 上逐级执行迁移，再反序列化为 `Workflow`；
+//! 保存时总是写入当前版本。
+//!
+//! 修改序列化结构时：递增 FORMAT_VERSION，并在 MIGRATIONS 末尾追加一个迁移函数。
+
+use super::Workflow;
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// 当前格式版本
+pub const FORMAT_VERSION: u64 = 2;
+/// 版本字段名
+const VERSION_FIELD: &str = "format_version";
+
+/// 迁移函数：就地把文档从版本 n 升级到 n + 1
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// MIGRATIONS[n] 将版本 n 升级到 n + 1
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [v0_to_v1, v1_to_v2];
+
+/// v0 → v1：v0 为没有版本号的旧文件，结构与 v1 相同
+fn v0_to_v1(_doc: &mut Map<String, Value>) -> Result<()> {
+    Ok(())
+}
+
+/// v1 → v2：不再保存选中状态和运行时输出值
+fn v1_to_v2(doc: &mut Map<String, Value>) -> Result<()> {
+    if let Some(blocks) = doc.get_mut("blocks").and_then(Value::as_object_mut) {
+        for block in blocks.values_mut().filter_map(Value::as_object_mut) {
+            block.remove("selected");
+            block.remove("output_values");
+        }
+    }
+    if let Some(connections) = doc.get_mut("connections").and_then(Value::as_object_mut) {
+        for connection in connections.values_mut().filter_map(Value::as_object_mut) {
+            connection.remove("selected");
+        }
+    }
+    Ok(())
+}
+
+/// 读取文档的格式版本
+pub fn version_of(doc: &Value) -> Result<u64> {
+    match doc.get(VERSION_FIELD) {
+        None => Ok(0),
+        Some(v) => v.as_u64().ok_or_else(|| anyhow!("无效的格式版本: {}", v)),
+    }
+}
+
+/// 将文档迁移到当前版本
+pub fn migrate(mut doc: Value) -> Result<Value> {
+    let version = version_of(&doc)?;
+    if version > FORMAT_VERSION {
+        return Err(anyhow!("文件格式版本 {} 过新（当前支持 {}），请更新程序", version, FORMAT_VERSION));
+    }
+
+    let map = doc.as_object_mut().ok_or_else(|| anyhow!("工作流文件顶层不是对象"))?;
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(map).map_err(|e| anyhow!("格式迁移 v{} → v{} 失败: {}", from, from + 1, e))?;
+        map.insert(VERSION_FIELD.to_string(), Value::from(from as u64 + 1));
+    }
+    Ok(doc)
+}
+
+/// 序列化为带当前版本号的文档
+pub fn to_value(workflow: &Workflow) -> Result<Value> {
+    let mut doc = serde_json::to_value(workflow)?;
+    if let Some(map) = doc.as_object_mut() {
+        map.insert(VERSION_FIELD.to_string(), Value::from(FORMAT_VERSION));
+        strip_connected_inputs(map);
+
+        // 引用外部文件的子图只保存引用
+        if let Some(subgraphs) = map.get_mut("subgraphs").and_then(Value::as_object_mut) {
+            for subgraph in subgraphs.values_mut().filter_map(Value::as_object_mut) {
+                if subgraph.contains_key("source") {
+                    subgraph.remove("blocks");
+                    subgraph.remove("connections");
+                } else {
+                    strip_connected_inputs(subgraph);
+                }
+            }
+        }
+    }
+    Ok(doc)
+}
+
+/// 由连线提供的输入值每次执行都会改写，只保存未连线端口的输入值
+fn strip_connected_inputs(doc: &mut Map<String, Value>) {
+    let connected: HashSet<(String, String)> = doc.get("connections")
+        .and_then(Value::as_object)
+        .map(|connections| {
+            connections.values()
+                .filter_map(|c| Some((c.get("to_block")?.as_str()?.to_string(), c.get("to_port")?.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    let Some(blocks) = doc.get_mut("blocks").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for (id, block) in blocks.iter_mut() {
+        if let Some(inputs) = block.get_mut("input_values").and_then(Value::as_object_mut) {
+            inputs.retain(|port, _| !connected.contains(&(id.clone(), port.clone())));
+        }
+    }
+}
+
+/// 迁移并反序列化
+pub fn from_value(doc: Value) -> Result<Workflow> {
+    let doc = migrate(doc)?;
+    Ok(serde_json::from_value(doc)?)
+}
+
+/// 供 `#[serde(with = "...")]` 使用，嵌入其他结构（如游戏包）的工作流同样带版本号并经过迁移
+pub mod versioned {
+    use super::*;
+    use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(workflow: &Workflow, serializer: S) -> Result<S::Ok, S::Error> {
+        to_value(workflow).map_err(S::Error::custom)?.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Workflow, D::Error> {
+        from_value(Value::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::BlueprintStorage;
+    use std::path::Path;
+
+    #[test]
+    fn test_checked_in_workflows_migrate() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("workflows");
+        let mut count = 0;
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("L") {
+                continue;
+            }
+
+            let raw: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let workflow = BlueprintStorage::load(&path, None)
+                .unwrap_or_else(|e| panic!("{} 加载失败: {}", path.display(), e));
+            assert_eq!(workflow.blocks.len(), raw["blocks"].as_object().unwrap().len(), "{}", path.display());
+            assert_eq!(workflow.connections.len(), raw["connections"].as_object().unwrap().len(), "{}", path.display());
+
+            // 再次保存写入当前版本，并且可以原样读回
+            let saved = to_value(&workflow).unwrap();
+            assert_eq!(version_of(&saved).unwrap(), FORMAT_VERSION);
+            assert_eq!(from_value(saved).unwrap().blocks.len(), workflow.blocks.len());
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_save_is_deterministic() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("workflows").join("lite_rpg_demo.L");
+        let save = || {
+            // 每次加载的 HashMap 使用不同的随机种子，迭代顺序不同
+            let mut workflow = BlueprintStorage::load(&path, None).unwrap();
+            let members = workflow.blocks.keys().copied().collect();
+            let group = crate::workflow::BlockGroup::new("分组".into(), members);
+            workflow.groups.insert(uuid::Uuid::nil(), group);
+            for block in workflow.blocks.values_mut() {
+                block.selected = true;
+                block.output_values.insert("hp".into(), crate::script::Value::Number(1.0));
+            }
+            let mut doc = to_value(&workflow).unwrap();
+            doc["groups"][uuid::Uuid::nil().to_string()]["id"] = Value::Null;
+            serde_json::to_string_pretty(&doc).unwrap()
+        };
+
+        let first = save();
+        assert_eq!(first, save());
+        assert!(!first.contains("\"selected\""));
+        assert!(!first.contains("\"output_values\""));
+
+        // 有连线的输入端口不保存输入值
+        let doc: Value = serde_json::from_str(&first).unwrap();
+        let connections = doc["connections"].as_object().unwrap();
+        assert!(!connections.is_empty());
+        for connection in connections.values() {
+            let block = connection["to_block"].as_str().unwrap();
+            let port = connection["to_port"].as_str().unwrap();
+            assert!(doc["blocks"][block]["input_values"].get(port).is_none(), "{}.{}", block, port);
+        }
+    }
+
+    #[test]
+    fn test_version_checks() {
+        let doc = serde_json::json!({ "name": "旧文件", "blocks": {}, "connections": {}, "groups": {}, "viewport": { "offset": { "x": 0, "y": 0 }, "zoom": 1.0 } });
+        assert_eq!(version_of(&doc).unwrap(), 0);
+        assert_eq!(version_of(&migrate(doc.clone()).unwrap()).unwrap(), FORMAT_VERSION);
+        assert!(from_value(doc).is_ok());
+
+        let future = serde_json::json!({ "format_version": FORMAT_VERSION + 1 });
+        assert!(migrate(future).is_err());
+    }
+}
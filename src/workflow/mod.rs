@@ -1,22 +1,49 @@
 //! Workflow Runtime - Block、连接、执行引擎
 
 mod block;
+mod clock;
 mod clipboard;
 mod connection;
-mod executor;
+pub mod crypto;
+mod debugger;
+mod diff;
+mod event;
+mod feedback;
+pub mod executor;
 mod graph;
 mod group;
+mod history;
 mod layer;
+mod merge;
+mod migration;
+pub mod ordered;
 mod package;
+mod rng;
+pub mod signing;
 mod storage;
+mod subgraph;
+#[cfg(test)]
+mod test_support;
+mod trace;
+mod worker;
 
 pub use block::*;
 pub use clipboard::Clipboard;
+pub use clock::{FixedStep, SimulationClock, DEFAULT_DT};
 pub use connection::*;
+pub use debugger::{Breakpoint, Pause, StepMode, TickStatus};
+pub use diff::{ChangeKind, DiffTarget, WorkflowDiff, POSITION_FIELD};
 pub use executor::WorkflowExecutor;
 pub use graph::{Viewport, Workflow};
 pub use group::BlockGroup;
+pub use history::{History, TickFrame, DEFAULT_HISTORY_CAPACITY};
 pub use layer::Layer;
+pub use merge::merge;
 pub use package::GamePackage;
+pub use rng::BlockRng;
+pub use signing::{PublisherKey, TrustStore};
 pub use storage::{BlueprintFormat, BlueprintStorage};
+pub use trace::{Interaction, Trace, TraceHeader, TraceRecorder, TRACE_EXTENSION};
+pub use subgraph::{instance_of, is_port_block, Subgraph, MAX_SUBGRAPH_DEPTH};
+pub use worker::{ExecutorCommand, ExecutorHandle};
 
@@ -0,0 +1,17 @@
+//! 稳定顺序序列化
+//!
+//! HashMap/HashSet 的迭代顺序每次运行都不同，保存文件时按键排序，
+//! 使 .L 文件的 git diff 只包含语义变化。
+
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// 按键排序序列化 HashMap
+pub fn map<K: Ord + Serialize, V: Serialize, S: Serializer>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// 排序后序列化 HashSet
+pub fn set<T: Ord + Serialize, S: Serializer>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    set.iter().collect::<BTreeSet<_>>().serialize(serializer)
+}
@@ -3,30 +3,35 @@
 //! .lpak (Legna Package) 格式：
 //! - 将工作流 + 所有使用的脚本打包成单一加密文件
 //! - 播放器从内存加载，无法提取原始脚本
+//! - 包内携带能力清单，播放器运行前展示给用户确认
 //!
 //! 文件结构:
 //! [8字节魔数: "LEGNAPAK"]
-//! [4字节版本: 0x0001]
-//! [N字节: AES加密的JSON数据]
+//! [4字节版本: 0x0003]
+//! [1字节: 是否签名]
+//! 签名时: [2字节发布者名称长度] [发布者名称] [32字节公钥] [64字节签名]
+//! [N字节: 口令加密容器，见 crypto 模块]
+//!
+//! 签名覆盖签名字段之外的全部字节，无需口令即可验证来源。
+//! v1 数据包使用固定密钥 AES-128-CBC，v2 没有签名段，均仍可读取（视为未签名）。
+//! 发布时口令可留空，此时内容只防篡改，不防查看。
 
+use super::crypto;
+use super::signing::{self, Publisher, PublisherKey, TrustStore, PUBLIC_KEY_LEN, SIGNATURE_LEN};
 use super::Workflow;
-use crate::script::{ScriptLoader, ScriptRegistry};
-use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use crate::script::{Capability, ScriptLoader, ScriptRegistry};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::path::Path;
 
 /// 魔数
 const MAGIC: &[u8; 8] = b"LEGNAPAK";
 /// 版本
-const VERSION: u32 = 1;
-/// 加密密钥（与 LZ 格式不同，更长更安全）
-const PACKAGE_KEY: &str = "LegnaGamePackage2024";
-
-type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
-type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+const VERSION: u32 = 3;
+/// v1 固定加密密钥
+const LEGACY_KEY: &str = "LegnaGamePackage2024";
 
 /// 游戏数据包内容（序列化到JSON）
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,10 +40,17 @@ pub struct GamePackage {
     pub name: String,
     /// 版本信息
     pub version: String,
-    /// 工作流数据
+    /// 工作流数据（带格式版本，加载时迁移）
+    #[serde(with = "super::migration::versioned")]
     pub workflow: Workflow,
     /// 脚本源码（key = script_id, value = Lua源码）
     pub scripts: HashMap<String, String>,
+    /// 能力清单：脚本需要的受限库（旧版数据包没有清单，视为不请求任何能力）
+    #[serde(default)]
+    pub capabilities: BTreeSet<Capability>,
+    /// 签名验证结果（加载时填充，不序列化）
+    #[serde(skip)]
+    pub publisher: Publisher,
 }
 
 impl GamePackage {
@@ -52,6 +64,9 @@ impl GamePackage {
     ) -> Result<Self> {
         let mut scripts = HashMap::new();
 
+        // 播放器不支持子图，打包前展开为普通Block
+        let workflow = &workflow.flatten_subgraphs()?;
+
         // 收集所有使用到的 script_id
         let used_scripts: std::collections::HashSet<_> = workflow
             .blocks
@@ -67,33 +82,97 @@ impl GamePackage {
             }
         }
 
+        let capabilities = Self::detect_capabilities(&scripts);
+
         Ok(Self {
             name: name.to_string(),
             version: version.to_string(),
             workflow: workflow.to_distributable(), // 只读版本
             scripts,
+            capabilities,
+            publisher: Publisher::Unsigned,
         })
     }
 
-    /// 保存到文件（加密）
-    pub fn save(&self, path: &Path) -> Result<()> {
+    /// 根据脚本源码推断需要的能力
+    pub fn detect_capabilities(scripts: &HashMap<String, String>) -> BTreeSet<Capability> {
+        scripts
+            .values()
+            .flat_map(|source| Capability::detect(source))
+            .collect()
+    }
+
+    /// 保存到文件（口令加密，空口令表示公开数据包；可选发布者签名）
+    pub fn save(&self, path: &Path, passphrase: &str, signer: Option<&PublisherKey>) -> Result<()> {
         let json = serde_json::to_string(self)?;
-        let encrypted = Self::encrypt(&json)?;
 
-        // 构建文件
-        let mut file_data = Vec::with_capacity(12 + encrypted.len());
+        let mut file_data = Vec::with_capacity(12 + json.len());
         file_data.extend_from_slice(MAGIC);
         file_data.extend_from_slice(&VERSION.to_le_bytes());
-        file_data.extend_from_slice(&encrypted);
+        let sealed = crypto::seal(json.as_bytes(), passphrase, &file_data)?;
+
+        match signer {
+            Some(key) => {
+                let name = key.name.as_bytes();
+                let name_len = u16::try_from(name.len()).map_err(|_| anyhow!("发布者名称过长"))?;
+                file_data.push(1);
+                file_data.extend_from_slice(&name_len.to_le_bytes());
+                file_data.extend_from_slice(name);
+                file_data.extend_from_slice(&key.public_key());
+
+                let mut message = file_data.clone();
+                message.extend_from_slice(&sealed);
+                file_data.extend_from_slice(&key.sign(&message));
+            }
+            None => file_data.push(0),
+        }
+        file_data.extend_from_slice(&sealed);
 
         fs::write(path, file_data)?;
         Ok(())
     }
 
-    /// 从文件加载（解密）
-    pub fn load(path: &Path) -> Result<Self> {
+    /// 从文件加载（验证签名后解密）
+    pub fn load(path: &Path, passphrase: &str, trust: &TrustStore) -> Result<Self> {
         let data = fs::read(path)?;
+        Self::load_from_bytes(&data, passphrase, trust)
+    }
 
+    /// 只验证签名，不解密（用于选择界面展示发布者）
+    pub fn inspect(path: &Path, trust: &TrustStore) -> Result<Publisher> {
+        let data = fs::read(path)?;
+        let version = Self::check_header(&data)?;
+        if version < VERSION {
+            return Ok(Publisher::Unsigned);
+        }
+        Ok(Self::verify_signature(&data, trust)?.0)
+    }
+
+    /// 从内存中的数据加载（用于嵌入式资源）
+    pub fn load_from_bytes(data: &[u8], passphrase: &str, trust: &TrustStore) -> Result<Self> {
+        let version = Self::check_header(data)?;
+        let (publisher, plaintext) = match version {
+            1 => {
+                let (key, iv) = Self::legacy_key_iv();
+                let plaintext = crypto::legacy_decrypt(&data[12..], key, iv)
+                    .ok_or_else(|| anyhow!("解密失败：数据包损坏或版本不兼容"))?;
+                (Publisher::Unsigned, plaintext)
+            }
+            2 => (Publisher::Unsigned, crypto::open(&data[12..], passphrase, &data[..12])?),
+            _ => {
+                let (publisher, offset) = Self::verify_signature(data, trust)?;
+                (publisher, crypto::open(&data[offset..], passphrase, &data[..12])?)
+            }
+        };
+
+        let json = String::from_utf8(plaintext).map_err(|_| anyhow!("解密失败：数据格式错误"))?;
+        let mut package: GamePackage = serde_json::from_str(&json)?;
+        package.publisher = publisher;
+        Ok(package)
+    }
+
+    /// 检查魔数和版本，返回版本号
+    fn check_header(data: &[u8]) -> Result<u32> {
         // 检查最小长度
         if data.len() < 12 {
             return Err(anyhow!("文件太小，不是有效的游戏数据包"));
@@ -109,57 +188,44 @@ impl GamePackage {
         if version > VERSION {
             return Err(anyhow!("数据包版本过新，请更新播放器"));
         }
-
-        // 解密
-        let json = Self::decrypt(&data[12..])?;
-        let package: GamePackage = serde_json::from_str(&json)?;
-
-        Ok(package)
+        Ok(version)
     }
 
-    /// 从内存中的数据加载（用于嵌入式资源）
-    pub fn load_from_bytes(data: &[u8]) -> Result<Self> {
-        if data.len() < 12 || &data[0..8] != MAGIC {
-            return Err(anyhow!("无效的游戏数据包"));
+    /// 验证 v3 签名段，返回发布者和加密容器的起始位置
+    fn verify_signature(data: &[u8], trust: &TrustStore) -> Result<(Publisher, usize)> {
+        let truncated = || anyhow!("数据包签名段不完整");
+        match data.get(12) {
+            Some(0) => return Ok((Publisher::Unsigned, 13)),
+            Some(1) => {}
+            Some(_) => return Err(anyhow!("未知的签名标记")),
+            None => return Err(truncated()),
         }
 
-        let json = Self::decrypt(&data[12..])?;
-        let package: GamePackage = serde_json::from_str(&json)?;
-        Ok(package)
-    }
-
-    /// AES-128-CBC 加密
-    fn encrypt(data: &str) -> Result<Vec<u8>> {
-        let (key, iv) = Self::derive_key_iv();
-        let data_bytes = data.as_bytes();
-
-        let block_size = 16;
-        let padded_len = (data_bytes.len() / block_size + 1) * block_size;
-        let mut buf = vec![0u8; padded_len];
-        buf[..data_bytes.len()].copy_from_slice(data_bytes);
-
-        let ct = Aes128CbcEnc::new(&key.into(), &iv.into())
-            .encrypt_padded_mut::<Pkcs7>(&mut buf, data_bytes.len())
-            .map_err(|_| anyhow!("加密失败"))?;
-
-        Ok(ct.to_vec())
-    }
+        let name_len = data.get(13..15).ok_or_else(truncated)?;
+        let name_len = u16::from_le_bytes([name_len[0], name_len[1]]) as usize;
+        let name_end = 15 + name_len;
+        let key_end = name_end + PUBLIC_KEY_LEN;
+        let signature_end = key_end + SIGNATURE_LEN;
+        if data.len() < signature_end {
+            return Err(truncated());
+        }
 
-    /// AES-128-CBC 解密
-    fn decrypt(data: &[u8]) -> Result<String> {
-        let (key, iv) = Self::derive_key_iv();
-        let mut buf = data.to_vec();
+        let claimed_name = String::from_utf8_lossy(&data[15..name_end]);
+        let public_key: [u8; PUBLIC_KEY_LEN] = data[name_end..key_end].try_into()?;
+        let signature: [u8; SIGNATURE_LEN] = data[key_end..signature_end].try_into()?;
 
-        let pt = Aes128CbcDec::new(&key.into(), &iv.into())
-            .decrypt_padded_mut::<Pkcs7>(&mut buf)
-            .map_err(|_| anyhow!("解密失败：数据包损坏或版本不兼容"))?;
+        let mut message = data[..key_end].to_vec();
+        message.extend_from_slice(&data[signature_end..]);
+        if !signing::verify(&message, &public_key, &signature) {
+            return Err(anyhow!("签名无效：数据包已被篡改"));
+        }
 
-        String::from_utf8(pt.to_vec()).map_err(|_| anyhow!("解密失败：数据格式错误"))
+        Ok((Publisher::identify(&claimed_name, &public_key, trust), signature_end))
     }
 
-    /// 派生密钥和IV
-    fn derive_key_iv() -> ([u8; 16], [u8; 16]) {
-        let key_bytes = PACKAGE_KEY.as_bytes();
+    /// v1 密钥和IV
+    fn legacy_key_iv() -> ([u8; 16], [u8; 16]) {
+        let key_bytes = LEGACY_KEY.as_bytes();
         let mut key = [0u8; 16];
         let mut iv = [0u8; 16];
 
@@ -172,3 +238,69 @@ impl GamePackage {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> GamePackage {
+        GamePackage {
+            name: "demo".into(),
+            version: "1.0.0".into(),
+            workflow: Workflow::new("demo"),
+            scripts: HashMap::new(),
+            capabilities: BTreeSet::new(),
+            publisher: Publisher::Unsigned,
+        }
+    }
+
+    #[test]
+    fn test_package_round_trip() {
+        let path = std::env::temp_dir().join(format!("lpak_test_{}.lpack", uuid::Uuid::new_v4()));
+        sample().save(&path, "secret", None).unwrap();
+
+        let data = fs::read(&path).unwrap();
+        assert_eq!(u32::from_le_bytes([data[8], data[9], data[10], data[11]]), VERSION);
+        let trust = TrustStore::default();
+        let package = GamePackage::load(&path, "secret", &trust).unwrap();
+        assert_eq!(package.name, "demo");
+        assert_eq!(package.publisher, Publisher::Unsigned);
+        assert!(GamePackage::load(&path, "wrong", &trust).is_err());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_signed_package() {
+        let path = std::env::temp_dir().join(format!("lpak_test_{}.lpack", uuid::Uuid::new_v4()));
+        let key = PublisherKey::generate("Legna");
+        sample().save(&path, "", Some(&key)).unwrap();
+
+        let mut trust = TrustStore::default();
+        assert!(matches!(GamePackage::inspect(&path, &trust).unwrap(), Publisher::Untrusted { .. }));
+        trust.add("Legna Studio", &key.public_key_hex()).unwrap();
+        assert_eq!(
+            GamePackage::load(&path, "", &trust).unwrap().publisher,
+            Publisher::Trusted { name: "Legna Studio".into() }
+        );
+
+        // 篡改任意字节都会导致签名验证失败
+        let mut data = fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let err = GamePackage::load_from_bytes(&data, "", &trust).unwrap_err();
+        assert!(err.to_string().contains("签名无效"));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_load_v1_package() {
+        let (key, iv) = GamePackage::legacy_key_iv();
+        let json = serde_json::to_string(&sample()).unwrap();
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&crypto::legacy_encrypt(json.as_bytes(), key, iv));
+
+        let package = GamePackage::load_from_bytes(&data, "", &TrustStore::default()).unwrap();
+        assert_eq!(package.name, "demo");
+        assert_eq!(package.publisher, Publisher::Unsigned);
+    }
+}
@@ -0,0 +1,171 @@
+//! 可复现的随机数 - 每个 Block 独立的确定性随机数流
+//!
+//! 工作流的 `seed` 保存在 .L 中，与 Block UUID 混合得到该 Block 的初始状态。
+//! 脚本环境中的 `math.random` / `math.randomseed` 被替换为只作用于本 Block 的版本，
+//! 同一种子下的结果与执行顺序、其他 Block 是否取随机数无关。
+
+use mlua::{Lua, Table, Value as LuaValue};
+use std::cell::Cell;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// SplitMix64 混合函数
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// 由工作流种子和 Block UUID 得到随机数流的初始状态
+fn stream_seed(seed: u64, block_id: Uuid) -> u64 {
+    let (high, low) = block_id.as_u64_pair();
+    mix(mix(seed ^ high) ^ low)
+}
+
+/// Block 的随机数流（SplitMix64，克隆后共享同一状态）
+#[derive(Debug, Clone)]
+pub struct BlockRng {
+    block_id: Uuid,
+    /// 工作流种子对应的初始状态，`math.randomseed()` 无参数时回到这里
+    initial: Rc<Cell<u64>>,
+    state: Rc<Cell<u64>>,
+}
+
+impl BlockRng {
+    pub fn new(seed: u64, block_id: Uuid) -> Self {
+        let initial = stream_seed(seed, block_id);
+        Self {
+            block_id,
+            initial: Rc::new(Cell::new(initial)),
+            state: Rc::new(Cell::new(initial)),
+        }
+    }
+
+    /// 按新的工作流种子回到初始状态
+    pub fn reseed(&self, seed: u64) {
+        self.initial.set(stream_seed(seed, self.block_id));
+        self.restart();
+    }
+
+    /// 回到当前种子的初始状态
+    pub fn restart(&self) {
+        self.state.set(self.initial.get());
+    }
+
+    /// 当前状态（用于保存执行历史）
+    pub fn position(&self) -> u64 {
+        self.state.get()
+    }
+
+    /// 回到之前保存的状态
+    pub fn seek(&self, position: u64) {
+        self.state.set(position);
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.state.set(state);
+        mix(state)
+    }
+
+    /// [0, 1) 内的浮点数
+    pub fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// [low, high] 内的整数（要求 low <= high）
+    pub fn range(&self, low: i64, high: i64) -> i64 {
+        let span = high.wrapping_sub(low) as u64;
+        if span == u64::MAX {
+            return self.next_u64() as i64;
+        }
+        low.wrapping_add((self.next_u64() % (span + 1)) as i64)
+    }
+
+    /// 在 Block 环境中安装 math 代理表：random / randomseed 使用本随机数流，其余函数来自全局 math
+    pub fn install(&self, lua: &Lua, env: &Table) -> mlua::Result<()> {
+        let math = lua.create_table()?;
+        let meta = lua.create_table()?;
+        meta.set("__index", lua.globals().get::<LuaValue>("math")?)?;
+        math.set_metatable(Some(meta));
+
+        let rng = self.clone();
+        math.set(
+            "random",
+            lua.create_function(move |_, (m, n): (Option<i64>, Option<i64>)| {
+                let (low, high) = match (m, n) {
+                    (None, _) => return Ok(LuaValue::Number(rng.next_f64())),
+                    (Some(0), None) => return Ok(LuaValue::Integer(rng.next_u64() as i64)),
+                    (Some(m), None) => (1, m),
+                    (Some(m), Some(n)) => (m, n),
+                };
+                if low > high {
+                    return Err(mlua::Error::runtime("bad argument to 'random' (interval is empty)"));
+                }
+                Ok(LuaValue::Integer(rng.range(low, high)))
+            })?,
+        )?;
+
+        let rng = self.clone();
+        math.set(
+            "randomseed",
+            lua.create_function(move |_, seed: Option<i64>| {
+                match seed {
+                    Some(seed) => rng.state.set(stream_seed(seed as u64, rng.block_id)),
+                    None => rng.restart(),
+                }
+                Ok(())
+            })?,
+        )?;
+
+        env.set("math", math)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rolls(rng: &BlockRng) -> Vec<i64> {
+        (0..8).map(|_| rng.range(1, 100)).collect()
+    }
+
+    #[test]
+    fn test_streams_are_reproducible_and_independent() {
+        let a = Uuid::from_u128(1);
+        let b = Uuid::from_u128(2);
+
+        let first = rolls(&BlockRng::new(42, a));
+        assert_eq!(first, rolls(&BlockRng::new(42, a)));
+        assert_ne!(first, rolls(&BlockRng::new(42, b)));
+        assert_ne!(first, rolls(&BlockRng::new(7, a)));
+        assert!(first.iter().all(|r| (1..=100).contains(r)));
+
+        let rng = BlockRng::new(7, a);
+        rng.reseed(42);
+        assert_eq!(first, rolls(&rng));
+    }
+
+    #[test]
+    fn test_lua_math_random_uses_block_stream() {
+        let lua = Lua::new();
+        let env = lua.create_table().unwrap();
+        let meta = lua.create_table().unwrap();
+        meta.set("__index", lua.globals()).unwrap();
+        env.set_metatable(Some(meta));
+        BlockRng::new(42, Uuid::from_u128(1)).install(&lua, &env).unwrap();
+
+        let roll = |code: &str| -> mlua::Result<Vec<i64>> {
+            lua.load(code).set_environment(env.clone()).eval()
+        };
+        let script = "local r = {} for i = 1, 8 do r[i] = math.random(100) end return r";
+        let first = roll(&format!("math.randomseed() {}", script)).unwrap();
+        assert_eq!(first, roll(&format!("math.randomseed() {}", script)).unwrap());
+        assert_eq!(first, rolls(&BlockRng::new(42, Uuid::from_u128(1))));
+
+        let x: f64 = lua.load("return math.random()").set_environment(env.clone()).eval().unwrap();
+        assert!((0.0..1.0).contains(&x));
+        assert_eq!(roll("return { math.floor(2.5) }").unwrap(), vec![2]);
+        assert!(roll("return { math.random(5, 1) }").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! 发布者签名
+//!
+//! 游戏包可用发布者的 Ed25519 密钥签名，播放器根据本地信任列表验证来源。
+//!
+//! 密钥文件 (JSON): { "name": "发布者", "secret_key": "<64位hex>" }
+//! 信任列表 (JSON): { "publishers": [ { "name": "发布者", "public_key": "<64位hex>" } ] }
+//! 信任列表默认位于 ~/.legna/trusted_publishers.json，可用环境变量 LEGNA_TRUST_STORE 覆盖。
+
+use aes_gcm::aead::OsRng;
+use anyhow::{anyhow, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 公钥长度
+pub const PUBLIC_KEY_LEN: usize = 32;
+/// 签名长度
+pub const SIGNATURE_LEN: usize = 64;
+/// 信任列表路径环境变量
+const TRUST_STORE_ENV: &str = "LEGNA_TRUST_STORE";
+
+/// 发布者密钥对
+pub struct PublisherKey {
+    pub name: String,
+    signing_key: SigningKey,
+}
+
+/// 密钥文件内容
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    name: String,
+    secret_key: String,
+}
+
+impl PublisherKey {
+    /// 生成新的密钥对
+    pub fn generate(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            signing_key: SigningKey::generate(&mut OsRng),
+        }
+    }
+
+    /// 从密钥文件加载
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("读取密钥文件失败 {}: {}", path.display(), e))?;
+        let file: KeyFile = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("密钥文件格式错误: {}", e))?;
+        let secret: [u8; 32] = hex::decode(&file.secret_key)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| anyhow!("密钥文件中的私钥无效"))?;
+
+        Ok(Self {
+            name: file.name,
+            signing_key: SigningKey::from_bytes(&secret),
+        })
+    }
+
+    /// 保存到密钥文件（Unix 下仅所有者可读）
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = KeyFile {
+            name: self.name.clone(),
+            secret_key: hex::encode(self.signing_key.to_bytes()),
+        };
+        fs::write(path, serde_json::to_string_pretty(&file)?)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// 公钥
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_LEN] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    /// 公钥（hex）
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.public_key())
+    }
+
+    /// 签名
+    pub fn sign(&self, message: &[u8]) -> [u8; SIGNATURE_LEN] {
+        self.signing_key.sign(message).to_bytes()
+    }
+}
+
+/// 验证签名
+pub fn verify(message: &[u8], public_key: &[u8; PUBLIC_KEY_LEN], signature: &[u8; SIGNATURE_LEN]) -> bool {
+    VerifyingKey::from_bytes(public_key)
+        .map(|key| key.verify(message, &Signature::from_bytes(signature)).is_ok())
+        .unwrap_or(false)
+}
+
+/// 公钥指纹（前 8 字节）
+pub fn fingerprint(public_key: &[u8; PUBLIC_KEY_LEN]) -> String {
+    public_key[..8]
+        .chunks(2)
+        .map(hex::encode)
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// 受信任的发布者
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedPublisher {
+    pub name: String,
+    pub public_key: String,
+}
+
+/// 本地信任列表
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrustStore {
+    #[serde(default)]
+    pub publishers: Vec<TrustedPublisher>,
+}
+
+impl TrustStore {
+    /// 默认路径：LEGNA_TRUST_STORE > ~/.legna/trusted_publishers.json
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os(TRUST_STORE_ENV) {
+            return PathBuf::from(path);
+        }
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        home.join(".legna").join("trusted_publishers.json")
+    }
+
+    /// 从文件加载（文件不存在视为空列表）
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| anyhow!("信任列表格式错误 {}: {}", path.display(), e))
+    }
+
+    /// 加载默认信任列表（失败时记录警告并返回空列表）
+    pub fn load_default() -> Self {
+        let path = Self::default_path();
+        Self::load(&path).unwrap_or_else(|e| {
+            log::warn!("加载信任列表失败: {}", e);
+            Self::default()
+        })
+    }
+
+    /// 保存到文件
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// 添加发布者（同一公钥只保留一条）
+    pub fn add(&mut self, name: &str, public_key: &str) -> Result<()> {
+        let key = parse_public_key(public_key)?;
+        let public_key = hex::encode(key);
+        self.publishers.retain(|p| p.public_key != public_key);
+        self.publishers.push(TrustedPublisher { name: name.to_string(), public_key });
+        Ok(())
+    }
+
+    /// 按名称或公钥移除，返回移除数量
+    pub fn remove(&mut self, name_or_key: &str) -> usize {
+        let key = name_or_key.to_lowercase();
+        let before = self.publishers.len();
+        self.publishers.retain(|p| p.name != name_or_key && p.public_key != key);
+        before - self.publishers.len()
+    }
+
+    /// 查找公钥对应的受信任发布者
+    pub fn find(&self, public_key: &[u8; PUBLIC_KEY_LEN]) -> Option<&TrustedPublisher> {
+        let key = hex::encode(public_key);
+        self.publishers.iter().find(|p| p.public_key == key)
+    }
+}
+
+/// 解析 hex 公钥
+pub fn parse_public_key(text: &str) -> Result<[u8; PUBLIC_KEY_LEN]> {
+    let key: [u8; PUBLIC_KEY_LEN] = hex::decode(text.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("无效的公钥: {}", text))?;
+    VerifyingKey::from_bytes(&key).map_err(|_| anyhow!("无效的公钥: {}", text))?;
+    Ok(key)
+}
+
+/// 游戏包的签名验证结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Publisher {
+    /// 未签名
+    #[default]
+    Unsigned,
+    /// 签名有效且公钥在信任列表中
+    Trusted { name: String },
+    /// 签名有效但公钥不在信任列表中
+    Untrusted { claimed_name: String, fingerprint: String },
+}
+
+impl Publisher {
+    /// 根据签名公钥和信任列表判定发布者
+    pub fn identify(claimed_name: &str, public_key: &[u8; PUBLIC_KEY_LEN], trust: &TrustStore) -> Self {
+        match trust.find(public_key) {
+            Some(trusted) => Self::Trusted { name: trusted.name.clone() },
+            None => Self::Untrusted {
+                claimed_name: claimed_name.to_string(),
+                fingerprint: fingerprint(public_key),
+            },
+        }
+    }
+
+    /// 显示文本
+    pub fn label(&self) -> String {
+        match self {
+            Self::Unsigned => "未签名".to_string(),
+            Self::Trusted { name } => format!("发布者: {}", name),
+            Self::Untrusted { claimed_name, fingerprint } => {
+                format!("未受信任: {} ({})", claimed_name, fingerprint)
+            }
+        }
+    }
+
+    pub fn is_trusted(&self) -> bool {
+        matches!(self, Self::Trusted { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_trust() {
+        let key = PublisherKey::generate("Legna");
+        let signature = key.sign(b"payload");
+        assert!(verify(b"payload", &key.public_key(), &signature));
+        assert!(!verify(b"tampered", &key.public_key(), &signature));
+
+        let mut trust = TrustStore::default();
+        assert!(matches!(Publisher::identify("Legna", &key.public_key(), &trust), Publisher::Untrusted { .. }));
+
+        trust.add("Legna Studio", &key.public_key_hex()).unwrap();
+        assert_eq!(
+            Publisher::identify("冒充者", &key.public_key(), &trust),
+            Publisher::Trusted { name: "Legna Studio".into() }
+        );
+
+        assert_eq!(trust.remove("Legna Studio"), 1);
+        assert!(trust.find(&key.public_key()).is_none());
+        assert!(trust.add("bad", "1234").is_err());
+    }
+}
@@ -3,24 +3,38 @@
 //!
 //! 两种保护机制：
 //! 1. **打开密码**：存储在Workflow.password_hash中，打开时需要验证
-//! 2. **加密**：.LZ文件使用"Legna"固定密钥AES加密，保护文件内容不被查看
+//! 2. **加密**：.LZ文件使用口令派生的密钥加密（Argon2id + AES-256-GCM），保护文件内容不被查看
+//!
+//! .LZ 文件结构:
+//! [8字节魔数: "LEGNALZC"]
+//! [4字节版本: 0x0002]
+//! [N字节: 口令加密容器，见 crypto 模块]
+//!
+//! 旧版 .LZ（魔数 "LEGNA_LZ"，固定密钥 AES-128-CBC）仍可读取，保存时升级为新格式。
+//!
+//! 明文 JSON 顶层带 format_version，加载时经 migration 模块升级旧版本。
 //!
 //! 文件命名：
 //!   - xxx.L - 明文版本（可设置打开密码）
-//!   - xxx.LZ - 加密版本（固定密钥加密 + 可设置打开密码）
+//!   - xxx.LZ - 加密版本（口令加密 + 可设置打开密码）
 //!   - xxx.dist.L / xxx.dist.LZ - 可分发版本（只读）
 
-use super::Workflow;
-use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use super::crypto;
+use super::migration;
+use super::{Workflow, MAX_SUBGRAPH_DEPTH};
 use anyhow::{anyhow, Result};
 use std::fs;
 use std::path::Path;
+use uuid::Uuid;
 
-/// 固定加密密钥
-const ENCRYPTION_KEY: &str = "Legna";
-
-type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
-type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+/// 加密容器魔数
+const MAGIC: &[u8; 8] = b"LEGNALZC";
+/// 加密容器版本
+const VERSION: u32 = 2;
+/// 旧版魔数（v1）
+const LEGACY_MAGIC: &[u8; 8] = b"LEGNA_LZ";
+/// 旧版固定加密密钥
+const LEGACY_KEY: &str = "Legna";
 
 /// 蓝图文件格式
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -76,8 +90,8 @@ impl BlueprintStorage {
         Ok((editable_path, dist_path))
     }
 
-    /// 内部保存函数（加密使用固定密钥，不需要用户密码）
-    fn save_internal(workflow: &Workflow, path: &Path, _password: Option<&str>) -> Result<()> {
+    /// 内部保存函数（.LZ 需要口令）
+    fn save_internal(workflow: &Workflow, path: &Path, password: Option<&str>) -> Result<()> {
         let ext = path.extension()
             .and_then(|e| e.to_str())
             .unwrap_or("L");
@@ -85,15 +99,15 @@ impl BlueprintStorage {
         let format = BlueprintFormat::from_extension(ext)
             .unwrap_or(BlueprintFormat::Legna);
 
-        let json = serde_json::to_string_pretty(workflow)?;
+        let json = serde_json::to_string_pretty(&migration::to_value(workflow)?)?;
 
         match format {
             BlueprintFormat::Legna => {
                 fs::write(path, json)?;
             }
             BlueprintFormat::LegnaEncrypted => {
-                // 使用固定密钥加密
-                let encrypted = Self::encrypt(&json)?;
+                let password = password.ok_or_else(|| anyhow!("加密文件需要密码"))?;
+                let encrypted = Self::encrypt(&json, password)?;
                 fs::write(path, encrypted)?;
             }
         }
@@ -101,8 +115,13 @@ impl BlueprintStorage {
         Ok(())
     }
 
-    /// 从文件加载蓝图（加密文件自动解密，打开密码需要调用者验证）
-    pub fn load(path: &Path, _password: Option<&str>) -> Result<Workflow> {
+    /// 从文件加载蓝图（加密文件用口令解密，打开密码需要调用者验证）
+    pub fn load(path: &Path, password: Option<&str>) -> Result<Workflow> {
+        Self::load_nested(path, password, 0)
+    }
+
+    /// 加载蓝图并读取子图引用的外部文件（depth 为引用层数）
+    fn load_nested(path: &Path, password: Option<&str>, depth: usize) -> Result<Workflow> {
         let ext = path.extension()
             .and_then(|e| e.to_str())
             .unwrap_or("L");
@@ -117,61 +136,96 @@ impl BlueprintStorage {
                 String::from_utf8(content)?
             }
             BlueprintFormat::LegnaEncrypted => {
-                // 使用固定密钥解密
-                Self::decrypt(&content)?
+                Self::decrypt(&content, password)?
             }
         };
 
-        let workflow: Workflow = serde_json::from_str(&json)?;
+        let mut workflow = migration::from_value(serde_json::from_str(&json)?)?;
+        Self::resolve_subgraph_sources(&mut workflow, path, depth);
         Ok(workflow)
     }
 
-    /// AES-128-CBC加密（使用固定密钥"Legna"）
-    fn encrypt(data: &str) -> Result<Vec<u8>> {
-        let (key, iv) = Self::derive_key_iv();
-
-        let data_bytes = data.as_bytes();
-        // 计算需要的缓冲区大小（PKCS7填充）
-        let block_size = 16;
-        let padded_len = (data_bytes.len() / block_size + 1) * block_size;
-        let mut buf = vec![0u8; padded_len];
-        buf[..data_bytes.len()].copy_from_slice(data_bytes);
-
-        let ct = Aes128CbcEnc::new(&key.into(), &iv.into())
-            .encrypt_padded_mut::<Pkcs7>(&mut buf, data_bytes.len())
-            .map_err(|_| anyhow!("加密失败"))?;
+    /// 读取引用外部 .L 文件的子图内容（失败时保留空子图并记录警告）
+    fn resolve_subgraph_sources(workflow: &mut Workflow, path: &Path, depth: usize) {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut sources: Vec<(Uuid, String)> = workflow.subgraphs.values()
+            .filter_map(|s| Some((s.id, s.source.clone()?)))
+            .collect();
+        sources.sort();
+
+        for (id, source) in sources {
+            let loaded = if depth >= MAX_SUBGRAPH_DEPTH {
+                Err(anyhow!("子图引用超过 {} 层", MAX_SUBGRAPH_DEPTH))
+            } else {
+                Self::load_nested(&dir.join(&source), None, depth + 1).and_then(|referenced| {
+                    if referenced.has_password() {
+                        Err(anyhow!("引用的文件有打开密码"))
+                    } else {
+                        Ok(referenced)
+                    }
+                })
+            };
+
+            match loaded {
+                Ok(referenced) => {
+                    for (nested_id, nested) in referenced.subgraphs {
+                        workflow.subgraphs.entry(nested_id).or_insert(nested);
+                    }
+                    if let Some(subgraph) = workflow.subgraphs.get_mut(&id) {
+                        subgraph.blocks = referenced.blocks;
+                        subgraph.connections = referenced.connections;
+                    }
+                }
+                Err(e) => log::warn!("加载子图 {} 失败: {}", source, e),
+            }
+        }
+    }
 
-        // 添加魔数 + 加密数据
-        let mut result = Vec::with_capacity(ct.len() + 8);
-        result.extend_from_slice(b"LEGNA_LZ");
-        result.extend_from_slice(ct);
+    /// 口令加密
+    fn encrypt(data: &str, password: &str) -> Result<Vec<u8>> {
+        let header = Self::header();
+        let sealed = crypto::seal(data.as_bytes(), password, &header)?;
 
+        let mut result = Vec::with_capacity(header.len() + sealed.len());
+        result.extend_from_slice(&header);
+        result.extend_from_slice(&sealed);
         Ok(result)
     }
 
-    /// AES-128-CBC解密（使用固定密钥"Legna"）
-    fn decrypt(data: &[u8]) -> Result<String> {
-        // 检查魔数
-        if data.len() < 8 || &data[0..8] != b"LEGNA_LZ" {
+    /// 解密（自动识别旧版固定密钥格式）
+    fn decrypt(data: &[u8], password: Option<&str>) -> Result<String> {
+        if data.len() >= 8 && &data[0..8] == LEGACY_MAGIC {
+            let (key, iv) = Self::legacy_key_iv();
+            let pt = crypto::legacy_decrypt(&data[8..], key, iv)
+                .ok_or_else(|| anyhow!("解密失败：文件损坏"))?;
+            return String::from_utf8(pt).map_err(|_| anyhow!("解密失败：数据格式错误"));
+        }
+
+        if data.len() < 12 || &data[0..8] != MAGIC {
             return Err(anyhow!("不是有效的LZ加密文件"));
         }
 
-        let (key, iv) = Self::derive_key_iv();
-        let encrypted = &data[8..];
+        let version = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
+        if version > VERSION {
+            return Err(anyhow!("LZ文件版本过新，请更新程序"));
+        }
 
-        let mut buf = encrypted.to_vec();
-        let pt = Aes128CbcDec::new(&key.into(), &iv.into())
-            .decrypt_padded_mut::<Pkcs7>(&mut buf)
-            .map_err(|_| anyhow!("解密失败：文件损坏"))?;
+        let password = password.ok_or_else(|| anyhow!("加密文件需要密码"))?;
+        let pt = crypto::open(&data[12..], password, &data[..12])?;
+        String::from_utf8(pt).map_err(|_| anyhow!("解密失败：数据格式错误"))
+    }
 
-        String::from_utf8(pt.to_vec())
-            .map_err(|_| anyhow!("解密失败：数据格式错误"))
+    /// 文件头：魔数 + 版本
+    fn header() -> Vec<u8> {
+        let mut header = Vec::with_capacity(12);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header
     }
 
-    /// 从固定密钥"Legna"派生AES-128密钥和IV
-    fn derive_key_iv() -> ([u8; 16], [u8; 16]) {
-        // 简单的密钥派生：重复密钥填充到16字节
-        let key_bytes = ENCRYPTION_KEY.as_bytes();
+    /// 旧版密钥派生：重复"Legna"填充到16字节
+    fn legacy_key_iv() -> ([u8; 16], [u8; 16]) {
+        let key_bytes = LEGACY_KEY.as_bytes();
         let mut key = [0u8; 16];
         let mut iv = [0u8; 16];
 
@@ -193,10 +247,20 @@ mod tests {
     fn test_encrypt_decrypt() {
         let data = "Hello, World!";
 
-        let encrypted = BlueprintStorage::encrypt(data).unwrap();
-        let decrypted = BlueprintStorage::decrypt(&encrypted).unwrap();
+        let encrypted = BlueprintStorage::encrypt(data, "secret").unwrap();
+        assert_eq!(&encrypted[0..8], MAGIC);
+        assert_eq!(BlueprintStorage::decrypt(&encrypted, Some("secret")).unwrap(), data);
+        assert!(BlueprintStorage::decrypt(&encrypted, Some("wrong")).is_err());
+        assert!(BlueprintStorage::decrypt(&encrypted, None).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_legacy() {
+        let (key, iv) = BlueprintStorage::legacy_key_iv();
+        let mut legacy = LEGACY_MAGIC.to_vec();
+        legacy.extend_from_slice(&crypto::legacy_encrypt(b"{\"old\":true}", key, iv));
 
-        assert_eq!(data, decrypted);
+        // 旧版文件不需要口令
+        assert_eq!(BlueprintStorage::decrypt(&legacy, None).unwrap(), "{\"old\":true}");
     }
 }
-
@@ -0,0 +1,551 @@
+//! 子图 - 可复用的工作流片段
+//!
+//! 子图定义保存在 `Workflow.subgraphs` 中，或通过 `source` 引用另一个 .L 文件。
+//! 子图实例是 script_id 为 "subgraph:<UUID>" 的 Block，端口来自子图内的
+//! `graph.input` / `graph.output` Block（端口名取自 `port` 属性，按位置从上到下排列），
+//! 执行时在 WorkflowExecutor 中作为嵌套作用域运行。
+//! 游戏包中的子图展开为普通 Block，播放器不需要支持子图。
+
+use super::{Block, Connection, Vec2, Workflow};
+use crate::script::{BlockDefinition, BlockMeta, DataType, PortDefinition, ScriptRegistry, Value, WidgetType};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+
+/// 子图实例 script_id 前缀
+pub const SUBGRAPH_PREFIX: &str = "subgraph:";
+/// 子图输入 Block
+pub const GRAPH_INPUT: &str = "graph.input";
+/// 子图输出 Block
+pub const GRAPH_OUTPUT: &str = "graph.output";
+/// 子图端口 Block 的值端口
+pub const VALUE_PORT: &str = "value";
+/// 子图端口名属性
+const PORT_PROPERTY: &str = "port";
+/// 最大嵌套层数（防止子图引用自身）
+pub const MAX_SUBGRAPH_DEPTH: usize = 16;
+/// 子图实例的分类和颜色
+const CATEGORY: &str = "子图";
+const COLOR: &str = "#26A69A";
+
+/// 子图定义
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subgraph {
+    pub id: Uuid,
+    pub name: String,
+    /// 引用的外部 .L 文件（相对于当前文件），内部 Block 在加载时从该文件读取，不随当前文件保存
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, serialize_with = "super::ordered::map")]
+    pub blocks: HashMap<Uuid, Block>,
+    #[serde(default, serialize_with = "super::ordered::map")]
+    pub connections: HashMap<Uuid, Connection>,
+}
+
+impl Subgraph {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            source: None,
+            blocks: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    /// 实例 Block 的 script_id
+    pub fn script_id(&self) -> String {
+        format!("{}{}", SUBGRAPH_PREFIX, self.id)
+    }
+
+    /// 子图内容构成的工作流
+    pub fn to_workflow(&self) -> Workflow {
+        let mut workflow = Workflow::new(self.name.clone());
+        workflow.blocks = self.blocks.clone();
+        workflow.connections = self.connections.clone();
+        workflow
+    }
+
+    /// 端口 Block（按位置从上到下，同名端口只取第一个）
+    fn ports(&self, script_id: &str) -> Vec<(&Block, String)> {
+        let mut ports: Vec<_> = self.blocks.values()
+            .filter(|b| b.script_id == script_id)
+            .map(|b| (b, port_name(b)))
+            .collect();
+        ports.sort_by(|(a, _), (b, _)| {
+            a.position.y.total_cmp(&b.position.y)
+                .then(a.position.x.total_cmp(&b.position.x))
+                .then(a.id.cmp(&b.id))
+        });
+        let mut seen = HashSet::new();
+        ports.retain(|(_, port)| seen.insert(port.clone()));
+        ports
+    }
+
+    /// 端口类型：连接到内部 event 端口的为 event，其余为 any
+    fn port_type(&self, block: &Block, registry: &ScriptRegistry) -> DataType {
+        let is_event_port = |block_id: &Uuid, port: &str, output: bool| {
+            let definition = registry.get(&self.blocks.get(block_id)?.script_id)?;
+            let ports = if output { &definition.outputs } else { &definition.inputs };
+            ports.iter().find(|p| p.id == port).map(|p| p.data_type == DataType::Event)
+        };
+        let is_event = self.connections.values().any(|c| {
+            let event = if block.script_id == GRAPH_INPUT && c.from_block == block.id {
+                is_event_port(&c.to_block, &c.to_port, false)
+            } else if block.script_id == GRAPH_OUTPUT && c.to_block == block.id {
+                is_event_port(&c.from_block, &c.from_port, true)
+            } else {
+                None
+            };
+            event == Some(true)
+        });
+        if is_event { DataType::Event } else { DataType::Any }
+    }
+
+    /// 实例 Block 的定义
+    fn definition(&self, always_run: bool, registry: &ScriptRegistry) -> BlockDefinition {
+        let port = |(block, id): (&Block, String)| PortDefinition {
+            name: block.custom_name.clone().filter(|n| !n.is_empty()).unwrap_or_else(|| id.clone()),
+            data_type: self.port_type(block, registry),
+            id,
+            default: Value::Nil,
+            description: String::new(),
+            required: false,
+            multiple: false,
+            element_type: None,
+            min: None,
+            max: None,
+            out_of_range: Default::default(),
+            coerce: Default::default(),
+        };
+
+        BlockDefinition {
+            meta: BlockMeta {
+                id: self.script_id(),
+                name: self.name.clone(),
+                version: "1.0.0".to_string(),
+                category: CATEGORY.to_string(),
+                description: format!("子图（{} 个Block）", self.blocks.len()),
+                icon: "📦".to_string(),
+                color: COLOR.to_string(),
+                widget: WidgetType::None,
+                placeholder: String::new(),
+                options: Vec::new(),
+                hideable: false,
+                always_run,
+            },
+            inputs: self.ports(GRAPH_INPUT).into_iter().map(port).collect(),
+            outputs: self.ports(GRAPH_OUTPUT).into_iter().map(port).collect(),
+            properties: Vec::new(),
+            script_path: String::new(),
+        }
+    }
+
+    /// 为实例创建独立的工作流，Block 和连线的 UUID 由实例派生，同一子图的多个实例互不影响
+    pub fn instantiate(&self, instance_id: Uuid, library: &HashMap<Uuid, Subgraph>) -> Workflow {
+        let scoped = |id: &Uuid| Uuid::new_v5(&instance_id, id.as_bytes());
+        let mut workflow = Workflow::new(self.name.clone());
+        for block in self.blocks.values() {
+            let mut block = block.clone();
+            block.id = scoped(&block.id);
+            block.group_id = None;
+            workflow.blocks.insert(block.id, block);
+        }
+        for connection in self.connections.values() {
+            let mut connection = connection.clone();
+            connection.id = scoped(&connection.id);
+            connection.from_block = scoped(&connection.from_block);
+            connection.to_block = scoped(&connection.to_block);
+            workflow.connections.insert(connection.id, connection);
+        }
+        workflow.subgraphs = library.clone();
+        workflow.update_execution_order();
+        workflow
+    }
+}
+
+/// 子图端口 Block 的端口名
+pub fn port_name(block: &Block) -> String {
+    match block.properties.get(PORT_PROPERTY) {
+        Some(Value::String(port)) if !port.is_empty() => port.clone(),
+        _ => block.id.simple().to_string()[..8].to_string(),
+    }
+}
+
+/// 子图实例引用的子图
+pub fn instance_of(script_id: &str) -> Option<Uuid> {
+    script_id.strip_prefix(SUBGRAPH_PREFIX)?.parse().ok()
+}
+
+/// 是否为子图输入/输出 Block
+pub fn is_port_block(script_id: &str) -> bool {
+    script_id == GRAPH_INPUT || script_id == GRAPH_OUTPUT
+}
+
+/// 生成不重复的端口名
+fn unique_port(base: &str, used: &mut HashSet<String>) -> String {
+    let mut name = base.to_string();
+    let mut n = 2;
+    while !used.insert(name.clone()) {
+        name = format!("{}_{}", base, n);
+        n += 1;
+    }
+    name
+}
+
+impl Workflow {
+    /// 子图内部是否有每个 tick 执行的 Block
+    fn subgraph_always_run(&self, subgraph: &Subgraph, registry: &ScriptRegistry, depth: usize) -> bool {
+        depth < MAX_SUBGRAPH_DEPTH
+            && subgraph.blocks.values().any(|b| match instance_of(&b.script_id) {
+                Some(id) => self.subgraphs.get(&id).is_some_and(|s| self.subgraph_always_run(s, registry, depth + 1)),
+                None => registry.get(&b.script_id).is_some_and(|d| d.meta.always_run),
+            })
+    }
+
+    /// 子图实例 Block 的定义
+    pub fn subgraph_definition(&self, subgraph_id: Uuid, registry: &ScriptRegistry) -> Option<BlockDefinition> {
+        let subgraph = self.subgraphs.get(&subgraph_id)?;
+        Some(subgraph.definition(self.subgraph_always_run(subgraph, registry, 0), registry))
+    }
+
+    /// 将子图定义注册到脚本注册表（子图实例 Block 的端口由此获得）
+    pub fn register_subgraphs(&self, registry: &mut ScriptRegistry) {
+        let definitions = self.subgraphs.keys()
+            .filter_map(|id| self.subgraph_definition(*id, registry))
+            .collect();
+        registry.set_subgraphs(definitions);
+    }
+
+    /// 将选中的 Block 折叠为子图，返回子图实例 Block 的 UUID
+    /// 跨越选区的连线改为经过子图输入/输出 Block，外部一侧接到实例的对应端口
+    pub fn collapse_to_subgraph(&mut self, ids: &HashSet<Uuid>, name: &str, registry: &ScriptRegistry) -> Result<Uuid> {
+        let input_def = registry.get(GRAPH_INPUT).ok_or_else(|| anyhow!("找不到Block定义: {}", GRAPH_INPUT))?;
+        let output_def = registry.get(GRAPH_OUTPUT).ok_or_else(|| anyhow!("找不到Block定义: {}", GRAPH_OUTPUT))?;
+
+        let mut selected: Vec<Uuid> = ids.iter().copied().filter(|id| self.blocks.contains_key(id)).collect();
+        if selected.is_empty() {
+            return Err(anyhow!("没有选中的Block"));
+        }
+        selected.sort();
+
+        // 选区边界
+        let mut min = Vec2::new(f32::MAX, f32::MAX);
+        let mut max = Vec2::new(f32::MIN, f32::MIN);
+        for id in &selected {
+            let block = &self.blocks[id];
+            min.x = min.x.min(block.position.x);
+            min.y = min.y.min(block.position.y);
+            max.x = max.x.max(block.position.x + block.size.x);
+            max.y = max.y.max(block.position.y + block.size.y);
+        }
+
+        let mut subgraph = Subgraph::new(name);
+        for id in &selected {
+            if let Some(mut block) = self.blocks.remove(id) {
+                block.selected = false;
+                block.group_id = None;
+                subgraph.blocks.insert(*id, block);
+            }
+        }
+        for group in self.groups.values_mut() {
+            group.blocks.retain(|id| !subgraph.blocks.contains_key(id));
+        }
+
+        let instance_id = Uuid::new_v4();
+        let port_block = |definition: &BlockDefinition, position: Vec2, port: &str| {
+            let mut block = Block::new(definition, position);
+            block.properties.insert(PORT_PROPERTY.to_string(), Value::String(port.to_string()));
+            block
+        };
+
+        // 外部来源（是否为反馈连线） / 内部来源 → (端口名, 端口 Block)
+        let mut inputs: BTreeMap<(Uuid, String, bool), (String, Uuid)> = BTreeMap::new();
+        let mut outputs: BTreeMap<(Uuid, String), (String, Uuid)> = BTreeMap::new();
+        let (mut input_names, mut output_names) = (HashSet::new(), HashSet::new());
+        let mut outer = Vec::new();
+
+        let mut connections: Vec<Connection> = self.connections.values().cloned().collect();
+        connections.sort_by_key(|c| c.id);
+        for conn in connections {
+            let from_inside = subgraph.blocks.contains_key(&conn.from_block);
+            let to_inside = subgraph.blocks.contains_key(&conn.to_block);
+            if !from_inside && !to_inside {
+                continue;
+            }
+            self.connections.remove(&conn.id);
+
+            match (from_inside, to_inside) {
+                (true, true) => {
+                    subgraph.connections.insert(conn.id, conn);
+                }
+                (false, true) => {
+                    let key = (conn.from_block, conn.from_port.clone(), conn.delayed);
+                    let block_id = match inputs.get(&key) {
+                        Some((_, block_id)) => *block_id,
+                        None => {
+                            let port = unique_port(&conn.to_port, &mut input_names);
+                            let position = Vec2::new(min.x - 200.0, min.y + inputs.len() as f32 * 80.0);
+                            let block = port_block(input_def, position, &port);
+                            let block_id = block.id;
+                            subgraph.blocks.insert(block_id, block);
+                            let mut outside = Connection::new(conn.from_block, conn.from_port.clone(), instance_id, port.clone());
+                            outside.delayed = conn.delayed;
+                            outer.push(outside);
+                            inputs.insert(key, (port, block_id));
+                            block_id
+                        }
+                    };
+                    let inner = Connection::new(block_id, VALUE_PORT.to_string(), conn.to_block, conn.to_port);
+                    subgraph.connections.insert(inner.id, inner);
+                }
+                (true, false) => {
+                    let key = (conn.from_block, conn.from_port.clone());
+                    let port = match outputs.get(&key) {
+                        Some((port, _)) => port.clone(),
+                        None => {
+                            let port = unique_port(&conn.from_port, &mut output_names);
+                            let position = Vec2::new(max.x + 60.0, min.y + outputs.len() as f32 * 80.0);
+                            let block = port_block(output_def, position, &port);
+                            let block_id = block.id;
+                            subgraph.blocks.insert(block_id, block);
+                            let inner = Connection::new(conn.from_block, conn.from_port.clone(), block_id, VALUE_PORT.to_string());
+                            subgraph.connections.insert(inner.id, inner);
+                            outputs.insert(key, (port.clone(), block_id));
+                            port
+                        }
+                    };
+                    let mut outside = Connection::new(instance_id, port, conn.to_block, conn.to_port);
+                    outside.delayed = conn.delayed;
+                    outer.push(outside);
+                }
+                (false, false) => {}
+            }
+        }
+
+        let subgraph_id = subgraph.id;
+        self.subgraphs.insert(subgraph_id, subgraph);
+        let definition = self.subgraph_definition(subgraph_id, registry)
+            .ok_or_else(|| anyhow!("找不到子图: {}", subgraph_id))?;
+
+        let mut instance = Block::new(&definition, min);
+        instance.id = instance_id;
+        self.blocks.insert(instance_id, instance);
+        for conn in outer {
+            self.connections.insert(conn.id, conn);
+        }
+        self.mark_dirty(instance_id);
+        self.update_execution_order();
+        Ok(instance_id)
+    }
+
+    /// 进入子图编辑：返回由子图内容构成的工作流（引用外部文件的子图只读）
+    pub fn subgraph_view(&self, subgraph_id: Uuid) -> Option<Workflow> {
+        let subgraph = self.subgraphs.get(&subgraph_id)?;
+        let mut view = subgraph.to_workflow();
+        view.subgraphs = self.subgraphs.clone();
+        view.readonly = self.readonly || subgraph.source.is_some();
+
+        // 视口对准子图内容的左上角
+        let min_x = view.blocks.values().map(|b| b.position.x).fold(f32::MAX, f32::min);
+        let min_y = view.blocks.values().map(|b| b.position.y).fold(f32::MAX, f32::min);
+        if !view.blocks.is_empty() {
+            view.viewport.offset = Vec2::new(100.0 - min_x, 100.0 - min_y);
+        }
+        view.update_execution_order();
+        Some(view)
+    }
+
+    /// 退出子图编辑：写回编辑后的内容，并标记所有子图实例需要重新执行
+    pub fn apply_subgraph_view(&mut self, subgraph_id: Uuid, mut view: Workflow) {
+        // 视图中可能编辑过更深层的子图
+        self.subgraphs = std::mem::take(&mut view.subgraphs);
+        if let Some(subgraph) = self.subgraphs.get_mut(&subgraph_id) {
+            if subgraph.source.is_none() {
+                view.clear_selection();
+                subgraph.blocks = view.blocks;
+                subgraph.connections = view.connections;
+            }
+        }
+
+        let instances: Vec<Uuid> = self.blocks.values()
+            .filter(|b| instance_of(&b.script_id).is_some())
+            .map(|b| b.id)
+            .collect();
+        for id in instances {
+            self.mark_dirty(id);
+        }
+        self.revision += 1;
+    }
+
+    /// 展开所有子图实例为普通 Block（用于游戏包）
+    pub fn flatten_subgraphs(&self) -> Result<Workflow> {
+        let mut flat = self.clone();
+        for _ in 0..=MAX_SUBGRAPH_DEPTH {
+            let mut instances: Vec<(Uuid, Uuid)> = flat.blocks.values()
+                .filter_map(|b| Some((b.id, instance_of(&b.script_id)?)))
+                .collect();
+            if instances.is_empty() {
+                flat.subgraphs.clear();
+                flat.update_execution_order();
+                return Ok(flat);
+            }
+            instances.sort();
+            for (instance_id, subgraph_id) in instances {
+                flat.inline_subgraph(instance_id, subgraph_id)?;
+            }
+        }
+        Err(anyhow!("子图嵌套超过 {} 层（是否引用了自身？）", MAX_SUBGRAPH_DEPTH))
+    }
+
+    /// 用子图内容替换一个实例：子图输入/输出 Block 去掉，连线直接接到外部
+    fn inline_subgraph(&mut self, instance_id: Uuid, subgraph_id: Uuid) -> Result<()> {
+        let subgraph = self.subgraphs.get(&subgraph_id).ok_or_else(|| anyhow!("找不到子图: {}", subgraph_id))?;
+        let scope = subgraph.instantiate(instance_id, &HashMap::new());
+        let Some(instance) = self.blocks.remove(&instance_id) else {
+            return Ok(());
+        };
+
+        // 实例输入端口的外部来源、实例输出端口的外部目标（含是否为反馈连线）
+        let mut sources: HashMap<String, (Uuid, String, bool)> = HashMap::new();
+        let mut targets: Vec<(String, Uuid, String, bool)> = Vec::new();
+        self.connections.retain(|_, c| {
+            if c.to_block == instance_id {
+                sources.insert(c.to_port.clone(), (c.from_block, c.from_port.clone(), c.delayed));
+                false
+            } else if c.from_block == instance_id {
+                targets.push((c.from_port.clone(), c.to_block, c.to_port.clone(), c.delayed));
+                false
+            } else {
+                true
+            }
+        });
+        targets.sort();
+
+        // 来自子图输入 Block 的连线改由外部来源提供
+        let resolve = |from_block: Uuid, from_port: &str| match scope.blocks.get(&from_block) {
+            Some(block) if block.script_id == GRAPH_INPUT => sources.get(&port_name(block)).cloned(),
+            Some(_) => Some((from_block, from_port.to_string(), false)),
+            None => None,
+        };
+        let is_output = |id: &Uuid| scope.blocks.get(id).is_some_and(|b| b.script_id == GRAPH_OUTPUT);
+
+        let mut output_sources: HashMap<String, (Uuid, String, bool)> = HashMap::new();
+        for conn in scope.connections.values() {
+            let Some((from_block, from_port, delayed)) = resolve(conn.from_block, &conn.from_port) else {
+                continue;
+            };
+            let delayed = delayed || conn.delayed;
+            if is_output(&conn.to_block) {
+                output_sources.insert(port_name(&scope.blocks[&conn.to_block]), (from_block, from_port, delayed));
+            } else {
+                let mut conn = conn.clone();
+                conn.from_block = from_block;
+                conn.from_port = from_port;
+                conn.delayed = delayed;
+                self.connections.insert(conn.id, conn);
+            }
+        }
+        for (port, to_block, to_port, delayed) in targets {
+            if let Some((from_block, from_port, inner_delayed)) = output_sources.get(&port) {
+                let mut conn = Connection::new(*from_block, from_port.clone(), to_block, to_port);
+                conn.delayed = delayed || *inner_delayed;
+                conn.id = Uuid::new_v5(&instance_id, format!("{}>{}.{}", port, to_block, conn.to_port).as_bytes());
+                self.connections.insert(conn.id, conn);
+            }
+        }
+
+        // 内部 Block 按实例位置平移
+        let min_x = scope.blocks.values().map(|b| b.position.x).fold(f32::MAX, f32::min);
+        let min_y = scope.blocks.values().map(|b| b.position.y).fold(f32::MAX, f32::min);
+        for mut block in scope.blocks.into_values().filter(|b| !is_port_block(&b.script_id)) {
+            block.position = Vec2::new(
+                block.position.x - min_x + instance.position.x,
+                block.position.y - min_y + instance.position.y,
+            );
+            self.blocks.insert(block.id, block);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::test_support::{chain_workflow, TestRegistry, ADD_SCRIPT, GRAPH_PORT_SCRIPTS};
+    use crate::workflow::{migration, BlueprintStorage, WorkflowExecutor};
+
+    /// 只包含加法和子图端口脚本的注册表
+    fn test_registry(name: &str) -> TestRegistry {
+        let [input, output] = GRAPH_PORT_SCRIPTS;
+        TestRegistry::new(&format!("subgraph_{}", name), &[("add.lua", ADD_SCRIPT), input, output])
+    }
+
+    fn result_of(workflow: &Workflow, id: Uuid) -> Option<Value> {
+        workflow.blocks[&id].output_values.get("result").cloned()
+    }
+
+    #[test]
+    fn test_collapse_runs_nested_and_flattens() {
+        let mut registry = test_registry("collapse");
+        let (mut workflow, ids) = chain_workflow(&registry, 4);
+
+        let selected: HashSet<Uuid> = ids[1..3].iter().copied().collect();
+        let instance = workflow.collapse_to_subgraph(&selected, "中段", &registry).unwrap();
+        workflow.register_subgraphs(&mut registry);
+
+        let definition = registry.get(&workflow.blocks[&instance].script_id).unwrap();
+        assert_eq!(definition.inputs.len(), 1);
+        assert_eq!(definition.outputs.len(), 1);
+        assert_eq!(workflow.blocks.len(), 3);
+        assert_eq!(workflow.connections.len(), 2);
+
+        let mut executor = WorkflowExecutor::new().unwrap();
+        executor.execute_all(&mut workflow, &registry).unwrap();
+        assert_eq!(result_of(&workflow, ids[3]), Some(Value::Number(4.0)));
+
+        // 展开后不含子图和端口 Block，结果相同
+        let mut flat = workflow.flatten_subgraphs().unwrap();
+        assert!(flat.subgraphs.is_empty());
+        assert_eq!(flat.blocks.len(), 4);
+        assert!(flat.blocks.values().all(|b| b.script_id == "test.add"));
+        assert_eq!(flat.connections.len(), 3);
+        let mut executor = WorkflowExecutor::new().unwrap();
+        executor.execute_all(&mut flat, &registry).unwrap();
+        assert_eq!(result_of(&flat, ids[3]), Some(Value::Number(4.0)));
+
+        // 在子图内编辑后写回，实例重新执行
+        let subgraph_id = instance_of(&workflow.blocks[&instance].script_id).unwrap();
+        let mut view = workflow.subgraph_view(subgraph_id).unwrap();
+        view.blocks.get_mut(&ids[2]).unwrap().input_values.insert("b".into(), Value::Number(10.0));
+        workflow.apply_subgraph_view(subgraph_id, view);
+        executor.execute_dirty(&mut workflow, &registry).unwrap();
+        assert_eq!(result_of(&workflow, ids[3]), Some(Value::Number(13.0)));
+    }
+
+    #[test]
+    fn test_referenced_subgraph_saves_only_source() {
+        let registry = test_registry("source");
+        let dir = registry.dir();
+        let (library, _) = chain_workflow(&registry, 4);
+        BlueprintStorage::save(&library, &dir.join("library.L"), None).unwrap();
+
+        let mut subgraph = Subgraph::new("引用");
+        subgraph.source = Some("library.L".to_string());
+        let subgraph_id = subgraph.id;
+        let mut workflow = Workflow::new("main");
+        workflow.subgraphs.insert(subgraph_id, subgraph);
+        let path = dir.join("main.L");
+        BlueprintStorage::save(&workflow, &path, None).unwrap();
+
+        let loaded = BlueprintStorage::load(&path, None).unwrap();
+        assert_eq!(loaded.subgraphs[&subgraph_id].blocks.len(), 4);
+        assert_eq!(loaded.subgraphs[&subgraph_id].connections.len(), 3);
+
+        let doc = migration::to_value(&loaded).unwrap();
+        let saved = &doc["subgraphs"][subgraph_id.to_string()];
+        assert_eq!(saved["source"], "library.L");
+        assert!(saved.get("blocks").is_none());
+    }
+}
@@ -0,0 +1,85 @@
+//! 测试辅助 - 临时脚本目录和常用的测试工作流
+
+use crate::script::ScriptRegistry;
+use crate::workflow::{Block, Connection, Vec2, Workflow};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub const ADD_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.add", name = "加法" },
+    inputs = {
+        { id = "a", name = "A", type = "number", default = 0 },
+        { id = "b", name = "B", type = "number", default = 1 },
+    },
+    outputs = { { id = "result", name = "结果", type = "number" } },
+    execute = function(self, inputs)
+        return { result = inputs.a + inputs.b }
+    end
+}
+"#;
+
+/// 子图输入/输出端口脚本
+pub const GRAPH_PORT_SCRIPTS: [(&str, &str); 2] = [
+    ("input.lua", include_str!("../../scripts/graph/input.lua")),
+    ("output.lua", include_str!("../../scripts/graph/output.lua")),
+];
+
+/// 从临时目录加载的脚本注册表（脚本在执行时读取，目录随注册表一起删除）
+pub struct TestRegistry {
+    registry: ScriptRegistry,
+    dir: PathBuf,
+}
+
+impl TestRegistry {
+    /// scripts 为 (文件名, 脚本内容)
+    pub fn new(name: &str, scripts: &[(&str, &str)]) -> Self {
+        let dir = std::env::temp_dir().join(format!("workflow_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, source) in scripts {
+            std::fs::write(dir.join(file), source).unwrap();
+        }
+        let registry = ScriptRegistry::new(&dir).unwrap();
+        Self { registry, dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Deref for TestRegistry {
+    type Target = ScriptRegistry;
+
+    fn deref(&self) -> &ScriptRegistry {
+        &self.registry
+    }
+}
+
+impl DerefMut for TestRegistry {
+    fn deref_mut(&mut self) -> &mut ScriptRegistry {
+        &mut self.registry
+    }
+}
+
+impl Drop for TestRegistry {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// count 个首尾相连的加法Block，返回 (工作流, 按连接顺序的 Block 列表)
+pub fn chain_workflow(registry: &ScriptRegistry, count: usize) -> (Workflow, Vec<Uuid>) {
+    let definition = registry.get("test.add").unwrap();
+    let mut workflow = Workflow::new("chain");
+    let mut ids: Vec<Uuid> = Vec::new();
+    for i in 0..count {
+        let id = workflow.add_block(Block::new(definition, Vec2::new(i as f32 * 200.0, 0.0)));
+        if let Some(prev) = ids.last() {
+            workflow.add_connection(Connection::new(*prev, "result".into(), id, "a".into()), registry).unwrap();
+        }
+        ids.push(id);
+    }
+    (workflow, ids)
+}
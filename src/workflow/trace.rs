@@ -0,0 +1,356 @@
+//! 执行录制 - 记录每个 tick 的Block输入、输出、状态和控件交互，用于回放
+//!
+//! 文件格式为 JSON Lines：第一行是 `TraceHeader`，之后每行一个 `TickRecord`。
+//! 每个Block只记录相对上一次记录变化的值（删除的键记为 null），
+//! 回放时从头累加得到任意 tick 的完整数据，不需要运行 Lua。
+
+use crate::script::Value;
+use crate::workflow::{Block, SimulationClock, Workflow};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+/// 录制文件格式版本
+pub const TRACE_VERSION: u32 = 1;
+
+/// 录制文件扩展名
+pub const TRACE_EXTENSION: &str = "ltrace";
+
+/// 录制文件头
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceHeader {
+    pub version: u32,
+    pub workflow: String,
+    pub seed: u64,
+    pub dt: f64,
+    pub time_scale: f64,
+}
+
+impl TraceHeader {
+    pub fn new(workflow: &Workflow, clock: SimulationClock) -> Self {
+        Self {
+            version: TRACE_VERSION,
+            workflow: workflow.name.clone(),
+            seed: workflow.seed,
+            dt: clock.dt,
+            time_scale: clock.time_scale,
+        }
+    }
+}
+
+/// 控件交互（在下一个 tick 执行前生效）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Interaction {
+    /// 控件写入Block输出
+    SetOutputs { block_id: Uuid, outputs: HashMap<String, Value> },
+    /// 控件触发事件
+    FireEvent { block_id: Uuid, port: String, payload: Value },
+}
+
+impl Interaction {
+    pub fn block_id(&self) -> Uuid {
+        match self {
+            Interaction::SetOutputs { block_id, .. } | Interaction::FireEvent { block_id, .. } => *block_id,
+        }
+    }
+}
+
+/// Block的一次执行记录
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlockRecord {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub state: BTreeMap<String, Value>,
+}
+
+impl BlockRecord {
+    fn of(block: &Block) -> Self {
+        let sorted = |map: &HashMap<String, Value>| map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        Self {
+            inputs: sorted(&block.input_values),
+            outputs: sorted(&block.output_values),
+            state: sorted(&block.state),
+        }
+    }
+
+    /// 相对 previous 变化的值
+    fn delta(&self, previous: &BlockRecord) -> Self {
+        fn diff(new: &BTreeMap<String, Value>, old: &BTreeMap<String, Value>) -> BTreeMap<String, Value> {
+            let mut changed: BTreeMap<String, Value> = new.iter()
+                .filter(|(k, v)| old.get(*k) != Some(*v))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            changed.extend(old.keys().filter(|k| !new.contains_key(*k)).map(|k| (k.clone(), Value::Nil)));
+            changed
+        }
+        Self {
+            inputs: diff(&self.inputs, &previous.inputs),
+            outputs: diff(&self.outputs, &previous.outputs),
+            state: diff(&self.state, &previous.state),
+        }
+    }
+
+    /// 累加一次变化（null 表示删除）
+    fn merge(&mut self, delta: &BlockRecord) {
+        fn apply(target: &mut BTreeMap<String, Value>, delta: &BTreeMap<String, Value>) {
+            for (k, v) in delta {
+                match v {
+                    Value::Nil => target.remove(k),
+                    _ => target.insert(k.clone(), v.clone()),
+                };
+            }
+        }
+        apply(&mut self.inputs, &delta.inputs);
+        apply(&mut self.outputs, &delta.outputs);
+        apply(&mut self.state, &delta.state);
+    }
+}
+
+/// 一个 tick 的记录（blocks 中出现的Block即本 tick 执行过的Block）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TickRecord {
+    pub tick: u64,
+    pub elapsed: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interactions: Vec<Interaction>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub blocks: BTreeMap<Uuid, BlockRecord>,
+}
+
+/// 录制器：边执行边写入文件
+#[derive(Debug)]
+pub struct TraceRecorder {
+    writer: BufWriter<File>,
+    current: Option<TickRecord>,
+    /// 尚未执行的 tick 之前发生的交互
+    pending: Vec<Interaction>,
+    /// 每个Block最近一次记录的完整数据
+    last: HashMap<Uuid, BlockRecord>,
+    /// 第一个写入错误（之后不再写入，结束时返回）
+    error: Option<std::io::Error>,
+    ticks: u64,
+}
+
+impl TraceRecorder {
+    pub fn create(path: &Path, header: &TraceHeader) -> Result<Self> {
+        let file = File::create(path).map_err(|e| anyhow!("创建录制文件失败: {}", e))?;
+        let mut recorder = Self {
+            writer: BufWriter::new(file),
+            current: None,
+            pending: Vec::new(),
+            last: HashMap::new(),
+            error: None,
+            ticks: 0,
+        };
+        recorder.write_line(header);
+        recorder.take_error()?;
+        Ok(recorder)
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = serde_json::to_writer(&mut self.writer, value)
+            .map_err(std::io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    fn take_error(&mut self) -> Result<()> {
+        match self.error.take() {
+            Some(e) => Err(anyhow!("写入录制文件失败: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    fn flush_tick(&mut self) {
+        if let Some(record) = self.current.take() {
+            self.write_line(&record);
+            self.ticks += 1;
+        }
+    }
+
+    /// 开始新的 tick（写出上一个 tick）
+    pub fn begin_tick(&mut self, clock: SimulationClock) {
+        self.flush_tick();
+        self.current = Some(TickRecord {
+            tick: clock.tick,
+            elapsed: clock.elapsed,
+            interactions: std::mem::take(&mut self.pending),
+            blocks: BTreeMap::new(),
+        });
+    }
+
+    /// 记录Block执行后的数据
+    pub fn record_block(&mut self, block: &Block) {
+        let Some(current) = self.current.as_mut() else {
+            return;
+        };
+        let full = BlockRecord::of(block);
+        let delta = full.delta(self.last.get(&block.id).unwrap_or(&BlockRecord::default()));
+        current.blocks.insert(block.id, delta);
+        self.last.insert(block.id, full);
+    }
+
+    /// 记录控件交互
+    pub fn record_interaction(&mut self, interaction: Interaction) {
+        self.pending.push(interaction);
+    }
+
+    /// 写出最后一个 tick 并关闭文件
+    pub fn finish(mut self) -> Result<u64> {
+        self.flush_tick();
+        if self.error.is_none() {
+            if let Err(e) = self.writer.flush() {
+                self.error = Some(e);
+            }
+        }
+        self.take_error()?;
+        Ok(self.ticks)
+    }
+}
+
+/// 未调用 finish 就销毁时（如直接关闭窗口）仍写出最后一个 tick
+impl Drop for TraceRecorder {
+    fn drop(&mut self) {
+        self.flush_tick();
+        let _ = self.writer.flush();
+    }
+}
+
+/// 加载后的录制
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub header: TraceHeader,
+    pub ticks: Vec<TickRecord>,
+}
+
+impl Trace {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| anyhow!("打开录制文件失败: {}", e))?;
+        let mut lines = BufReader::new(file).lines();
+
+        let first = lines.next().ok_or_else(|| anyhow!("录制文件为空"))??;
+        let header: TraceHeader = serde_json::from_str(&first).map_err(|e| anyhow!("录制文件头无效: {}", e))?;
+        if header.version > TRACE_VERSION {
+            return Err(anyhow!("录制格式版本 {} 过新（当前支持 {}），请更新程序", header.version, TRACE_VERSION));
+        }
+
+        let mut ticks = Vec::new();
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line).map_err(|e| anyhow!("第 {} 行无效: {}", index + 2, e))?;
+            ticks.push(record);
+        }
+        Ok(Self { header, ticks })
+    }
+
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    /// 第 index 个 tick 结束时各Block的完整数据
+    pub fn frame(&self, index: usize) -> HashMap<Uuid, BlockRecord> {
+        let mut frame: HashMap<Uuid, BlockRecord> = HashMap::new();
+        for record in self.ticks.iter().take(index + 1) {
+            for (id, delta) in &record.blocks {
+                frame.entry(*id).or_default().merge(delta);
+            }
+        }
+        frame
+    }
+
+    /// 将第 index 个 tick 的数据写入工作流，并激活该 tick 执行过的Block（流动动画）
+    pub fn apply(&self, index: usize, workflow: &mut Workflow) {
+        let Some(record) = self.ticks.get(index) else {
+            return;
+        };
+        for (id, data) in self.frame(index) {
+            let Some(block) = workflow.blocks.get_mut(&id) else {
+                continue;
+            };
+            block.input_values = data.inputs.into_iter().collect();
+            block.output_values = data.outputs.into_iter().collect();
+            block.state = data.state.into_iter().collect();
+            if let Some(Value::Object(animation)) = block.state.get("_animation") {
+                let number = |key: &str| animation.get(key).and_then(Value::as_number).map(|v| v as f32);
+                let (x, y, speed) = (number("x").unwrap_or(0.0), number("y").unwrap_or(0.0), number("speed"));
+                block.set_animation_target(x, y, speed);
+            }
+        }
+        for id in record.blocks.keys() {
+            if workflow.blocks.contains_key(id) {
+                workflow.activate_block(*id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_round_trip_with_deltas() {
+        let path = std::env::temp_dir().join(format!("trace_round_trip_{}.ltrace", std::process::id()));
+        let mut workflow = Workflow::new("trace");
+        let mut block: Block = serde_json::from_value(serde_json::json!({
+            "id": Uuid::new_v4(), "script_id": "lite.boss",
+            "position": { "x": 0.0, "y": 0.0 }, "size": { "x": 140.0, "y": 60.0 },
+            "input_values": {}, "properties": {}, "state": {}, "collapsed": false, "group_id": null
+        }))
+        .unwrap();
+        let id = block.id;
+        workflow.blocks.insert(id, block.clone());
+
+        let mut clock = SimulationClock::default();
+        let mut recorder = TraceRecorder::create(&path, &TraceHeader::new(&workflow, clock)).unwrap();
+        for hp in [100.0, 90.0, 90.0] {
+            clock.advance();
+            recorder.begin_tick(clock);
+            block.output_values.insert("hp".into(), Value::Number(hp));
+            block.state.insert("hits".into(), Value::Number(100.0 - hp));
+            recorder.record_block(&block);
+        }
+        recorder.record_interaction(Interaction::FireEvent { block_id: id, port: "click".into(), payload: Value::Boolean(true) });
+        clock.advance();
+        recorder.begin_tick(clock);
+        block.state.remove("hits");
+        recorder.record_block(&block);
+        assert_eq!(recorder.finish().unwrap(), 4);
+
+        let trace = Trace::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(trace.header.workflow, "trace");
+        assert_eq!(trace.len(), 4);
+        // 没有变化的 tick 只记录Block执行过
+        assert_eq!(trace.ticks[2].blocks[&id], BlockRecord::default());
+        assert_eq!(trace.ticks[3].interactions.len(), 1);
+        assert_eq!(trace.ticks[3].blocks[&id].state["hits"], Value::Nil);
+
+        trace.apply(1, &mut workflow);
+        assert_eq!(workflow.blocks[&id].output_values["hp"], Value::Number(90.0));
+        assert_eq!(workflow.blocks[&id].state["hits"], Value::Number(10.0));
+        trace.apply(3, &mut workflow);
+        assert!(!workflow.blocks[&id].state.contains_key("hits"));
+        assert!(workflow.active_blocks.contains_key(&id));
+    }
+}
@@ -0,0 +1,521 @@
+//! 后台执行线程 - 在独立线程中运行执行引擎
+//!
+//! Lua 运行时不能跨线程移动，因此执行引擎和脚本注册表都在工作线程内创建。
+//! UI 线程通过命令通道同步工作流编辑，工作线程每个 tick 结束后
+//! 通过快照通道回传输出、状态等运行时数据，慢脚本不会阻塞界面渲染。
+
+use crate::script::{PortDefinition, ScriptRegistry, Value};
+use crate::workflow::{
+    FixedStep, Interaction, Pause, SimulationClock, StepMode, TickStatus, TraceRecorder, Vec2, Workflow, WorkflowExecutor,
+    DEFAULT_HISTORY_CAPACITY,
+};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use uuid::Uuid;
+
+/// UI 线程发往工作线程的命令
+#[derive(Debug)]
+pub enum ExecutorCommand {
+    /// 同步编辑后的工作流（保留工作线程中已有Block的运行时数据）
+    Sync(Box<Workflow>),
+    /// 替换整个工作流（打开文件、撤销/重做，运行时数据以新工作流为准）
+    Load(Box<Workflow>),
+    /// 写入Block输出（交互控件）
+    SetOutputs { block_id: Uuid, outputs: HashMap<String, Value> },
+    /// 向Block的输入端口投递事件（交互控件），立即执行
+    FireEvent { block_id: Uuid, port: String, payload: Value },
+    /// 热重载脚本文件
+    ReloadScript(PathBuf),
+    /// 清除故障标记，None 表示全部
+    ClearFaults(Option<String>),
+    /// 开始/暂停自动执行
+    SetRunning(bool),
+    /// 自动执行频率 (Hz)
+    SetSpeed(f32),
+    /// 每个 tick 的模拟步长（秒）
+    SetDt(f64),
+    /// 模拟时间缩放
+    SetTimeScale(f64),
+    /// 立即推进执行：Tick 执行完一个 tick（忽略断点），Block 停在下一个要执行的Block之前，
+    /// Continue 执行到下一个断点（停在断点处时恢复自动执行）
+    Step(StepMode),
+    /// 回到执行历史中某个 tick 结束时的状态（结束正在进行的录制）
+    Rewind(u64),
+    /// 开始录制执行过程
+    StartTrace(Box<TraceRecorder>),
+    /// 结束录制并写出文件
+    StopTrace,
+    /// 结束工作线程
+    Shutdown,
+}
+
+/// 单个Block的运行时数据
+#[derive(Debug, Clone)]
+pub struct BlockSnapshot {
+    pub input_values: HashMap<String, Value>,
+    pub output_values: HashMap<String, Value>,
+    pub state: HashMap<String, Value>,
+    pub dynamic_outputs: Vec<PortDefinition>,
+    pub fault: Option<String>,
+    pub warnings: Vec<String>,
+    pub waiting_for: Vec<String>,
+    pub animation_target: Vec2,
+    pub animation_speed: f32,
+}
+
+/// 一次执行后的快照
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionSnapshot {
+    pub blocks: HashMap<Uuid, BlockSnapshot>,
+    /// 本次执行的Block（用于流动动画）
+    pub executed: Vec<Uuid>,
+    /// 执行错误
+    pub error: Option<String>,
+    /// 执行后的模拟时钟
+    pub clock: SimulationClock,
+    /// 停在断点处时的Block信息
+    pub paused: Option<Pause>,
+    /// 执行历史中最早和最晚的 tick
+    pub history: Option<(u64, u64)>,
+}
+
+impl ExecutionSnapshot {
+    /// 将运行时数据写回 UI 线程的工作流
+    pub fn apply(self, workflow: &mut Workflow, registry: &ScriptRegistry) {
+        for (id, snapshot) in self.blocks {
+            let Some(block) = workflow.blocks.get_mut(&id) else {
+                continue;
+            };
+            block.input_values = snapshot.input_values;
+            block.output_values = snapshot.output_values;
+            block.state = snapshot.state;
+            block.dynamic_outputs = snapshot.dynamic_outputs;
+            block.fault = snapshot.fault;
+            block.warnings = snapshot.warnings;
+            block.waiting_for = snapshot.waiting_for;
+            block.animation_target = snapshot.animation_target;
+            block.animation_speed = snapshot.animation_speed;
+            if let Some(definition) = registry.get(&block.script_id) {
+                block.recalculate_size(definition);
+            }
+        }
+
+        for id in self.executed {
+            if workflow.blocks.contains_key(&id) {
+                workflow.activate_block(id);
+            }
+        }
+    }
+}
+
+/// 后台执行线程句柄（销毁时结束线程）
+pub struct ExecutorHandle {
+    commands: Sender<ExecutorCommand>,
+    snapshots: Receiver<ExecutionSnapshot>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ExecutorHandle {
+    /// 启动工作线程，脚本注册表从 script_dir 重新加载
+    pub fn spawn(script_dir: PathBuf) -> Result<Self> {
+        let (command_tx, command_rx) = mpsc::channel();
+        let (snapshot_tx, snapshot_rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let thread = std::thread::Builder::new()
+            .name("workflow-executor".to_string())
+            .spawn(move || {
+                let worker = ScriptRegistry::new(&script_dir)
+                    .and_then(|registry| Ok(Worker::new(registry, WorkflowExecutor::new()?, snapshot_tx)));
+                match worker {
+                    Ok(worker) => {
+                        let _ = ready_tx.send(Ok(()));
+                        worker.run(command_rx);
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e.to_string()));
+                    }
+                }
+            })
+            .map_err(|e| anyhow!("启动执行线程失败: {}", e))?;
+
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow!("执行线程意外退出"))?
+            .map_err(|e| anyhow!("初始化执行线程失败: {}", e))?;
+
+        Ok(Self {
+            commands: command_tx,
+            snapshots: snapshot_rx,
+            thread: Some(thread),
+        })
+    }
+
+    /// 发送命令（线程已退出时忽略）
+    pub fn send(&self, command: ExecutorCommand) {
+        if self.commands.send(command).is_err() {
+            log::error!("执行线程已退出");
+        }
+    }
+
+    /// 取出所有未处理的快照
+    pub fn poll(&self) -> Vec<ExecutionSnapshot> {
+        self.snapshots.try_iter().collect()
+    }
+}
+
+impl Drop for ExecutorHandle {
+    fn drop(&mut self) {
+        let _ = self.commands.send(ExecutorCommand::Shutdown);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// 工作线程状态
+struct Worker {
+    registry: ScriptRegistry,
+    executor: WorkflowExecutor,
+    workflow: Workflow,
+    snapshots: Sender<ExecutionSnapshot>,
+    running: bool,
+    step: FixedStep,
+    /// 待执行的单步命令
+    pending_step: Option<StepMode>,
+}
+
+impl Worker {
+    fn new(registry: ScriptRegistry, mut executor: WorkflowExecutor, snapshots: Sender<ExecutionSnapshot>) -> Self {
+        executor.set_history_capacity(DEFAULT_HISTORY_CAPACITY);
+        Self {
+            registry,
+            executor,
+            workflow: Workflow::default(),
+            snapshots,
+            running: false,
+            step: FixedStep::new(10.0),
+            pending_step: None,
+        }
+    }
+
+    fn run(mut self, commands: Receiver<ExecutorCommand>) {
+        loop {
+            // 等待下一条命令或下一个 tick（停在断点处时只等待命令）
+            let command = if self.running && !self.executor.is_paused() {
+                match commands.recv_timeout(self.step.until_next()) {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                match commands.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => return,
+                }
+            };
+
+            // 合并积压的命令，只执行一次
+            let mut execute_now = false;
+            for command in command.into_iter().chain(commands.try_iter()) {
+                // 回退后停在目标 tick，之前的命令不再触发执行
+                if matches!(command, ExecutorCommand::Rewind(_)) {
+                    execute_now = false;
+                }
+                match self.handle(command) {
+                    Some(execute) => execute_now |= execute,
+                    None => return,
+                }
+            }
+
+            if let Some(mode) = self.pending_step.take() {
+                self.execute(mode);
+                self.step.restart();
+                continue;
+            }
+
+            // 停在断点处时编辑只同步，不执行
+            if self.executor.is_paused() {
+                continue;
+            }
+
+            // 固定步长：按经过的时间补足 tick，脏Block在第一个 tick 中执行
+            let due = if self.running { self.step.due() } else { 0 };
+            if due == 0 && execute_now {
+                // 命令触发的执行留在当前 tick 内，不推进时钟
+                self.rerun();
+            }
+            for _ in 0..due {
+                self.execute(StepMode::Continue);
+                if self.executor.is_paused() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// 处理命令，返回是否需要立即执行；None 表示退出
+    fn handle(&mut self, command: ExecutorCommand) -> Option<bool> {
+        match command {
+            ExecutorCommand::Sync(mut workflow) => {
+                adopt_runtime_state(&mut workflow, &self.workflow);
+                self.workflow = *workflow;
+                self.workflow.register_subgraphs(&mut self.registry);
+                return Some(!self.workflow.dirty_blocks.is_empty());
+            }
+            ExecutorCommand::Load(workflow) => {
+                self.workflow = *workflow;
+                self.workflow.register_subgraphs(&mut self.registry);
+                self.workflow.update_execution_order();
+                self.executor.restart();
+                // 新工作流的所有Block都需要执行一次
+                self.workflow.dirty_blocks.extend(self.workflow.blocks.keys().copied());
+                return Some(true);
+            }
+            ExecutorCommand::SetOutputs { block_id, outputs } => {
+                self.executor.record_interaction(Interaction::SetOutputs { block_id, outputs: outputs.clone() });
+                if let Some(block) = self.workflow.blocks.get_mut(&block_id) {
+                    block.output_values.extend(outputs);
+                }
+            }
+            ExecutorCommand::FireEvent { block_id, port, payload } => {
+                self.executor.record_interaction(Interaction::FireEvent { block_id, port: port.clone(), payload: payload.clone() });
+                self.workflow.fire_event(block_id, &port, payload);
+                return Some(true);
+            }
+            ExecutorCommand::ReloadScript(path) => match self.registry.reload_script(&path) {
+                Ok(script_id) => {
+                    self.executor.invalidate_script(&script_id);
+                    self.workflow.clear_faults(Some(&script_id));
+                    let reloaded: Vec<Uuid> = self.workflow.blocks.values()
+                        .filter(|b| b.script_id == script_id)
+                        .map(|b| b.id)
+                        .collect();
+                    for id in reloaded {
+                        self.workflow.mark_dirty(id);
+                    }
+                    return Some(true);
+                }
+                Err(e) => log::error!("执行线程重载失败: {}", e),
+            },
+            ExecutorCommand::ClearFaults(script_id) => {
+                self.workflow.clear_faults(script_id.as_deref());
+                return Some(!self.workflow.dirty_blocks.is_empty());
+            }
+            ExecutorCommand::SetRunning(running) => {
+                self.running = running;
+                self.step.restart();
+            }
+            ExecutorCommand::SetSpeed(hz) => self.step.set_rate(hz),
+            ExecutorCommand::SetDt(dt) => self.executor.set_dt(dt),
+            ExecutorCommand::SetTimeScale(scale) => self.executor.set_time_scale(scale),
+            ExecutorCommand::Step(mode) => self.pending_step = Some(mode),
+            ExecutorCommand::Rewind(tick) => {
+                self.stop_trace();
+                let error = self.executor.rewind(&mut self.workflow, tick).err().map(|e| e.to_string());
+                self.publish(None, error);
+            }
+            ExecutorCommand::StartTrace(recorder) => {
+                self.stop_trace();
+                self.executor.start_trace(*recorder);
+            }
+            ExecutorCommand::StopTrace => self.stop_trace(),
+            ExecutorCommand::Shutdown => {
+                self.stop_trace();
+                return None;
+            }
+        }
+        Some(false)
+    }
+
+    /// 结束正在进行的录制
+    fn stop_trace(&mut self) {
+        if let Some(recorder) = self.executor.take_trace() {
+            match recorder.finish() {
+                Ok(ticks) => log::info!("录制结束: {} 个 tick", ticks),
+                Err(e) => log::error!("录制失败: {}", e),
+            }
+        }
+    }
+
+    /// 执行脏Block并发布快照
+    fn execute(&mut self, mode: StepMode) {
+        if self.workflow.blocks.is_empty() && !self.executor.is_paused() {
+            self.workflow.dirty_blocks.clear();
+            return;
+        }
+
+        let was_paused = self.executor.is_paused();
+        let (paused, error) = match self.executor.run_tick(&mut self.workflow, &self.registry, mode) {
+            Ok(TickStatus::Paused(pause)) => (Some(pause), None),
+            Ok(TickStatus::Completed) => (None, None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        // 没有Block执行且暂停状态未变化时不发布快照
+        if self.workflow.active_blocks.is_empty() && error.is_none() && paused.is_none() && !was_paused {
+            return;
+        }
+        self.publish(paused, error);
+    }
+
+    /// 在当前 tick 内执行脏Block并发布快照
+    fn rerun(&mut self) {
+        if self.workflow.blocks.is_empty() {
+            self.workflow.dirty_blocks.clear();
+            return;
+        }
+
+        let error = self.executor.rerun_dirty(&mut self.workflow, &self.registry).err().map(|e| e.to_string());
+        if self.workflow.active_blocks.is_empty() && error.is_none() {
+            return;
+        }
+        self.publish(None, error);
+    }
+
+    /// 发布当前运行时数据
+    fn publish(&mut self, paused: Option<Pause>, error: Option<String>) {
+        let snapshot = ExecutionSnapshot {
+            blocks: self
+                .workflow
+                .blocks
+                .iter()
+                .map(|(id, block)| {
+                    (
+                        *id,
+                        BlockSnapshot {
+                            input_values: block.input_values.clone(),
+                            output_values: block.output_values.clone(),
+                            state: block.state.clone(),
+                            dynamic_outputs: block.dynamic_outputs.clone(),
+                            fault: block.fault.clone(),
+                            warnings: block.warnings.clone(),
+                            waiting_for: block.waiting_for.clone(),
+                            animation_target: block.animation_target,
+                            animation_speed: block.animation_speed,
+                        },
+                    )
+                })
+                .collect(),
+            executed: self.workflow.active_blocks.keys().copied().collect(),
+            error,
+            clock: self.executor.clock(),
+            paused,
+            history: self.executor.history().range(),
+        };
+        self.workflow.active_blocks.clear();
+        self.workflow.active_connections.clear();
+
+        let _ = self.snapshots.send(snapshot);
+    }
+}
+
+/// 保留工作线程中已有Block的运行时数据（UI 线程的副本可能落后若干 tick）
+/// 属性被修改的Block清除故障标记，重新尝试执行
+fn adopt_runtime_state(workflow: &mut Workflow, previous: &Workflow) {
+    workflow.pending_events = previous.pending_events.iter()
+        .filter(|((id, _), _)| workflow.blocks.contains_key(id))
+        .map(|(key, payload)| (key.clone(), payload.clone()))
+        .collect();
+    workflow.feedback_events = previous.feedback_events.iter()
+        .filter(|((id, _), _)| workflow.blocks.contains_key(id))
+        .map(|(key, payload)| (key.clone(), payload.clone()))
+        .collect();
+    workflow.feedback_values = previous.feedback_values.iter()
+        .filter(|(id, _)| workflow.connections.get(*id).is_some_and(|c| c.delayed))
+        .map(|(id, value)| (*id, value.clone()))
+        .collect();
+    // 编辑中断开的输入端口不沿用旧值
+    let connected = |workflow: &Workflow, id: Uuid, port: &str| {
+        workflow.connections.values().any(|c| c.to_block == id && c.to_port == port)
+    };
+    let disconnected: HashSet<(Uuid, String)> = previous.connections.values()
+        .filter(|c| !connected(workflow, c.to_block, &c.to_port))
+        .map(|c| (c.to_block, c.to_port.clone()))
+        .collect();
+    for (id, block) in workflow.blocks.iter_mut() {
+        let Some(old) = previous.blocks.get(id) else {
+            continue;
+        };
+        if old.script_id != block.script_id {
+            continue;
+        }
+        block.input_values = old.input_values.iter()
+            .filter(|(port, _)| !disconnected.contains(&(*id, (*port).clone())))
+            .map(|(port, value)| (port.clone(), value.clone()))
+            .collect();
+        block.output_values = old.output_values.clone();
+        block.state = old.state.clone();
+        block.dynamic_outputs = old.dynamic_outputs.clone();
+        block.warnings = old.warnings.clone();
+        block.waiting_for = old.waiting_for.clone();
+        block.animation_target = old.animation_target;
+        block.animation_speed = old.animation_speed;
+        block.fault = if old.properties == block.properties {
+            old.fault.clone()
+        } else {
+            None
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::test_support::TestRegistry;
+    use crate::workflow::Block;
+    use std::time::{Duration, Instant};
+
+    const SLOW_SCRIPT: &str = r#"
+return {
+    meta = { id = "test.slow", name = "慢脚本", always_run = true },
+    outputs = { { id = "count", name = "计数", type = "number" } },
+    execute = function(self, inputs)
+        local start = os.clock()
+        while os.clock() - start < 0.2 do end
+        self.state.count = (self.state.count or 0) + 1
+        return { count = self.state.count }
+    end
+}
+"#;
+
+    #[test]
+    fn test_worker_does_not_block_caller() {
+        let registry = TestRegistry::new("worker", &[("slow.lua", SLOW_SCRIPT)]);
+
+        let mut workflow = Workflow::new("test");
+        let id = workflow.add_block(Block::new(registry.get("test.slow").unwrap(), Vec2::new(0.0, 0.0)));
+
+        let handle = ExecutorHandle::spawn(registry.dir().to_path_buf()).unwrap();
+        let wait_for = |count: usize, snapshots: &mut Vec<ExecutionSnapshot>| {
+            let start = Instant::now();
+            while snapshots.len() < count && start.elapsed() < Duration::from_secs(10) {
+                snapshots.extend(handle.poll());
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        // 新增的Block是脏的，同步后立即执行一次
+        let mut snapshots = Vec::new();
+        handle.send(ExecutorCommand::Sync(Box::new(workflow.clone())));
+        wait_for(1, &mut snapshots);
+
+        // 脚本执行期间调用方不会被阻塞
+        let start = Instant::now();
+        handle.send(ExecutorCommand::Step(StepMode::Tick));
+        snapshots.extend(handle.poll());
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        wait_for(2, &mut snapshots);
+        assert_eq!(snapshots.len(), 2);
+
+        for snapshot in snapshots {
+            snapshot.apply(&mut workflow, &registry);
+        }
+        assert_eq!(workflow.blocks[&id].get_output("count").and_then(|v| v.as_number()), Some(2.0));
+        assert!(workflow.get_block_activation(id) > 0.0);
+    }
+}